@@ -0,0 +1,527 @@
+//! Bloom Filter
+//!
+//! `bloom` provides [BloomFilter](struct.BloomFilter.html) - a probabilistic take on the
+//! 'Uniqueness' check from earlier in this module.
+//!
+//! Rather than store the keys themselves, a Bloom filter keeps an array of `m` bits. To insert a
+//! key, `k` different hash functions each pick a bit, and those bits are set. To check a key, the
+//! same `k` bits are examined:
+//!
+//! * If any of them is unset, the key was definitely never inserted
+//! * If all of them are set, the key was *probably* inserted - or other keys happened to set the
+//!   same bits between them, a false positive
+//!
+//! ```text
+//!  insert "foo" ==> bits 1, 4, 6          contains "bar" ==> bits 1, 3, 6
+//!
+//!  [0][1][0][0][1][0][1][0]               [0][1][0][0][1][0][1][0]
+//!   0  1  2  3  4  5  6  7                    ^     ^     ^
+//!                                             bit 3 is unset - "bar" is definitely absent
+//! ```
+//!
+//! There are no false negatives, and the false positive rate is set by how many bits we give
+//! each key. For `n` expected keys and a target false positive rate `p`, the best choices are:
+//!
+//! ```text
+//!        n · ln(p)                m
+//!  m = - ---------          k = - · ln(2)
+//!         ln(2)²                  n
+//! ```
+//!
+//! The `k` hash functions are derived from a single 64-bit hash by double hashing - the `i`th
+//! bit is `h₁ + i · h₂ (mod m)` - which performs as well as `k` independent functions would. So
+//! that no bit is chosen twice, `h₂` is nudged until it shares no factor with `m`: stepping by it
+//! then visits every one of the `m` bits before coming back round.
+
+use std::error::Error;
+use std::fmt;
+
+use super::hashing::{mix, Fnv1a, SlotHasher, TaggedHasher};
+
+const WORD_BITS: usize = 64;
+const HEADER_LEN: usize = 24;
+
+/// The ways in which inserting into, combining or decoding filters and sketches can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterError {
+    /// The filters or sketches were built with different sizes, hash functions or hashers
+    Incompatible,
+    /// The bytes do not describe a filter
+    Malformed,
+    /// The filter has no room left for another key
+    Full,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterError::Incompatible => write!(f, "filters were built with different parameters"),
+            FilterError::Malformed => write!(f, "bytes do not describe a filter"),
+            FilterError::Full => write!(f, "filter has no room for another key"),
+        }
+    }
+}
+
+impl Error for FilterError {}
+
+/// A Bloom filter
+///
+/// Answers whether a key has been inserted with either 'definitely not' or 'probably', using a
+/// fixed number of bits regardless of the size of the keys.
+///
+/// Should be expected to have performance characteristics of `O(k)` for inserts and lookups,
+/// where `k` is the number of hash functions.
+///
+/// Keys are hashed with [Fnv1a](struct.Fnv1a.html) unless another
+/// [SlotHasher](trait.SlotHasher.html) is supplied - its hashes don't change between Rust
+/// releases, so a filter encoded with [to_bytes](struct.BloomFilter.html#method.to_bytes) and
+/// decoded by a later build still finds every key inserted into it. Keys whose `Hash`
+/// implementation writes integers - `usize`s included - hash differently on targets of another
+/// byte order or pointer width, so such filters should be decoded on the same kind of target.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = BloomFilter::new(1000, 0.01);
+/// phone_book.insert("John");
+///
+/// assert!(phone_book.contains("John"));
+/// assert!(!phone_book.contains("Ringo")); // almost certainly!
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter<S = Fnv1a> {
+    bits: Vec<u64>,
+    num_bits: usize,
+    num_hashes: u32,
+    hasher: S,
+}
+
+impl BloomFilter<Fnv1a> {
+    /// Creates a filter sized to hold `expected_items` keys at the given false positive rate
+    ///
+    /// See [with_hasher](struct.BloomFilter.html#method.with_hasher).
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        Self::with_hasher(expected_items, false_positive_rate, Fnv1a)
+    }
+}
+
+impl<S> BloomFilter<S> {
+    /// Creates a filter sized to hold `expected_items` keys at the given false positive rate,
+    /// hashing keys with `hasher`
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_items` - the number of keys the filter is sized for
+    /// * `false_positive_rate` - the chance, between `0` and `1` (exclusive), of a false positive
+    ///   once `expected_items` keys have been inserted
+    /// * `hasher` - the hash function the filter's `k` functions are derived from
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_rate` is not strictly between `0` and `1`.
+    pub fn with_hasher(expected_items: usize, false_positive_rate: f64, hasher: S) -> Self {
+        let (num_bits, num_hashes) = optimal_parameters(expected_items, false_positive_rate);
+        Self::with_parameters(num_bits, num_hashes, hasher)
+    }
+
+    /// Creates a filter with exactly `num_bits` bits and `num_hashes` hash functions
+    ///
+    /// # Panics
+    ///
+    /// Panics if either `num_bits` or `num_hashes` is zero.
+    pub fn with_parameters(num_bits: usize, num_hashes: u32, hasher: S) -> Self {
+        assert!(num_bits > 0, "a Bloom filter needs at least one bit");
+        assert!(
+            num_hashes > 0,
+            "a Bloom filter needs at least one hash function"
+        );
+
+        BloomFilter {
+            bits: vec![0; num_bits.div_ceil(WORD_BITS)],
+            num_bits,
+            num_hashes,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the filter's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of bits in the filter
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Returns the number of hash functions applied to each key
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// Returns the number of bits currently set
+    pub fn count_ones(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns `true` if no bits are set
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0)
+    }
+
+    /// Estimates the number of distinct keys inserted, from the fraction of bits set
+    pub fn estimated_len(&self) -> f64 {
+        let (m, k) = (self.num_bits as f64, f64::from(self.num_hashes));
+        let set = self.count_ones() as f64;
+        if set >= m {
+            return f64::INFINITY;
+        }
+        -(m / k) * (1.0 - set / m).ln()
+    }
+
+    /// Estimates the current false positive rate, from the fraction of bits set
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        (self.count_ones() as f64 / self.num_bits as f64).powi(self.num_hashes as i32)
+    }
+
+    /// Inserts a key - returns `true` if it was definitely not present before
+    pub fn insert<K>(&mut self, key: &K) -> bool
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let hash = self.hasher.hash(key);
+        let mut added = false;
+        for bit in bit_indexes(hash, self.num_hashes, self.num_bits) {
+            let (word, mask) = (bit / WORD_BITS, 1 << (bit % WORD_BITS));
+            added |= self.bits[word] & mask == 0;
+            self.bits[word] |= mask;
+        }
+        added
+    }
+
+    /// Returns `false` if the key was definitely never inserted, and `true` if it probably was
+    pub fn contains<K>(&self, key: &K) -> bool
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let hash = self.hasher.hash(key);
+        bit_indexes(hash, self.num_hashes, self.num_bits)
+            .all(|bit| self.bits[bit / WORD_BITS] & (1 << (bit % WORD_BITS)) != 0)
+    }
+
+    /// Unsets every bit
+    pub fn clear(&mut self) {
+        for word in self.bits.iter_mut() {
+            *word = 0;
+        }
+    }
+
+    /// Adds every key of `other` to this filter - afterwards it is exactly the filter that would
+    /// have been built by inserting the keys of both
+    ///
+    /// The filters must share a size, number of hash functions and hasher.
+    pub fn union_with(&mut self, other: &Self) -> Result<(), FilterError>
+    where
+        S: PartialEq,
+    {
+        self.check_compatible(other)?;
+        for (word, theirs) in self.bits.iter_mut().zip(&other.bits) {
+            *word |= theirs;
+        }
+        Ok(())
+    }
+
+    /// Keeps only the bits set in both filters - afterwards it answers `true` for any key
+    /// inserted into both, though it may report more false positives than a filter built
+    /// from just those keys
+    ///
+    /// The filters must share a size, number of hash functions and hasher.
+    pub fn intersect_with(&mut self, other: &Self) -> Result<(), FilterError>
+    where
+        S: PartialEq,
+    {
+        self.check_compatible(other)?;
+        for (word, theirs) in self.bits.iter_mut().zip(&other.bits) {
+            *word &= theirs;
+        }
+        Ok(())
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), FilterError>
+    where
+        S: PartialEq,
+    {
+        if self.num_bits == other.num_bits
+            && self.num_hashes == other.num_hashes
+            && self.hasher == other.hasher
+        {
+            Ok(())
+        } else {
+            Err(FilterError::Incompatible)
+        }
+    }
+}
+
+impl<S: TaggedHasher> BloomFilter<S> {
+    /// Encodes the filter as bytes
+    ///
+    /// The layout is the hasher's [tag](trait.TaggedHasher.html), the number of bits (`u64`), the
+    /// number of hash functions (`u64`), then the bit array as `u64` words - all little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.bits.len() * 8);
+        bytes.extend_from_slice(&S::TAG);
+        bytes.extend_from_slice(&(self.num_bits as u64).to_le_bytes());
+        bytes.extend_from_slice(&u64::from(self.num_hashes).to_le_bytes());
+        for word in &self.bits {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a filter encoded by [to_bytes](struct.BloomFilter.html#method.to_bytes)
+    ///
+    /// # Errors
+    ///
+    /// Returns [FilterError::Incompatible](enum.FilterError.html#variant.Incompatible) if the
+    /// filter was encoded with a different hasher, as it would not find the keys inserted into it,
+    /// and [FilterError::Malformed](enum.FilterError.html#variant.Malformed) if the bytes do not
+    /// describe a filter.
+    pub fn from_bytes(bytes: &[u8], hasher: S) -> Result<Self, FilterError> {
+        if bytes.len() < HEADER_LEN || !(bytes.len() - HEADER_LEN).is_multiple_of(8) {
+            return Err(FilterError::Malformed);
+        }
+        if bytes[0..8] != S::TAG {
+            return Err(FilterError::Incompatible);
+        }
+
+        let num_bits = read_u64(&bytes[8..16]) as usize;
+        let num_hashes = read_u64(&bytes[16..24]);
+        let bits: Vec<u64> = bytes[HEADER_LEN..].chunks(8).map(read_u64).collect();
+
+        let valid = num_bits > 0
+            && num_hashes > 0
+            && num_hashes <= u64::from(u32::MAX)
+            && bits.len() == num_bits.div_ceil(WORD_BITS);
+        if !valid {
+            return Err(FilterError::Malformed);
+        }
+
+        Ok(BloomFilter {
+            bits,
+            num_bits,
+            num_hashes: num_hashes as u32,
+            hasher,
+        })
+    }
+}
+
+/// The optimal number of bits and hash functions for `expected_items` keys at the given
+/// false positive rate
+pub(crate) fn optimal_parameters(expected_items: usize, false_positive_rate: f64) -> (usize, u32) {
+    assert!(
+        false_positive_rate > 0.0 && false_positive_rate < 1.0,
+        "false_positive_rate must be between 0 and 1 (exclusive), got {}",
+        false_positive_rate
+    );
+
+    let n = expected_items.max(1) as f64;
+    let ln2 = std::f64::consts::LN_2;
+    let num_bits = (-n * false_positive_rate.ln() / (ln2 * ln2))
+        .ceil()
+        .max(1.0);
+    let num_hashes = (num_bits / n * ln2).round().max(1.0);
+    (num_bits as usize, num_hashes as u32)
+}
+
+/// The `k` positions chosen for a key by double hashing its 64-bit hash
+///
+/// Both hashes are reduced modulo `m` before stepping, so the positions form an arithmetic
+/// progression modulo `m` - and a step coprime to `m` keeps the first `m` of them distinct.
+pub(crate) fn bit_indexes(hash: u64, k: u32, m: usize) -> impl Iterator<Item = usize> {
+    let m = m as u64;
+    let mut bit = hash % m;
+    let step = coprime_step(mix(hash, 0) % m, m);
+    (0..k).map(move |_| {
+        let current = bit;
+        // Both are below `m`, so the sum cannot overflow for any `m` a `Vec` can hold
+        bit = (bit + step) % m;
+        current as usize
+    })
+}
+
+/// The first step from `start` upwards, wrapping within `1..m`, that shares no factor with `m`
+fn coprime_step(start: u64, m: u64) -> u64 {
+    if m == 1 {
+        return 0;
+    }
+    let mut step = start.max(1);
+    while gcd(step, m) != 1 {
+        step = if step + 1 < m { step + 1 } else { 1 };
+    }
+    step
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(bytes);
+    u64::from_le_bytes(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::hashing::LengthHasher;
+    use super::*;
+
+    #[test]
+    fn inserted_keys_are_always_found() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        assert!((0..1000).all(|i| filter.contains(&i)))
+    }
+
+    #[test]
+    fn false_positive_rate_is_near_target() {
+        let mut filter = BloomFilter::new(10_000, 0.01);
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+        let false_positives = (10_000..110_000).filter(|i| filter.contains(i)).count();
+        let rate = false_positives as f64 / 100_000.0;
+        assert!(rate < 0.02, "{}", rate);
+        assert!((filter.estimated_false_positive_rate() - 0.01).abs() < 0.005)
+    }
+
+    #[test]
+    fn a_key_never_chooses_the_same_bit_twice() {
+        for &m in &[7, 64, 100, 9586, 1 << 16] {
+            for hash in (0..2000u64).map(|i| mix(i, 42)) {
+                let mut bits: Vec<usize> = bit_indexes(hash, 7, m).collect();
+                assert!(bits.iter().all(|bit| *bit < m));
+                bits.sort_unstable();
+                bits.dedup();
+                assert_eq!(bits.len(), 7, "m = {}, hash = {}", m, hash);
+            }
+        }
+    }
+
+    #[test]
+    fn sizing_follows_the_optimal_formulas() {
+        let filter = BloomFilter::new(1000, 0.01);
+        assert_eq!(filter.num_bits(), 9586);
+        assert_eq!(filter.num_hashes(), 7)
+    }
+
+    #[test]
+    fn insert_reports_whether_key_was_new() {
+        let mut filter = BloomFilter::new(100, 0.01);
+        assert!(filter.insert("John"));
+        assert!(!filter.insert("John"))
+    }
+
+    #[test]
+    fn estimated_len_tracks_distinct_inserts() {
+        let mut filter = BloomFilter::new(10_000, 0.01);
+        for i in 0..5000 {
+            filter.insert(&i);
+            filter.insert(&i);
+        }
+        assert!((filter.estimated_len() - 5000.0).abs() < 250.0)
+    }
+
+    #[test]
+    fn union_contains_keys_of_both() {
+        let mut left = BloomFilter::new(100, 0.01);
+        let mut right = BloomFilter::new(100, 0.01);
+        left.insert("John");
+        right.insert("Ringo");
+        left.union_with(&right).unwrap();
+        assert!(left.contains("John") && left.contains("Ringo"))
+    }
+
+    #[test]
+    fn intersection_contains_shared_keys() {
+        let mut left = BloomFilter::new(100, 0.001);
+        let mut right = BloomFilter::new(100, 0.001);
+        for name in &["John", "Paul", "George"] {
+            left.insert(*name);
+        }
+        for name in &["George", "Ringo"] {
+            right.insert(*name);
+        }
+        left.intersect_with(&right).unwrap();
+        assert!(left.contains("George"));
+        assert!(!left.contains("John") || !left.contains("Ringo"))
+    }
+
+    #[test]
+    fn incompatible_filters_cannot_be_combined() {
+        let mut left = BloomFilter::new(100, 0.01);
+        let right = BloomFilter::new(1000, 0.01);
+        assert_eq!(left.union_with(&right), Err(FilterError::Incompatible));
+        assert_eq!(left.intersect_with(&right), Err(FilterError::Incompatible))
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut filter = BloomFilter::with_hasher(500, 0.05, Fnv1a);
+        for i in 0..500 {
+            filter.insert(&i);
+        }
+        let decoded = BloomFilter::from_bytes(&filter.to_bytes(), Fnv1a).unwrap();
+        assert_eq!(decoded, filter);
+        assert!((0..500).all(|i| decoded.contains(&i)))
+    }
+
+    #[test]
+    fn malformed_bytes_are_rejected() {
+        let bytes = BloomFilter::new(100, 0.01).to_bytes();
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes[..bytes.len() - 8], Fnv1a),
+            Err(FilterError::Malformed)
+        );
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes[..3], Fnv1a),
+            Err(FilterError::Malformed)
+        )
+    }
+
+    #[test]
+    fn bytes_for_another_hasher_are_incompatible() {
+        let mut filter = BloomFilter::new(100, 0.01);
+        filter.insert("John");
+        assert_eq!(
+            BloomFilter::from_bytes(&filter.to_bytes(), LengthHasher),
+            Err(FilterError::Incompatible)
+        )
+    }
+
+    #[test]
+    fn the_default_hasher_sets_the_same_bits_in_every_build() {
+        let mut filter = BloomFilter::new(100, 0.01);
+        filter.insert("John");
+        // Fnv1a over the bytes of "John" and the `0xff` that `Hash for str` ends with
+        let mut expected: Vec<usize> = bit_indexes(0x0859_33e6_c284_0859, 7, 959).collect();
+        expected.sort_unstable();
+        expected.dedup();
+        let set: Vec<usize> = (0..filter.num_bits())
+            .filter(|bit| filter.bits[bit / WORD_BITS] & (1 << (bit % WORD_BITS)) != 0)
+            .collect();
+        assert_eq!((filter.num_bits(), filter.num_hashes()), (959, 7));
+        assert_eq!(set, expected);
+        assert_eq!(filter.to_bytes()[..8], *b"FNV-1A64")
+    }
+}
@@ -0,0 +1,652 @@
+//! Separate Chaining
+//!
+//! `chaining` provides [ChainedHashMap](struct.ChainedHashMap.html) - a hash table in which each
+//! slot holds a chain (here, a small vector) of every entry that hashed to it.
+//!
+//! ```text
+//! [ ][ ][ ][ ][ ]
+//!  |     |
+//!  |     +-> ("bar", 2) -> ("baz", 3)
+//!  +-> ("foo", 1)
+//! ```
+//!
+//! Collisions never force an entry out of its home slot, they only lengthen its chain - so the
+//! load factor of a chained table may happily exceed one. The cost is paid on lookup, which must
+//! walk the chain, and so the distribution of chain lengths is the number worth watching.
+//!
+//! Past its maximum load factor the map doubles its slots. Under
+//! [Resizing::Incremental](enum.Resizing.html#variant.Incremental) the old slots are kept and their
+//! chains moved across a few at a time, so no single insert pays to rehash the whole map.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::mem;
+
+use super::hashing::{SipHash, SlotHasher};
+use super::{load_factor, minimum_step, Resizing};
+
+const DEFAULT_SLOTS: usize = 8;
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 1.0;
+
+/// A report describing how entries are spread across the slots of a table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketStats {
+    /// The total number of slots
+    pub slots: usize,
+    /// The number of slots with no entries
+    pub empty_slots: usize,
+    /// The length of the longest chain
+    pub longest_chain: usize,
+    /// The average length of the non-empty chains
+    pub mean_chain_length: f64,
+    /// The number of entries over the number of slots
+    pub load_factor: f64,
+}
+
+/// A separate-chaining hash map
+///
+/// Stores key-value pairs in per-slot chains, growing the slot array once the load factor
+/// crosses a configurable threshold.
+///
+/// Should be expected to have average performance characteristics of `O(1)` for inserts,
+/// lookups and removals - degrading towards `O(n)` should every key land in the same chain.
+///
+/// Keys are hashed with [SipHash](struct.SipHash.html) unless another
+/// [SlotHasher](trait.SlotHasher.html) is supplied - which makes this table a convenient way to
+/// see how a given hasher spreads a set of keys.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = ChainedHashMap::with_slots(10);
+/// phone_book.insert("Esther", 4283058824);
+/// phone_book.insert("Ben", 2123134152);
+///
+/// let stats = phone_book.bucket_stats();
+/// assert_eq!(stats.slots, 10);
+/// assert_eq!(stats.load_factor, 0.2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChainedHashMap<K, V, S = SipHash> {
+    buckets: Vec<Vec<(K, V)>>,
+    len: usize,
+    max_load_factor: f64,
+    resizing: Resizing,
+    migration: Option<Migration<K, V>>,
+    hasher: S,
+}
+
+// The slot array being drained by an incremental resize, the next chain to move, and the
+// fewest chains to move per step to be done before the next resize
+#[derive(Debug, Clone)]
+struct Migration<K, V> {
+    buckets: Vec<Vec<(K, V)>>,
+    next: usize,
+    min_step: usize,
+}
+
+impl<K: Hash + Eq, V> ChainedHashMap<K, V, SipHash> {
+    /// Creates an empty map with a maximum load factor of `1.0`
+    pub fn new() -> Self {
+        Self::with_options(DEFAULT_SLOTS, DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Creates an empty map with exactly `slots` slots
+    pub fn with_slots(slots: usize) -> Self {
+        Self::with_options(slots, DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Creates an empty map with the given slot count and maximum load factor
+    ///
+    /// See [with_options_and_hasher](struct.ChainedHashMap.html#method.with_options_and_hasher).
+    pub fn with_options(slots: usize, max_load_factor: f64) -> Self {
+        Self::with_options_and_hasher(slots, max_load_factor, SipHash)
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> ChainedHashMap<K, V, S> {
+    /// Creates an empty map which hashes its keys with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_options_and_hasher(DEFAULT_SLOTS, DEFAULT_MAX_LOAD_FACTOR, hasher)
+    }
+
+    /// Creates an empty map with the given slot count, maximum load factor and hasher
+    ///
+    /// # Arguments
+    ///
+    /// * `slots` - the initial number of slots (at least one is always allocated)
+    /// * `max_load_factor` - the load factor at which the slot array doubles
+    /// * `hasher` - the hash function used to place keys
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_load_factor` is not greater than `0`.
+    pub fn with_options_and_hasher(slots: usize, max_load_factor: f64, hasher: S) -> Self {
+        assert!(
+            max_load_factor > 0.0,
+            "max_load_factor must be greater than 0, got {}",
+            max_load_factor
+        );
+
+        ChainedHashMap {
+            buckets: empty_buckets(slots.max(1)),
+            len: 0,
+            max_load_factor,
+            resizing: Resizing::Immediate,
+            migration: None,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the map's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of entries in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots
+    pub fn slots(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Returns the current load factor - the number of entries over the number of slots
+    pub fn load_factor(&self) -> f64 {
+        load_factor(self.len, self.buckets.len())
+    }
+
+    /// Returns how the map moves its entries when it outgrows its slots
+    pub fn resizing(&self) -> Resizing {
+        self.resizing
+    }
+
+    /// Sets how the map moves its entries when it outgrows its slots
+    ///
+    /// Switching to [Resizing::Immediate](enum.Resizing.html#variant.Immediate) completes any
+    /// resize already in progress.
+    ///
+    /// # Panics
+    ///
+    /// Panics if given an incremental `step` of zero.
+    pub fn set_resizing(&mut self, resizing: Resizing) {
+        if let Resizing::Incremental { step } = resizing {
+            assert!(
+                step > 0,
+                "an incremental resize must migrate at least one slot per step"
+            );
+        }
+        self.resizing = resizing;
+        if resizing == Resizing::Immediate {
+            self.finish_resize();
+        }
+    }
+
+    /// Returns `true` if an incremental resize is in progress
+    pub fn is_resizing(&self) -> bool {
+        self.migration.is_some()
+    }
+
+    /// Moves every remaining chain out of the old slot array, completing any resize in progress
+    pub fn finish_resize(&mut self) {
+        self.migrate(usize::MAX);
+    }
+
+    /// Inserts a key-value pair - returns the previous value if the key was already present
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.migrate_step();
+
+        if let Some(v) = self.find_mut(&key) {
+            return Some(mem::replace(v, value));
+        }
+
+        if load_factor(self.len + 1, self.buckets.len()) > self.max_load_factor {
+            self.resize(self.buckets.len() * 2);
+        }
+
+        let index = self.slot_of(&key, self.buckets.len());
+        self.buckets[index].push((key, value));
+        self.len += 1;
+        None
+    }
+
+    /// Returns a reference to the value stored for `key`, if present
+    ///
+    /// Lookups through a shared reference cannot advance an incremental resize - use
+    /// [get_mut](struct.ChainedHashMap.html#method.get_mut) on hot paths to keep one moving.
+    /// Inserts alone still move it fast enough to finish before the next resize.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let current = &self.buckets[self.slot_of(key, self.buckets.len())];
+        let old = self
+            .migration
+            .as_ref()
+            .map(|m| &m.buckets[self.slot_of(key, m.buckets.len())]);
+
+        current
+            .iter()
+            .chain(old.into_iter().flatten())
+            .find(|(k, _)| k.borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns a mutable reference to the value stored for `key`, if present
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.migrate_step();
+        self.find_mut(key)
+    }
+
+    /// Returns `true` if the map holds an entry for `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Removes the entry for `key` from its chain - returns the removed value
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.migrate_step();
+
+        let index = self.slot_of(key, self.buckets.len());
+        let old_index = self
+            .migration
+            .as_ref()
+            .map(|m| self.slot_of(key, m.buckets.len()));
+
+        let chain = &mut self.buckets[index];
+        if let Some(position) = chain.iter().position(|(k, _)| k.borrow() == key) {
+            self.len -= 1;
+            return Some(chain.swap_remove(position).1);
+        }
+
+        let chain = &mut self.migration.as_mut()?.buckets[old_index?];
+        let position = chain.iter().position(|(k, _)| k.borrow() == key)?;
+        self.len -= 1;
+        Some(chain.swap_remove(position).1)
+    }
+
+    /// Returns an iterator over the entries of the map, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let old = self.migration.iter().flat_map(|m| m.buckets.iter());
+        self.buckets
+            .iter()
+            .chain(old)
+            .flat_map(|chain| chain.iter().map(|(k, v)| (k, v)))
+    }
+
+    /// Removes every entry, keeping the current number of slots
+    pub fn clear(&mut self) {
+        for chain in self.buckets.iter_mut() {
+            chain.clear();
+        }
+        self.migration = None;
+        self.len = 0;
+    }
+
+    /// Reports how the entries are distributed across the slots
+    ///
+    /// The slot figures describe the current array only, matching
+    /// [slots](struct.ChainedHashMap.html#method.slots) and
+    /// [load_factor](struct.ChainedHashMap.html#method.load_factor). While an incremental
+    /// resize is in progress, the old chains still waiting to move count toward the chain
+    /// lengths.
+    ///
+    /// Should be expected to have performance characteristics of `O(s)`, where `s` is the
+    /// number of slots.
+    pub fn bucket_stats(&self) -> BucketStats {
+        let slots = self.buckets.len();
+        let empty_slots = self.buckets.iter().filter(|chain| chain.is_empty()).count();
+
+        let unmigrated = self
+            .migration
+            .iter()
+            .flat_map(|m| m.buckets[m.next..].iter());
+        let chains: Vec<usize> = self
+            .buckets
+            .iter()
+            .chain(unmigrated)
+            .map(Vec::len)
+            .filter(|len| *len > 0)
+            .collect();
+        let longest_chain = chains.iter().copied().max().unwrap_or(0);
+        let mean_chain_length = match chains.len() {
+            0 => 0.0,
+            used => self.len as f64 / used as f64,
+        };
+
+        BucketStats {
+            slots,
+            empty_slots,
+            longest_chain,
+            mean_chain_length,
+            load_factor: self.load_factor(),
+        }
+    }
+
+    fn slot_of<Q>(&self, key: &Q, slots: usize) -> usize
+    where
+        Q: ?Sized,
+        S: SlotHasher<Q>,
+    {
+        (self.hasher.hash(key) % slots as u64) as usize
+    }
+
+    // Looks up `key` in its chain of the current array, then of the old array of any
+    // resize in progress
+    fn find_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let index = self.slot_of(key, self.buckets.len());
+        let old_index = self
+            .migration
+            .as_ref()
+            .map(|m| self.slot_of(key, m.buckets.len()));
+
+        let old = match (&mut self.migration, old_index) {
+            (Some(migration), Some(old_index)) => Some(&mut migration.buckets[old_index]),
+            _ => None,
+        };
+
+        self.buckets[index]
+            .iter_mut()
+            .chain(old.into_iter().flatten())
+            .find(|(k, _)| (*k).borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    // Swaps in a fresh slot array, then moves the chains of the old one across - all at
+    // once, or a step at a time as the map is used
+    fn resize(&mut self, slots: usize) {
+        self.finish_resize();
+
+        let old = mem::replace(&mut self.buckets, empty_buckets(slots));
+
+        // The next resize comes once the entries reach the limit, and only inserts add entries
+        let headroom = slots as f64 * self.max_load_factor - (self.len + 1) as f64;
+        self.migration = Some(Migration {
+            min_step: minimum_step(old.len(), headroom),
+            buckets: old,
+            next: 0,
+        });
+
+        if self.resizing == Resizing::Immediate {
+            self.finish_resize();
+        }
+    }
+
+    fn migrate_step(&mut self) {
+        if let Resizing::Incremental { step } = self.resizing {
+            let min_step = self.migration.as_ref().map_or(0, |m| m.min_step);
+            self.migrate(step.max(min_step));
+        }
+    }
+
+    // Rehashes the entries of the next `step` old chains into the current array
+    fn migrate(&mut self, step: usize) {
+        let mut migration = match self.migration.take() {
+            Some(migration) => migration,
+            None => return,
+        };
+
+        let end = migration
+            .next
+            .saturating_add(step)
+            .min(migration.buckets.len());
+        for chain in &mut migration.buckets[migration.next..end] {
+            for (k, v) in chain.drain(..) {
+                let index = self.slot_of(&k, self.buckets.len());
+                self.buckets[index].push((k, v));
+            }
+        }
+        migration.next = end;
+
+        if end < migration.buckets.len() {
+            self.migration = Some(migration);
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Default for ChainedHashMap<K, V, SipHash> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn empty_buckets<K, V>(slots: usize) -> Vec<Vec<(K, V)>> {
+    (0..slots).map(|_| Vec::new()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::hashing::{ConstantHasher, PrimeSumHasher};
+    use super::*;
+
+    #[test]
+    fn insert_then_get_returns_value() {
+        let mut map = ChainedHashMap::new();
+        map.insert("foo", 1);
+        map.insert("bar", 2);
+        assert_eq!(map.get("foo"), Some(&1));
+        assert_eq!(map.get("bar"), Some(&2));
+        assert_eq!(map.get("baz"), None)
+    }
+
+    #[test]
+    fn insert_existing_key_replaces_value() {
+        let mut map = ChainedHashMap::new();
+        assert_eq!(map.insert("foo", 1), None);
+        assert_eq!(map.insert("foo", 2), Some(1));
+        assert_eq!(map.len(), 1)
+    }
+
+    #[test]
+    fn remove_returns_value_and_shortens_chain() {
+        let mut map = ChainedHashMap::with_options(1, 100.0);
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.bucket_stats().longest_chain, 10);
+        assert_eq!(map.remove(&4), Some(4));
+        assert_eq!(map.remove(&4), None);
+        assert_eq!(map.bucket_stats().longest_chain, 9);
+        assert!((0..10).filter(|i| *i != 4).all(|i| map.contains_key(&i)))
+    }
+
+    #[test]
+    fn map_grows_once_load_factor_is_crossed() {
+        let mut map = ChainedHashMap::with_options(2, 2.0);
+        for i in 0..1000 {
+            map.insert(i, i);
+            assert!(map.load_factor() <= 2.0);
+        }
+        assert!((0..1000).all(|i| map.get(&i) == Some(&i)))
+    }
+
+    #[test]
+    fn constant_hasher_places_every_key_in_one_chain() {
+        let mut map = ChainedHashMap::with_options_and_hasher(10, 10.0, ConstantHasher);
+        for name in &["Esther", "Ben", "Bob", "Dan"] {
+            map.insert(*name, name.len());
+        }
+        let stats = map.bucket_stats();
+        assert_eq!(stats.longest_chain, 4);
+        assert_eq!(stats.empty_slots, 9);
+        assert_eq!(map.get("Bob"), Some(&3))
+    }
+
+    #[test]
+    fn prime_sum_hasher_spreads_phonebook_names() {
+        let mut map = ChainedHashMap::with_hasher(PrimeSumHasher);
+        for name in &["Esther", "Ben", "Bob", "Dan"] {
+            map.insert(String::from(*name), ());
+        }
+        assert_eq!(map.bucket_stats().longest_chain, 1);
+        assert!(map.contains_key("Dan"))
+    }
+
+    #[test]
+    fn bucket_stats_of_empty_map() {
+        let map: ChainedHashMap<&str, i32> = ChainedHashMap::with_slots(10);
+        let stats = map.bucket_stats();
+        assert_eq!(stats.empty_slots, 10);
+        assert_eq!(stats.longest_chain, 0);
+        assert_eq!(stats.mean_chain_length, 0.0);
+        assert_eq!(stats.load_factor, 0.0)
+    }
+
+    #[test]
+    fn bucket_stats_account_for_every_entry() {
+        let mut map = ChainedHashMap::with_options(16, 4.0);
+        for i in 0..40 {
+            map.insert(i, i);
+        }
+        let stats = map.bucket_stats();
+        let used = stats.slots - stats.empty_slots;
+        assert_eq!(stats.load_factor, 2.5);
+        assert!((stats.mean_chain_length * used as f64 - 40.0).abs() < 1e-9);
+        assert!(stats.longest_chain as f64 >= stats.mean_chain_length)
+    }
+
+    #[test]
+    fn bucket_stats_mid_resize_describe_the_current_array() {
+        let mut map = ChainedHashMap::with_options(8, 1.0);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        map.insert(i, i);
+        let migration = map.migration.as_ref().unwrap();
+        assert!(migration.next > 0 && migration.next < migration.buckets.len());
+        let waiting: usize = migration.buckets[migration.next..]
+            .iter()
+            .map(Vec::len)
+            .sum();
+        assert!(waiting > 0);
+
+        let stats = map.bucket_stats();
+        assert_eq!(stats.slots, map.slots());
+        assert_eq!(stats.load_factor, map.load_factor());
+        assert!(stats.empty_slots <= stats.slots);
+        let moved = map.buckets.iter().map(Vec::len).sum::<usize>();
+        assert_eq!(moved + waiting, map.len());
+        let used = map
+            .buckets
+            .iter()
+            .chain(&migration.buckets[migration.next..]);
+        let used = used.filter(|chain| !chain.is_empty()).count();
+        assert!((stats.mean_chain_length * used as f64 - map.len() as f64).abs() < 1e-9)
+    }
+
+    #[test]
+    fn incremental_resize_keeps_every_entry_reachable() {
+        let mut map = ChainedHashMap::with_options(8, 1.0);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        for i in 0..10_000 {
+            map.insert(i, i);
+            assert_eq!(map.get(&(i / 2)), Some(&(i / 2)));
+        }
+        assert_eq!(map.len(), 10_000);
+        assert_eq!(map.iter().count(), 10_000);
+        assert!((0..10_000).all(|i| map.get(&i) == Some(&i)))
+    }
+
+    #[test]
+    fn incremental_resize_migrates_one_step_per_operation() {
+        let mut map = ChainedHashMap::with_options(8, 1.0);
+        map.set_resizing(Resizing::Incremental { step: 2 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        assert_eq!(map.migration.as_ref().unwrap().next, 0);
+        map.insert(i, i);
+        assert_eq!(map.migration.as_ref().unwrap().next, 2);
+        map.get_mut(&0);
+        assert_eq!(map.migration.as_ref().unwrap().next, 4)
+    }
+
+    #[test]
+    fn incremental_resize_finishes_before_the_next_one_starts() {
+        let mut map = ChainedHashMap::with_options(8, 0.5);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        for i in 0..10_000 {
+            let remaining = map
+                .migration
+                .as_ref()
+                .map(|m| (m.buckets.len() - m.next, m.min_step.max(1)));
+            let slots = map.buckets.len();
+            map.insert(i, i);
+            if map.buckets.len() != slots {
+                // Whatever was left of the last resize fit in the step this insert took
+                if let Some((remaining, step)) = remaining {
+                    assert!(
+                        remaining <= step,
+                        "{} chains left for a step of {}",
+                        remaining,
+                        step
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn remove_during_incremental_resize_reaches_old_buckets() {
+        let mut map = ChainedHashMap::with_options(8, 1.0);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        for j in 0..=i {
+            assert_eq!(map.remove(&j), if j < i { Some(j) } else { None });
+        }
+        assert!(map.is_empty());
+        assert_eq!(map.iter().count(), 0)
+    }
+
+    #[test]
+    fn switching_to_immediate_resizing_finishes_migration() {
+        let mut map = ChainedHashMap::with_options(8, 1.0);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        map.set_resizing(Resizing::Immediate);
+        assert!(!map.is_resizing());
+        assert!((0..i).all(|j| map.get(&j) == Some(&j)))
+    }
+}
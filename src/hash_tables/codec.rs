@@ -0,0 +1,98 @@
+//! Byte Encoding
+//!
+//! `codec` provides [ByteCodec](trait.ByteCodec.html) - a minimal way of turning keys and values
+//! into bytes and back, for the structures in this module that can be saved and reloaded.
+//!
+//! Integers are written little-endian at their full width, and strings and byte vectors as a
+//! `u32` length followed by their bytes.
+
+/// A type which can be written to, and read back from, a byte buffer
+pub trait ByteCodec: Sized {
+    /// Appends the encoded value to `out`
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Reads a value from the front of `bytes`, advancing past it - or returns `None` if the
+    /// bytes do not hold one
+    fn decode(bytes: &mut &[u8]) -> Option<Self>;
+}
+
+macro_rules! integer_codec {
+    ($($int:ty),*) => {
+        $(
+            impl ByteCodec for $int {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(bytes: &mut &[u8]) -> Option<Self> {
+                    const WIDTH: usize = std::mem::size_of::<$int>();
+                    let mut buffer = [0; WIDTH];
+                    buffer.copy_from_slice(take(bytes, WIDTH)?);
+                    Some(<$int>::from_le_bytes(buffer))
+                }
+            }
+        )*
+    };
+}
+
+integer_codec!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl ByteCodec for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let len = u32::decode(bytes)? as usize;
+        take(bytes, len).map(|taken| taken.to_vec())
+    }
+}
+
+impl ByteCodec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        String::from_utf8(Vec::decode(bytes)?).ok()
+    }
+}
+
+/// Splits `len` bytes off the front of `bytes`
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Some(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip_in_sequence() {
+        let mut out = Vec::new();
+        42u16.encode(&mut out);
+        String::from("Watchmen").encode(&mut out);
+        (-7i64).encode(&mut out);
+
+        let mut bytes = out.as_slice();
+        assert_eq!(u16::decode(&mut bytes), Some(42));
+        assert_eq!(String::decode(&mut bytes), Some("Watchmen".to_string()));
+        assert_eq!(i64::decode(&mut bytes), Some(-7));
+        assert!(bytes.is_empty())
+    }
+
+    #[test]
+    fn truncated_bytes_decode_to_none() {
+        let mut out = Vec::new();
+        String::from("Fun Home").encode(&mut out);
+        let mut bytes = &out[..out.len() - 1];
+        assert_eq!(String::decode(&mut bytes), None)
+    }
+}
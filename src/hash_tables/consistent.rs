@@ -0,0 +1,640 @@
+//! Consistent Hashing
+//!
+//! `consistent` provides ways of spreading keys across a changing set of nodes - cache processes,
+//! database shards - so that when a node joins or leaves, only a small share of keys move.
+//!
+//! The obvious approach, `hash(key) % n`, is a disaster here: going from `n` to `n + 1` nodes
+//! changes the answer for almost every key, and a cache cluster that grows by one process
+//! loses nearly all of its hits at once. Ideally, adding a node should move just the `1 / (n + 1)`
+//! of keys it takes on, and removing one should move just the keys it held.
+//!
+//! Three schemes get there:
+//!
+//! * [HashRing](struct.HashRing.html) - nodes and keys are hashed onto a ring of `2⁶⁴` points,
+//!   and each key belongs to the first node at or after it, going clockwise. Each node is placed
+//!   at many *virtual* points, so the arcs even out, and a node's weight scales its number of points
+//! * [jump_consistent_hash](fn.jump_consistent_hash.html) - maps a key to one of `n` numbered
+//!   buckets with no memory at all, so long as buckets are only ever added or removed at the end
+//! * [RendezvousHash](struct.RendezvousHash.html) - each key scores every node and picks the
+//!   highest, so any node may leave, at the cost of examining every node per lookup
+//!
+//! ```text
+//!              A₁
+//!          .-- ● --.
+//!     B₁ ●           ◆ key            the key belongs to C₁, the next point clockwise -
+//!        |           |                so it belongs to node C
+//!     A₂ ●           ● C₁
+//!          '-- ● --'
+//!              B₂
+//! ```
+//!
+//! To see how well any of them does, [measure_remapping](fn.measure_remapping.html) counts how many
+//! of a set of keys change node between two assignments.
+
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use super::hashing::{mix, Fnv1a, SlotHasher};
+
+const DEFAULT_REPLICAS: u32 = 100;
+const RING_SIZE: f64 = 18_446_744_073_709_551_616.0;
+
+/// A consistent hash ring with virtual nodes
+///
+/// Places each node at `replicas × weight` points around a ring, assigning each key to the first
+/// point clockwise of its hash.
+///
+/// Should be expected to have performance characteristics of `O(log v)` for lookups, and
+/// `O(r log v)` for adding or removing a node with `r` points, where `v` is the total number of
+/// points on the ring.
+///
+/// Nodes and keys are hashed with [Fnv1a](struct.Fnv1a.html) unless another
+/// [SlotHasher](trait.SlotHasher.html) is supplied - its hashes don't change between Rust
+/// releases, so processes built by different toolchains still agree on which node a key
+/// belongs to.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut caches = HashRing::new();
+/// caches.add_node("cache-1", 1);
+/// caches.add_node("cache-2", 1);
+///
+/// let moved = caches.add_node("cache-3", 1); // roughly a third of the keys
+/// println!("{:?}", caches.node_for("John"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HashRing<N, S = Fnv1a> {
+    ring: BTreeMap<u64, N>,
+    weights: Vec<(N, u32)>,
+    replicas: u32,
+    hasher: S,
+}
+
+impl<N: Hash + Eq + Clone> HashRing<N, Fnv1a> {
+    /// Creates an empty ring placing `100` points per unit of weight
+    pub fn new() -> Self {
+        Self::with_replicas(DEFAULT_REPLICAS)
+    }
+
+    /// Creates an empty ring placing `replicas` points per unit of weight
+    ///
+    /// See [with_replicas_and_hasher](struct.HashRing.html#method.with_replicas_and_hasher).
+    pub fn with_replicas(replicas: u32) -> Self {
+        Self::with_replicas_and_hasher(replicas, Fnv1a)
+    }
+}
+
+impl<N: Hash + Eq + Clone> Default for HashRing<N, Fnv1a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: Eq + Clone, S: SlotHasher<N>> HashRing<N, S> {
+    /// Creates an empty ring placing `100` points per unit of weight, hashing with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_replicas_and_hasher(DEFAULT_REPLICAS, hasher)
+    }
+
+    /// Creates an empty ring placing `replicas` points per unit of weight, hashing with `hasher`
+    ///
+    /// More points even out the share of keys each node receives, at the cost of memory and a
+    /// slightly deeper search per lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `replicas` - the number of virtual points for a node of weight `1`
+    /// * `hasher` - the hash function nodes and keys are placed with
+    ///
+    /// # Panics
+    ///
+    /// Panics if `replicas` is zero.
+    pub fn with_replicas_and_hasher(replicas: u32, hasher: S) -> Self {
+        assert!(replicas > 0, "replicas must be greater than 0");
+
+        HashRing {
+            ring: BTreeMap::new(),
+            weights: Vec::new(),
+            replicas,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the ring's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of nodes on the ring
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Returns `true` if the ring has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// Returns the number of virtual points on the ring
+    pub fn points(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns an iterator over the nodes and their weights
+    pub fn nodes(&self) -> impl Iterator<Item = (&N, u32)> {
+        self.weights.iter().map(|(node, weight)| (node, *weight))
+    }
+
+    /// Returns the weight of a node, if it is on the ring
+    pub fn weight(&self, node: &N) -> Option<u32> {
+        self.weights
+            .iter()
+            .find(|(member, _)| member == node)
+            .map(|(_, weight)| *weight)
+    }
+
+    /// Adds a node, or changes the weight of one already on the ring - returns the fraction of
+    /// the key space which moved as a result
+    ///
+    /// Every key that moves on an addition moves to the new node, so for `n` equally weighted
+    /// nodes the fraction is around `1 / n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is zero.
+    pub fn add_node(&mut self, node: N, weight: u32) -> f64 {
+        assert!(weight > 0, "weight must be greater than 0");
+
+        let previous = self.remove_node(&node).unwrap_or(0.0);
+        for point in self.node_points(&node, weight) {
+            self.ring.entry(point).or_insert_with(|| node.clone());
+        }
+        self.weights.push((node.clone(), weight));
+
+        // A re-weighted node keeps some of its old arcs, so only the difference has moved
+        (self.share(&node, weight) - previous).abs()
+    }
+
+    /// Removes a node - returns the fraction of the key space which moved, being exactly the
+    /// share the node held, or `None` if it was not on the ring
+    pub fn remove_node(&mut self, node: &N) -> Option<f64> {
+        let position = self.weights.iter().position(|(member, _)| member == node)?;
+        let (_, weight) = self.weights.remove(position);
+        let share = self.share(node, weight);
+
+        for point in self.node_points(node, weight) {
+            if self.ring.get(&point) == Some(node) {
+                self.ring.remove(&point);
+            }
+        }
+        Some(share)
+    }
+
+    /// Returns the node responsible for a key, or `None` if the ring is empty
+    pub fn node_for<K>(&self, key: &K) -> Option<&N>
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let hash = self.hasher.hash(key);
+        self.ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, node)| node)
+    }
+
+    /// Returns each node with the fraction of the key space it is responsible for
+    pub fn ownership(&self) -> Vec<(&N, f64)> {
+        self.weights
+            .iter()
+            .map(|(node, weight)| (node, self.share(node, *weight)))
+            .collect()
+    }
+
+    fn node_points(&self, node: &N, weight: u32) -> impl Iterator<Item = u64> {
+        let hash = self.hasher.hash(node);
+        let count = u64::from(self.replicas) * u64::from(weight);
+        (0..count).map(move |replica| mix(hash, replica))
+    }
+
+    /// The fraction of the ring covered by the arcs ending at the node's points - found from
+    /// the point before each of them, so only the node's own `r` points are visited
+    fn share(&self, node: &N, weight: u32) -> f64 {
+        let last = match self.ring.keys().next_back() {
+            Some(last) => *last,
+            None => return 0.0,
+        };
+
+        let mut covered = 0.0;
+        for point in self.node_points(node, weight) {
+            // A point another node placed first isn't this node's to count
+            if self.ring.get(&point) != Some(node) {
+                continue;
+            }
+            if self.ring.len() == 1 {
+                return 1.0;
+            }
+            let previous = self
+                .ring
+                .range(..point)
+                .next_back()
+                .map_or(last, |(previous, _)| *previous);
+            covered += point.wrapping_sub(previous) as f64;
+        }
+        covered / RING_SIZE
+    }
+}
+
+/// Maps a hash to one of `num_buckets` buckets using Lamping and Veach's jump consistent hash
+///
+/// No state is kept at all - growing from `n` to `n + 1` buckets moves exactly the keys that
+/// now map to bucket `n`, about `1 / (n + 1)` of them. The catch is that buckets are numbered,
+/// so only the last bucket can be removed without remapping the rest.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `hash` - the key's hash, for example from a [SlotHasher](trait.SlotHasher.html)
+/// * `num_buckets` - the number of buckets
+///
+/// # Panics
+///
+/// Panics if `num_buckets` is zero.
+///
+/// # Examples
+///
+/// ```rust
+/// let shard = jump_consistent_hash(Fnv1a.hash("John"), 10);
+/// assert!(shard < 10);
+/// ```
+pub fn jump_consistent_hash(hash: u64, num_buckets: u32) -> u32 {
+    assert!(num_buckets > 0, "num_buckets must be greater than 0");
+
+    let (mut key, mut bucket, mut next) = (hash, -1i64, 0i64);
+    while next < i64::from(num_buckets) {
+        bucket = next;
+        key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+    bucket as u32
+}
+
+/// A rendezvous (highest random weight) hash
+///
+/// Assigns each key to whichever node scores highest for it, where every key-node pair gets an
+/// independent pseudo-random score scaled by the node's weight.
+///
+/// Should be expected to have performance characteristics of `O(n)` for lookups, where `n` is
+/// the number of nodes.
+///
+/// As with [HashRing](struct.HashRing.html), nodes and keys are hashed with
+/// [Fnv1a](struct.Fnv1a.html) unless another [SlotHasher](trait.SlotHasher.html) is supplied.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut caches = RendezvousHash::new();
+/// caches.add_node("cache-1", 1.0);
+/// caches.add_node("cache-2", 2.0);
+///
+/// println!("{:?}", caches.node_for("John"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RendezvousHash<N, S = Fnv1a> {
+    nodes: Vec<(N, u64, f64)>,
+    hasher: S,
+}
+
+impl<N: Hash + Eq> RendezvousHash<N, Fnv1a> {
+    /// Creates a hash with no nodes
+    pub fn new() -> Self {
+        Self::with_hasher(Fnv1a)
+    }
+}
+
+impl<N: Hash + Eq> Default for RendezvousHash<N, Fnv1a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: Eq, S: SlotHasher<N>> RendezvousHash<N, S> {
+    /// Creates a hash with no nodes, hashing with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        RendezvousHash {
+            nodes: Vec::new(),
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the hash's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if there are no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns an iterator over the nodes and their weights
+    pub fn nodes(&self) -> impl Iterator<Item = (&N, f64)> {
+        self.nodes.iter().map(|(node, _, weight)| (node, *weight))
+    }
+
+    /// Adds a node, or changes the weight of one already present
+    ///
+    /// A node receives a share of the keys in proportion to its weight.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is not positive.
+    pub fn add_node(&mut self, node: N, weight: f64) {
+        assert!(
+            weight > 0.0,
+            "weight must be greater than 0, got {}",
+            weight
+        );
+
+        self.remove_node(&node);
+        let hash = self.hasher.hash(&node);
+        self.nodes.push((node, hash, weight));
+    }
+
+    /// Removes a node - returns `false` if it was not present
+    ///
+    /// Only the keys the node held are remapped.
+    pub fn remove_node(&mut self, node: &N) -> bool {
+        match self.nodes.iter().position(|(member, _, _)| member == node) {
+            Some(position) => {
+                self.nodes.remove(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the node responsible for a key, or `None` if there are no nodes
+    pub fn node_for<K>(&self, key: &K) -> Option<&N>
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let hash = self.hasher.hash(key);
+        self.nodes
+            .iter()
+            .map(|(node, node_hash, weight)| (node, score(mix(hash, *node_hash), *weight)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(node, _)| node)
+    }
+}
+
+/// Turns a pseudo-random hash into a weighted score, `-weight / ln(u)` for `u` uniform in `(0, 1)`
+fn score(hash: u64, weight: f64) -> f64 {
+    let unit = ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    -weight / unit.ln()
+}
+
+/// How many keys changed node between two assignments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Remapping {
+    /// The number of keys compared
+    pub keys: usize,
+    /// The number of keys assigned to a different node
+    pub moved: usize,
+}
+
+impl Remapping {
+    /// Returns the fraction of keys which moved
+    pub fn fraction(&self) -> f64 {
+        match self.keys {
+            0 => 0.0,
+            _ => self.moved as f64 / self.keys as f64,
+        }
+    }
+}
+
+/// Counts how many keys are assigned to a different node by `after` than by `before`
+///
+/// # Arguments
+///
+/// * `keys` - the keys to compare
+/// * `before` - the assignment before a membership change
+/// * `after` - the assignment after it
+///
+/// # Examples
+///
+/// ```rust
+/// let keys: Vec<u64> = (0..10_000).collect();
+/// let remapping = measure_remapping(
+///     &keys,
+///     |key| jump_consistent_hash(Fnv1a.hash(key), 10),
+///     |key| jump_consistent_hash(Fnv1a.hash(key), 11),
+/// );
+///
+/// println!("{:.1}% of keys moved", remapping.fraction() * 100.0);
+/// ```
+pub fn measure_remapping<'a, K, N, I, F, G>(keys: I, mut before: F, mut after: G) -> Remapping
+where
+    K: ?Sized + 'a,
+    N: PartialEq,
+    I: IntoIterator<Item = &'a K>,
+    F: FnMut(&K) -> N,
+    G: FnMut(&K) -> N,
+{
+    let mut remapping = Remapping { keys: 0, moved: 0 };
+    for key in keys {
+        remapping.keys += 1;
+        if before(key) != after(key) {
+            remapping.moved += 1;
+        }
+    }
+    remapping
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<u64> {
+        (0..20_000).collect()
+    }
+
+    fn assignments<F: Fn(&u64) -> String>(assign: F) -> Vec<String> {
+        keys().iter().map(assign).collect()
+    }
+
+    #[test]
+    fn an_empty_ring_has_no_nodes_for_keys() {
+        let ring: HashRing<&str> = HashRing::new();
+        assert_eq!(ring.node_for("John"), None)
+    }
+
+    #[test]
+    fn the_default_hasher_assigns_the_same_nodes_in_every_build() {
+        let mut ring = HashRing::new();
+        let mut rendezvous = RendezvousHash::new();
+        for node in &["cache-1", "cache-2", "cache-3"] {
+            ring.add_node(*node, 1);
+            rendezvous.add_node(*node, 1.0);
+        }
+        let names = ["John", "Paul", "George", "Ringo"];
+        let on_ring: Vec<&str> = names
+            .iter()
+            .map(|name| *ring.node_for(*name).unwrap())
+            .collect();
+        let by_rendezvous: Vec<&str> = names
+            .iter()
+            .map(|name| *rendezvous.node_for(*name).unwrap())
+            .collect();
+        // Fnv1a's hashes are fixed, so any build - on any toolchain - routes keys the same way
+        assert_eq!(on_ring, ["cache-2", "cache-2", "cache-1", "cache-3"]);
+        assert_eq!(by_rendezvous, ["cache-2", "cache-2", "cache-3", "cache-2"])
+    }
+
+    #[test]
+    fn ring_shares_follow_weights() {
+        let mut ring = HashRing::with_replicas(1000);
+        ring.add_node("a", 1);
+        ring.add_node("b", 1);
+        ring.add_node("c", 2);
+
+        let on_c = keys()
+            .iter()
+            .filter(|key| ring.node_for(*key) == Some(&"c"))
+            .count();
+        assert!((on_c as f64 / 20_000.0 - 0.5).abs() < 0.05);
+
+        let total: f64 = ring.ownership().iter().map(|(_, share)| share).sum();
+        assert!((total - 1.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn a_lone_node_owns_the_whole_ring() {
+        let mut ring = HashRing::with_replicas(1);
+        assert_eq!(ring.add_node("a", 1), 1.0);
+        assert_eq!(ring.ownership(), vec![(&"a", 1.0)]);
+
+        ring.add_node("b", 3);
+        let total: f64 = ring.ownership().iter().map(|(_, share)| share).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert_eq!(ring.remove_node(&"b").map(|share| share < 1.0), Some(true));
+        assert_eq!(ring.remove_node(&"a"), Some(1.0))
+    }
+
+    #[test]
+    fn adding_a_node_only_moves_keys_onto_it() {
+        let mut ring = HashRing::new();
+        for node in &["a", "b", "c", "d"] {
+            ring.add_node(node.to_string(), 1);
+        }
+        let before = assignments(|key| ring.node_for(key).unwrap().clone());
+
+        let reported = ring.add_node("e".to_string(), 1);
+        let after = assignments(|key| ring.node_for(key).unwrap().clone());
+
+        let moved: Vec<_> = before.iter().zip(&after).filter(|(b, a)| b != a).collect();
+        assert!(moved.iter().all(|(_, a)| *a == "e"));
+
+        let fraction = moved.len() as f64 / 20_000.0;
+        assert!((fraction - 0.2).abs() < 0.05);
+        assert!((fraction - reported).abs() < 0.02)
+    }
+
+    #[test]
+    fn removing_a_node_only_moves_its_keys() {
+        let mut ring = HashRing::with_replicas(50);
+        for node in &["a", "b", "c"] {
+            ring.add_node(node.to_string(), 1);
+        }
+        let before = assignments(|key| ring.node_for(key).unwrap().clone());
+
+        let reported = ring.remove_node(&"b".to_string()).unwrap();
+        let after = assignments(|key| ring.node_for(key).unwrap().clone());
+
+        let moved = before.iter().zip(&after).filter(|(b, a)| b != a);
+        assert!(moved.clone().all(|(b, _)| b == "b"));
+        assert!((moved.count() as f64 / 20_000.0 - reported).abs() < 0.02);
+        assert_eq!(ring.remove_node(&"b".to_string()), None)
+    }
+
+    #[test]
+    fn reweighting_a_node_keeps_one_entry() {
+        let mut ring = HashRing::with_replicas(10);
+        ring.add_node("a", 1);
+        ring.add_node("a", 3);
+        assert_eq!(ring.len(), 1);
+        assert_eq!(ring.weight(&"a"), Some(3));
+        assert_eq!(ring.points(), 30)
+    }
+
+    #[test]
+    fn jump_hash_growth_only_moves_keys_to_the_new_bucket() {
+        for buckets in 1..20 {
+            for key in keys().iter().take(2000) {
+                let hash = Fnv1a.hash(key);
+                let (before, after) = (
+                    jump_consistent_hash(hash, buckets),
+                    jump_consistent_hash(hash, buckets + 1),
+                );
+                assert!(after == before || after == buckets)
+            }
+        }
+
+        let remapping = measure_remapping(
+            &keys(),
+            |key| jump_consistent_hash(Fnv1a.hash(key), 9),
+            |key| jump_consistent_hash(Fnv1a.hash(key), 10),
+        );
+        assert!((remapping.fraction() - 0.1).abs() < 0.02)
+    }
+
+    #[test]
+    fn jump_hash_spreads_keys_evenly() {
+        let mut counts = [0; 8];
+        for key in keys() {
+            counts[jump_consistent_hash(Fnv1a.hash(&key), 8) as usize] += 1;
+        }
+        assert!(counts
+            .iter()
+            .all(|count| (*count as f64 / 2500.0 - 1.0).abs() < 0.1));
+        assert!(keys().iter().all(|key| jump_consistent_hash(*key, 1) == 0))
+    }
+
+    #[test]
+    fn rendezvous_removal_only_moves_the_removed_nodes_keys() {
+        let mut rendezvous = RendezvousHash::new();
+        for node in &["a", "b", "c", "d"] {
+            rendezvous.add_node(node.to_string(), 1.0);
+        }
+        let before = assignments(|key| rendezvous.node_for(key).unwrap().clone());
+
+        assert!(rendezvous.remove_node(&"c".to_string()));
+        let after = assignments(|key| rendezvous.node_for(key).unwrap().clone());
+
+        let moved = before.iter().zip(&after).filter(|(b, a)| b != a);
+        assert!(moved.clone().all(|(b, _)| b == "c"));
+        assert!((moved.count() as f64 / 20_000.0 - 0.25).abs() < 0.03)
+    }
+
+    #[test]
+    fn rendezvous_shares_follow_weights() {
+        let mut rendezvous = RendezvousHash::new();
+        rendezvous.add_node("light", 1.0);
+        rendezvous.add_node("heavy", 3.0);
+
+        let on_heavy = keys()
+            .iter()
+            .filter(|key| rendezvous.node_for(*key) == Some(&"heavy"))
+            .count();
+        assert!((on_heavy as f64 / 20_000.0 - 0.75).abs() < 0.03)
+    }
+}
@@ -0,0 +1,329 @@
+//! Count-Min Sketch
+//!
+//! `count_min` provides [CountMinSketch](struct.CountMinSketch.html) - an estimate of how often
+//! each key has been seen, in a fixed amount of space no matter how many keys there are.
+//!
+//! The sketch is a grid of counters, `depth` rows of `width` columns, with one hash function per
+//! row. Adding a key increments one counter in every row; estimating a key reads the same
+//! counters back and takes the smallest:
+//!
+//! ```text
+//!  add "foo" ==> row 0, column 2; row 1, column 0; row 2, column 3
+//!
+//!  row 0:  [ 0][ 4][ 7][ 1]
+//!  row 1:  [ 7][ 2][ 0][ 3]          estimate "foo" = min(7, 7, 9) = 7
+//!  row 2:  [ 0][ 1][ 2][ 9]
+//! ```
+//!
+//! Other keys sharing a counter can only inflate it, so estimates never fall short of the true
+//! count - and taking the minimum picks the row where the inflation was least. With a total of `N`
+//! added, choosing:
+//!
+//! ```text
+//!  width = ⌈e / ε⌉        depth = ⌈ln(1 / δ)⌉
+//! ```
+//!
+//! guarantees each estimate exceeds the true count by at most `ε · N`, except with probability `δ`.
+//!
+//! A *conservative update* tightens this further: rather than increment every row, it raises each
+//! of the key's counters only as far as the key's new estimate requires. No estimate can fall short
+//! as a result, but counters shared with other keys grow far more slowly.
+
+use std::f64::consts::E;
+
+use super::bloom::FilterError;
+use super::hashing::{mix, SipHash, SlotHasher};
+
+/// How a [CountMinSketch](struct.CountMinSketch.html) updates its counters when a key is added
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CounterUpdate {
+    /// Adds the count to the key's counter in every row
+    #[default]
+    Standard,
+    /// Raises the key's counters only as far as its new estimate - less overestimation, though
+    /// the counters no longer sum to the total added
+    Conservative,
+}
+
+/// A Count-Min sketch
+///
+/// Estimates the number of times each key has been added, never underestimating, using a grid of
+/// `width × depth` counters.
+///
+/// Should be expected to have performance characteristics of `O(d)` for adds and estimates, where
+/// `d` is the depth of the sketch.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut page_views = CountMinSketch::new(0.001, 0.01);
+/// page_views.add("/home", 3);
+/// page_views.increment("/about");
+///
+/// assert!(page_views.estimate("/home") >= 3);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountMinSketch<S = SipHash> {
+    counters: Vec<u64>,
+    width: usize,
+    depth: usize,
+    total: u64,
+    update: CounterUpdate,
+    hasher: S,
+}
+
+impl CountMinSketch<SipHash> {
+    /// Creates a sketch whose estimates exceed the true count by at most `epsilon` times the total,
+    /// with probability `1 - delta`
+    ///
+    /// See [with_hasher](struct.CountMinSketch.html#method.with_hasher).
+    pub fn new(epsilon: f64, delta: f64) -> Self {
+        Self::with_hasher(epsilon, delta, SipHash)
+    }
+}
+
+impl<S> CountMinSketch<S> {
+    /// Creates a sketch whose estimates exceed the true count by at most `epsilon` times the total,
+    /// with probability `1 - delta`, hashing keys with `hasher`
+    ///
+    /// # Arguments
+    ///
+    /// * `epsilon` - the tolerated overestimate, as a fraction of the total count
+    /// * `delta` - the chance, between `0` and `1` (exclusive), of exceeding that tolerance
+    /// * `hasher` - the hash function each row's function is derived from
+    ///
+    /// # Panics
+    ///
+    /// Panics if `epsilon` is not positive or `delta` is not strictly between `0` and `1`.
+    pub fn with_hasher(epsilon: f64, delta: f64, hasher: S) -> Self {
+        assert!(
+            epsilon > 0.0,
+            "epsilon must be greater than 0, got {}",
+            epsilon
+        );
+        assert!(
+            delta > 0.0 && delta < 1.0,
+            "delta must be between 0 and 1 (exclusive), got {}",
+            delta
+        );
+
+        let width = (E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil().max(1.0) as usize;
+        Self::with_dimensions(width, depth, hasher)
+    }
+
+    /// Creates a sketch with exactly `width` columns and `depth` rows
+    ///
+    /// # Panics
+    ///
+    /// Panics if either `width` or `depth` is zero.
+    pub fn with_dimensions(width: usize, depth: usize, hasher: S) -> Self {
+        assert!(width > 0, "width must be greater than 0");
+        assert!(depth > 0, "depth must be greater than 0");
+
+        CountMinSketch {
+            counters: vec![0; width * depth],
+            width,
+            depth,
+            total: 0,
+            update: CounterUpdate::default(),
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the sketch's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of counters in each row
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of rows
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the sum of every count added
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the most any estimate should exceed its true count by, `e / width` times the total
+    pub fn error_bound(&self) -> f64 {
+        E / self.width as f64 * self.total as f64
+    }
+
+    /// Returns how counters are updated when a key is added
+    pub fn update(&self) -> CounterUpdate {
+        self.update
+    }
+
+    /// Changes how counters are updated when a key is added
+    ///
+    /// Estimates remain upper bounds after switching, in either direction.
+    pub fn set_update(&mut self, update: CounterUpdate) {
+        self.update = update;
+    }
+
+    /// Adds `count` occurrences of a key
+    pub fn add<K>(&mut self, key: &K, count: u64)
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let cells: Vec<usize> = self.cells(key).collect();
+        match self.update {
+            CounterUpdate::Standard => {
+                for cell in cells {
+                    self.counters[cell] = self.counters[cell].saturating_add(count);
+                }
+            }
+            CounterUpdate::Conservative => {
+                let estimate = cells.iter().map(|cell| self.counters[*cell]).min();
+                let target = estimate.unwrap_or(0).saturating_add(count);
+                for cell in cells {
+                    self.counters[cell] = self.counters[cell].max(target);
+                }
+            }
+        }
+        self.total = self.total.saturating_add(count);
+    }
+
+    /// Adds a single occurrence of a key
+    pub fn increment<K>(&mut self, key: &K)
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        self.add(key, 1)
+    }
+
+    /// Estimates the number of occurrences of a key - never less than the true count
+    pub fn estimate<K>(&self, key: &K) -> u64
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        self.cells(key)
+            .map(|cell| self.counters[cell])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Resets every counter
+    pub fn clear(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter = 0;
+        }
+        self.total = 0;
+    }
+
+    /// Adds the counts of `other` to this sketch
+    ///
+    /// The sketches must share dimensions and a hasher. Merging standard sketches gives exactly
+    /// the sketch of the combined keys; merging conservative ones still gives upper bounds.
+    pub fn merge(&mut self, other: &Self) -> Result<(), FilterError>
+    where
+        S: PartialEq,
+    {
+        if self.width != other.width || self.depth != other.depth || self.hasher != other.hasher {
+            return Err(FilterError::Incompatible);
+        }
+
+        for (counter, theirs) in self.counters.iter_mut().zip(&other.counters) {
+            *counter = counter.saturating_add(*theirs);
+        }
+        self.total = self.total.saturating_add(other.total);
+        Ok(())
+    }
+
+    /// The index of the key's counter in each row
+    fn cells<K>(&self, key: &K) -> impl Iterator<Item = usize>
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let hash = self.hasher.hash(key);
+        let width = self.width;
+        (0..self.depth)
+            .map(move |row| row * width + (mix(hash, row as u64) % width as u64) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A skewed stream: key `i` appears `1000 / (i + 1)` times
+    fn zipf_stream() -> Vec<(u32, u64)> {
+        (0..1000u32)
+            .map(|i| (i, 1000 / (u64::from(i) + 1)))
+            .collect()
+    }
+
+    #[test]
+    fn dimensions_follow_epsilon_and_delta() {
+        let sketch = CountMinSketch::new(0.01, 0.01);
+        assert_eq!(sketch.width(), 272);
+        assert_eq!(sketch.depth(), 5)
+    }
+
+    #[test]
+    fn estimates_are_upper_bounds_within_the_error_bound() {
+        let mut sketch = CountMinSketch::new(0.01, 0.001);
+        for (key, count) in zipf_stream() {
+            sketch.add(&key, count);
+        }
+        for (key, count) in zipf_stream() {
+            let estimate = sketch.estimate(&key);
+            assert!(estimate >= count);
+            assert!((estimate - count) as f64 <= sketch.error_bound())
+        }
+    }
+
+    #[test]
+    fn conservative_update_never_overestimates_more() {
+        let mut standard = CountMinSketch::with_dimensions(64, 3, SipHash);
+        let mut conservative = standard.clone();
+        conservative.set_update(CounterUpdate::Conservative);
+        for (key, count) in zipf_stream() {
+            standard.add(&key, count);
+            conservative.add(&key, count);
+        }
+
+        let error = |sketch: &CountMinSketch| -> u64 {
+            zipf_stream()
+                .iter()
+                .map(|(key, count)| sketch.estimate(key) - count)
+                .sum()
+        };
+        for (key, count) in zipf_stream() {
+            assert!(conservative.estimate(&key) >= count);
+            assert!(conservative.estimate(&key) <= standard.estimate(&key))
+        }
+        assert!(error(&conservative) < error(&standard))
+    }
+
+    #[test]
+    fn unseen_keys_on_an_empty_sketch_are_zero() {
+        let sketch = CountMinSketch::new(0.01, 0.01);
+        assert_eq!(sketch.estimate("John"), 0)
+    }
+
+    #[test]
+    fn merging_adds_counts() {
+        let mut left = CountMinSketch::new(0.01, 0.01);
+        let mut right = CountMinSketch::new(0.01, 0.01);
+        left.add("John", 3);
+        right.add("John", 4);
+        left.merge(&right).unwrap();
+        assert_eq!(left.estimate("John"), 7);
+        assert_eq!(left.total(), 7);
+
+        let narrow = CountMinSketch::new(0.1, 0.01);
+        assert_eq!(left.merge(&narrow), Err(FilterError::Incompatible))
+    }
+}
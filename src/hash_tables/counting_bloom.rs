@@ -0,0 +1,233 @@
+//! Counting Bloom Filter
+//!
+//! `counting_bloom` provides [CountingBloomFilter](struct.CountingBloomFilter.html) - a Bloom
+//! filter that can remove keys.
+//!
+//! A plain Bloom filter cannot unset a key's bits, since other keys may share them. Replacing
+//! each bit with a small counter fixes that: inserting increments the key's `k` counters,
+//! removing decrements them, and a key is probably present while all of its counters are
+//! non-zero.
+//!
+//! ```text
+//!  insert "foo" ==> 1, 4, 6     insert "bar" ==> 1, 3, 6     remove "foo" ==> 1, 4, 6
+//!
+//!  [0][1][0][0][1][0][1][0]     [0][2][0][1][1][0][2][0]     [0][1][0][1][0][0][1][0]
+//! ```
+//!
+//! The price is space - each counter here is a byte, eight times the size of a bit. A counter
+//! that reaches its maximum is left there for good, as we can no longer know how many keys it
+//! stands for.
+
+use super::bloom::{bit_indexes, optimal_parameters, FilterError};
+use super::hashing::{SipHash, SlotHasher};
+use super::membership::ApproxMembership;
+
+/// A counting Bloom filter
+///
+/// A Bloom filter with a byte-sized counter in place of each bit, so that keys can be removed
+/// as well as inserted.
+///
+/// Should be expected to have performance characteristics of `O(k)` for inserts, lookups and
+/// removals, where `k` is the number of hash functions.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = CountingBloomFilter::new(1000, 0.01);
+/// phone_book.insert("John").unwrap();
+/// phone_book.remove("John");
+///
+/// assert!(!phone_book.contains("John"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountingBloomFilter<S = SipHash> {
+    counters: Vec<u8>,
+    num_hashes: u32,
+    len: usize,
+    hasher: S,
+}
+
+impl CountingBloomFilter<SipHash> {
+    /// Creates a filter sized to hold `expected_items` keys at the given false positive rate
+    ///
+    /// See [with_hasher](struct.CountingBloomFilter.html#method.with_hasher).
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        Self::with_hasher(expected_items, false_positive_rate, SipHash)
+    }
+}
+
+impl<S> CountingBloomFilter<S> {
+    /// Creates a filter sized to hold `expected_items` keys at the given false positive rate,
+    /// hashing keys with `hasher`
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_items` - the number of keys the filter is sized for
+    /// * `false_positive_rate` - the chance, between `0` and `1` (exclusive), of a false positive
+    ///   once `expected_items` keys have been inserted
+    /// * `hasher` - the hash function the filter's `k` functions are derived from
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_rate` is not strictly between `0` and `1`.
+    pub fn with_hasher(expected_items: usize, false_positive_rate: f64, hasher: S) -> Self {
+        let (num_counters, num_hashes) = optimal_parameters(expected_items, false_positive_rate);
+        CountingBloomFilter {
+            counters: vec![0; num_counters],
+            num_hashes,
+            len: 0,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the filter's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of counters in the filter
+    pub fn num_counters(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// Returns the number of hash functions applied to each key
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// Returns the number of counters stuck at their maximum, which removals can no longer clear
+    pub fn saturated_counters(&self) -> usize {
+        self.counters.iter().filter(|c| **c == u8::MAX).count()
+    }
+
+    /// Resets every counter
+    pub fn clear(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter = 0;
+        }
+        self.len = 0;
+    }
+
+    fn indexes<K>(&self, key: &K) -> impl Iterator<Item = usize>
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        bit_indexes(self.hasher.hash(key), self.num_hashes, self.counters.len())
+    }
+}
+
+impl<S> ApproxMembership for CountingBloomFilter<S> {
+    type Hasher = S;
+
+    /// Increments the key's counters - never fails, though a saturated counter stays saturated
+    fn insert<K>(&mut self, key: &K) -> Result<(), FilterError>
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        for index in self.indexes(key) {
+            self.counters[index] = self.counters[index].saturating_add(1);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn contains<K>(&self, key: &K) -> bool
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        self.indexes(key).all(|index| self.counters[index] > 0)
+    }
+
+    fn remove<K>(&mut self, key: &K) -> bool
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        if !self.contains(key) {
+            return false;
+        }
+
+        for index in self.indexes(key) {
+            if self.counters[index] < u8::MAX {
+                self.counters[index] -= 1;
+            }
+        }
+        self.len = self.len.saturating_sub(1);
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.counters.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_keys_are_forgotten() {
+        let mut filter = CountingBloomFilter::new(100, 0.001);
+        filter.insert("John").unwrap();
+        filter.insert("Ringo").unwrap();
+        assert!(filter.remove("John"));
+        assert!(!filter.contains("John"));
+        assert!(filter.contains("Ringo"));
+        assert_eq!(filter.len(), 1)
+    }
+
+    #[test]
+    fn removing_an_absent_key_changes_nothing() {
+        let mut filter = CountingBloomFilter::new(100, 0.001);
+        filter.insert("John").unwrap();
+        let before = filter.clone();
+        assert!(!filter.remove("Ringo"));
+        assert_eq!(filter, before)
+    }
+
+    #[test]
+    fn duplicate_inserts_need_matching_removals() {
+        let mut filter = CountingBloomFilter::new(100, 0.001);
+        filter.insert("John").unwrap();
+        filter.insert("John").unwrap();
+        filter.remove("John");
+        assert!(filter.contains("John"));
+        filter.remove("John");
+        assert!(!filter.contains("John"))
+    }
+
+    #[test]
+    fn survivors_are_still_found_after_churn() {
+        let mut filter = CountingBloomFilter::new(2000, 0.01);
+        for i in 0..2000 {
+            filter.insert(&i).unwrap();
+        }
+        for i in (0..2000).step_by(2) {
+            filter.remove(&i);
+        }
+        assert!((1..2000).step_by(2).all(|i| filter.contains(&i)));
+        assert!(
+            filter.measure_false_positive_rate(&(0..2000).step_by(2).collect::<Vec<_>>()) < 0.01
+        )
+    }
+
+    #[test]
+    fn saturated_counters_are_never_decremented() {
+        let mut filter = CountingBloomFilter::new(10, 0.1);
+        for _ in 0..300 {
+            filter.insert("John").unwrap();
+        }
+        assert!(filter.saturated_counters() > 0);
+        for _ in 0..300 {
+            filter.remove("John");
+        }
+        assert!(filter.contains("John"))
+    }
+}
@@ -0,0 +1,576 @@
+//! Cuckoo Hashing
+//!
+//! `cuckoo` provides [CuckooMap](struct.CuckooMap.html) - a table whose lookups examine at most two
+//! slots, no matter how full the table or how unlucky the keys.
+//!
+//! The table is split in two, each half with its own hash function, and every key may live in
+//! exactly one of two places: its slot in the first half, or its slot in the second. A lookup
+//! checks both and is done.
+//!
+//! Inserting is where the work lies. If the key's slot in the first half is taken, the new key
+//! moves in anyway and kicks the resident out - like a cuckoo chick - to its alternate slot in
+//! the other half, which may in turn evict another resident, and so on:
+//!
+//! ```text
+//!  first:   [ ][a][ ][c]         first:   [ ][x][ ][c]
+//!  second:  [b][ ][ ][ ]   ==>   second:  [b][ ][a][ ]
+//!
+//!  inserting `x` (slot 1 of first) evicts `a` to its alternate, slot 2 of second
+//! ```
+//!
+//! Occasionally the evictions go round in a cycle. Rather than chase it forever, the chain is
+//! cut off after a bounded number of evictions and the whole table is rehashed with a fresh pair
+//! of hash functions. That keeps the worst case of lookups a hard `O(1)`, and inserts `O(1)` in
+//! expectation.
+//!
+//! No pair of functions can separate keys sharing a full hash, though - three such keys have only
+//! two slots between them. An entry which fresh functions can't place is set aside in a small
+//! *stash*, which lookups check after the two slots:
+//!
+//! ```text
+//!  first:   [ ][a][ ][c]
+//!  second:  [b][ ][x][ ]     stash: [ y ]    `y` shares its hash with `a` and `x`
+//! ```
+//!
+//! The stash holds at most four entries, so checking it is `O(1)` too. Should it overflow, the
+//! table grows instead - and if even that can't bring the stash back within bounds, as when more
+//! keys share a hash than two slots and the stash can hold, the insert is refused. With a
+//! reasonable hasher the stash stays empty, and each rehash tries to empty it again.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::mem;
+
+use super::hashing::{mix, SipHash, SlotHasher};
+use super::load_factor;
+
+const DEFAULT_CAPACITY: usize = 8;
+const DEFAULT_MAX_EVICTIONS: usize = 32;
+const MAX_LOAD_FACTOR: f64 = 0.5;
+const REHASH_ATTEMPTS: u64 = 4;
+const STASH_CAPACITY: usize = 4;
+
+// The slot, if any, that each entry would be placed in by a rehash
+type Plan = Vec<Option<(usize, usize)>>;
+
+/// A cuckoo hash map
+///
+/// Stores key-value pairs across two tables with independent hash functions, so that any key
+/// is found in one of exactly two slots.
+///
+/// Should be expected to have worst-case performance characteristics of `O(1)` for lookups and
+/// removals, and average performance characteristics of `O(1)` for inserts.
+///
+/// The two hash functions are derived from a single [SlotHasher](trait.SlotHasher.html), so
+/// no choice of functions can separate keys sharing a full 64-bit hash - up to four entries
+/// without a slot are kept in a stash instead, which lookups search after the two slots.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = CuckooMap::new();
+/// phone_book.insert("John", 4283058824).unwrap();
+/// phone_book.insert("Ringo", 2123134152).unwrap();
+///
+/// assert_eq!(phone_book.get("John"), Some(&4283058824));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CuckooMap<K, V, S = SipHash> {
+    tables: [Vec<Option<(K, V)>>; 2],
+    stash: Vec<(K, V)>,
+    seeds: [u64; 2],
+    generation: u64,
+    len: usize,
+    max_evictions: usize,
+    rehashes: usize,
+    hasher: S,
+}
+
+impl<K: Hash + Eq, V> CuckooMap<K, V, SipHash> {
+    /// Creates an empty map, giving up on an eviction chain after 32 evictions
+    pub fn new() -> Self {
+        Self::with_options(DEFAULT_CAPACITY, DEFAULT_MAX_EVICTIONS)
+    }
+
+    /// Creates an empty map able to hold at least `capacity` entries without resizing
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_options(capacity, DEFAULT_MAX_EVICTIONS)
+    }
+
+    /// Creates an empty map with the given capacity and eviction bound
+    ///
+    /// See [with_options_and_hasher](struct.CuckooMap.html#method.with_options_and_hasher).
+    pub fn with_options(capacity: usize, max_evictions: usize) -> Self {
+        Self::with_options_and_hasher(capacity, max_evictions, SipHash)
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> CuckooMap<K, V, S> {
+    /// Creates an empty map which hashes its keys with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_options_and_hasher(DEFAULT_CAPACITY, DEFAULT_MAX_EVICTIONS, hasher)
+    }
+
+    /// Creates an empty map with the given capacity, eviction bound and hasher
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the number of entries the map can hold without resizing
+    /// * `max_evictions` - the longest eviction chain an insert may follow before the table is rehashed
+    /// * `hasher` - the hash function both of the table's hash functions are derived from
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_evictions` is zero.
+    pub fn with_options_and_hasher(capacity: usize, max_evictions: usize, hasher: S) -> Self {
+        assert!(max_evictions > 0, "max_evictions must be greater than 0");
+
+        let per_table = (capacity as f64 / MAX_LOAD_FACTOR / 2.0).ceil() as usize;
+        let per_table = per_table.max(1).next_power_of_two();
+        CuckooMap {
+            tables: [empty_slots(per_table), empty_slots(per_table)],
+            stash: Vec::new(),
+            seeds: seeds_for(1),
+            generation: 1,
+            len: 0,
+            max_evictions,
+            rehashes: 0,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the map's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of entries in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots across both tables
+    pub fn capacity(&self) -> usize {
+        self.tables[0].len() * 2
+    }
+
+    /// Returns the current load factor - the number of entries over the number of slots
+    pub fn load_factor(&self) -> f64 {
+        load_factor(self.len, self.capacity())
+    }
+
+    /// Returns the number of times the map has been rehashed with fresh hash functions
+    pub fn rehashes(&self) -> usize {
+        self.rehashes
+    }
+
+    /// Returns the number of entries kept in the stash, rather than in either table - never more
+    /// than four
+    pub fn stashed(&self) -> usize {
+        self.stash.len()
+    }
+
+    /// Inserts a key-value pair - returns the previous value if the key was already present
+    ///
+    /// # Errors
+    ///
+    /// Hands the pair back, leaving the map unchanged, if there is nowhere to keep it - which
+    /// only happens when so many keys share its 64-bit hash that neither slot nor stash is free.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        let hash = self.hasher.hash(&key);
+        if let Some(location) = self.locate(hash, &key) {
+            let (_, v) = self.entry_mut(location);
+            return Ok(Some(mem::replace(v, value)));
+        }
+
+        if load_factor(self.len + 1, self.capacity()) > MAX_LOAD_FACTOR {
+            let _ = self.rehash(self.tables[0].len() * 2);
+        }
+
+        if let Err(entry) = self.place((key, value)) {
+            // Stashed, fresh functions may find it a slot - or if the stash overflows, more room may
+            self.stash.push(entry);
+            let rehashed = self.rehash(self.tables[0].len()).is_ok();
+            if !rehashed && self.stash.len() > STASH_CAPACITY && !self.grow_to_fit() {
+                return Err(self.stash.pop().expect("entry was just stashed"));
+            }
+        }
+        self.len += 1;
+        Ok(None)
+    }
+
+    /// Returns a reference to the value stored for `key`, if present
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let location = self.locate(self.hasher.hash(key), key)?;
+        Some(&self.entry(location).1)
+    }
+
+    /// Returns a mutable reference to the value stored for `key`, if present
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let location = self.locate(self.hasher.hash(key), key)?;
+        Some(&mut self.entry_mut(location).1)
+    }
+
+    /// Returns `true` if the map holds an entry for `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.locate(self.hasher.hash(key), key).is_some()
+    }
+
+    /// Removes the entry for `key` - returns the removed value
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let location = self.locate(self.hasher.hash(key), key)?;
+        self.len -= 1;
+        let (_, v) = match location {
+            Location::Slot(table, index) => self.tables[table][index]
+                .take()
+                .expect("found slot is occupied"),
+            Location::Stash(index) => self.stash.swap_remove(index),
+        };
+        Some(v)
+    }
+
+    /// Returns an iterator over the entries of the map
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries().map(|(k, v)| (k, v))
+    }
+
+    // Every entry - the first table's, then the second's, then the stash's
+    fn entries(&self) -> impl Iterator<Item = &(K, V)> {
+        self.tables
+            .iter()
+            .flat_map(|table| table.iter().flatten())
+            .chain(self.stash.iter())
+    }
+
+    // The slot for `hash` in the given half of the table
+    fn slot(&self, table: usize, hash: u64) -> usize {
+        slot_for(hash, self.seeds[table], self.tables[table].len())
+    }
+
+    // Checks the key's slot in each half, and then the stash - the only places it may be
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<Location>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let slot = (0..2)
+            .map(|table| (table, self.slot(table, hash)))
+            .find(|(table, index)| match &self.tables[*table][*index] {
+                Some((k, _)) => k.borrow() == key,
+                None => false,
+            });
+        match slot {
+            Some((table, index)) => Some(Location::Slot(table, index)),
+            None => self
+                .stash
+                .iter()
+                .position(|(k, _)| k.borrow() == key)
+                .map(Location::Stash),
+        }
+    }
+
+    fn entry(&self, location: Location) -> &(K, V) {
+        match location {
+            Location::Slot(table, index) => self.tables[table][index]
+                .as_ref()
+                .expect("found slot is occupied"),
+            Location::Stash(index) => &self.stash[index],
+        }
+    }
+
+    fn entry_mut(&mut self, location: Location) -> &mut (K, V) {
+        match location {
+            Location::Slot(table, index) => self.tables[table][index]
+                .as_mut()
+                .expect("found slot is occupied"),
+            Location::Stash(index) => &mut self.stash[index],
+        }
+    }
+
+    // Follows the eviction chain for an entry known not to be present. If the chain grows past
+    // `max_evictions`, each eviction is undone in turn - every entry evicted from a table sat in
+    // its own slot there, so stepping back through the tables finds where each came from - and
+    // the entry is handed back with the tables as they were
+    fn place(&mut self, mut entry: (K, V)) -> Result<(), (K, V)> {
+        let mut table = 0;
+        for _ in 0..self.max_evictions {
+            let index = self.slot(table, self.hasher.hash(&entry.0));
+            match self.tables[table][index].replace(entry) {
+                None => return Ok(()),
+                Some(evicted) => entry = evicted,
+            }
+            table ^= 1;
+        }
+
+        for _ in 0..self.max_evictions {
+            table ^= 1;
+            let index = self.slot(table, self.hasher.hash(&entry.0));
+            entry = self.tables[table][index]
+                .replace(entry)
+                .expect("evicted from an occupied slot");
+        }
+        Err(entry)
+    }
+
+    // Rebuilds the tables, `per_table` slots each, with whichever of a few fresh pairs of hash
+    // functions leaves the fewest entries - the stash's included - without a slot. Each pair is
+    // only tried out on the entries' hashes, so nothing moves unless the best of them leaves the
+    // stash within its bound, and - when not growing - smaller than it was. Otherwise returns the
+    // fewest entries any pair left without a slot
+    fn rehash(&mut self, per_table: usize) -> Result<(), usize> {
+        let hashes: Vec<u64> = self.entries().map(|(k, _)| self.hasher.hash(k)).collect();
+        let growing = per_table != self.tables[0].len();
+
+        let mut best: Option<(usize, u64, [u64; 2], Plan)> = None;
+        for attempt in 1..=REHASH_ATTEMPTS {
+            let seeds = seeds_for(self.generation + attempt);
+            let (plan, homeless) = self.plan(&hashes, per_table, seeds);
+            if best.as_ref().is_none_or(|(fewest, ..)| homeless < *fewest) {
+                best = Some((homeless, attempt, seeds, plan));
+            }
+            if homeless == 0 {
+                break;
+            }
+        }
+        let (attempt, seeds, plan) = match best {
+            Some((homeless, attempt, seeds, plan))
+                if homeless <= STASH_CAPACITY && (growing || homeless < self.stash.len()) =>
+            {
+                (attempt, seeds, plan)
+            }
+            Some((homeless, ..)) => return Err(homeless),
+            None => unreachable!("at least one pair of functions is tried"),
+        };
+
+        self.generation += attempt;
+        self.seeds = seeds;
+        self.rehashes += 1;
+
+        let [first, second] = mem::replace(
+            &mut self.tables,
+            [empty_slots(per_table), empty_slots(per_table)],
+        );
+        let stash = mem::take(&mut self.stash);
+        let entries = first
+            .into_iter()
+            .flatten()
+            .chain(second.into_iter().flatten());
+        for (entry, slot) in entries.chain(stash).zip(plan) {
+            match slot {
+                Some((table, index)) => self.tables[table][index] = Some(entry),
+                None => self.stash.push(entry),
+            }
+        }
+        Ok(())
+    }
+
+    // Doubles the tables until a rehash leaves the stash within its bound - returns `false`,
+    // with the tables as they were, once doubling stops leaving fewer entries without a slot,
+    // as happens when more keys share a hash than any size of table can separate
+    fn grow_to_fit(&mut self) -> bool {
+        let mut per_table = self.tables[0].len();
+        let mut fewest = usize::MAX;
+        loop {
+            per_table *= 2;
+            match self.rehash(per_table) {
+                Ok(()) => return true,
+                Err(homeless) if homeless < fewest => fewest = homeless,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    // Follows the eviction chains `place` would, for entries with the given hashes, in tables of
+    // `per_table` slots hashed with `seeds` - returns the slot each entry ends up in (None for
+    // those left without one), and how many were left without one
+    fn plan(&self, hashes: &[u64], per_table: usize, seeds: [u64; 2]) -> (Plan, usize) {
+        let mut occupants: [Vec<Option<usize>>; 2] = [vec![None; per_table], vec![None; per_table]];
+        let mut homeless = 0;
+        for id in 0..hashes.len() {
+            let (mut entry, mut table) = (id, 0);
+            let mut placed = false;
+            for _ in 0..self.max_evictions {
+                let index = slot_for(hashes[entry], seeds[table], per_table);
+                match occupants[table][index].replace(entry) {
+                    None => {
+                        placed = true;
+                        break;
+                    }
+                    Some(evicted) => entry = evicted,
+                }
+                table ^= 1;
+            }
+            if !placed {
+                homeless += 1;
+            }
+        }
+
+        let mut plan = vec![None; hashes.len()];
+        for (table, occupants) in occupants.iter().enumerate() {
+            for (index, occupant) in occupants.iter().enumerate() {
+                if let Some(id) = occupant {
+                    plan[*id] = Some((table, index));
+                }
+            }
+        }
+        (plan, homeless)
+    }
+}
+
+// Where an entry was found - a slot in one of the tables, or a place in the stash
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Slot(usize, usize),
+    Stash(usize),
+}
+
+fn slot_for(hash: u64, seed: u64, len: usize) -> usize {
+    mix(hash, seed) as usize & (len - 1)
+}
+
+fn seeds_for(generation: u64) -> [u64; 2] {
+    [mix(generation, 0), mix(generation, 1)]
+}
+
+impl<K: Hash + Eq, V> Default for CuckooMap<K, V, SipHash> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn empty_slots<K, V>(capacity: usize) -> Vec<Option<(K, V)>> {
+    (0..capacity).map(|_| None).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::hashing::ConstantHasher;
+    use super::*;
+
+    #[test]
+    fn insert_then_get_returns_value() {
+        let mut map = CuckooMap::new();
+        map.insert("foo", 1).unwrap();
+        map.insert("bar", 2).unwrap();
+        assert_eq!(map.get("foo"), Some(&1));
+        assert_eq!(map.get("bar"), Some(&2));
+        assert_eq!(map.get("baz"), None)
+    }
+
+    #[test]
+    fn insert_existing_key_replaces_value() {
+        let mut map = CuckooMap::new();
+        assert_eq!(map.insert("foo", 1), Ok(None));
+        assert_eq!(map.insert("foo", 2), Ok(Some(1)));
+        assert_eq!(map.len(), 1)
+    }
+
+    #[test]
+    fn every_key_sits_in_one_of_its_two_slots() {
+        let mut map = CuckooMap::with_capacity(0);
+        for i in 0..10_000 {
+            map.insert(i, i).unwrap();
+        }
+        for i in 0..10_000 {
+            let hash = map.hasher().hash(&i);
+            let first = &map.tables[0][map.slot(0, hash)];
+            let second = &map.tables[1][map.slot(1, hash)];
+            assert!(first == &Some((i, i)) || second == &Some((i, i)));
+        }
+        assert!(map.load_factor() <= MAX_LOAD_FACTOR)
+    }
+
+    #[test]
+    fn remove_frees_the_slot() {
+        let mut map = CuckooMap::new();
+        for i in 0..100 {
+            map.insert(i, i).unwrap();
+        }
+        assert_eq!(map.remove(&50), Some(50));
+        assert_eq!(map.remove(&50), None);
+        assert_eq!(map.len(), 99);
+        assert!((0..100).filter(|i| *i != 50).all(|i| map.contains_key(&i)))
+    }
+
+    #[test]
+    fn short_eviction_bound_forces_rehash_without_losing_entries() {
+        let mut map = CuckooMap::with_options(1024, 2);
+        let capacity = map.capacity();
+        for i in 0..500 {
+            map.insert(i, i * 2).unwrap();
+            assert!(map.stashed() <= STASH_CAPACITY);
+        }
+        assert!(map.rehashes() > 0);
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.capacity(), capacity);
+        assert!((0..500).all(|i| map.get(&i) == Some(&(i * 2))))
+    }
+
+    #[test]
+    fn keys_sharing_a_hash_fall_back_to_the_stash() {
+        let mut map = CuckooMap::with_hasher(ConstantHasher);
+        let keys = ["Esther", "Ben", "Bob", "Adit", "Rama"];
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(map.insert(*key, i), Ok(None));
+        }
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.stashed(), 3);
+        assert!(map.capacity() <= 32);
+
+        assert_eq!(map.insert("Bob", 9), Ok(Some(2)));
+        assert_eq!(map.remove("Esther"), Some(0));
+        assert_eq!(map.get("Bob"), Some(&9));
+        assert_eq!(map.iter().count(), 4);
+        assert!(keys[1..].iter().all(|key| map.contains_key(*key)))
+    }
+
+    #[test]
+    fn the_stash_never_outgrows_its_bound() {
+        let mut map = CuckooMap::with_hasher(ConstantHasher);
+        // Two slots and a full stash hold six keys sharing a hash - a seventh has nowhere to go
+        for i in 0..6 {
+            assert_eq!(map.insert(i, i), Ok(None));
+            assert!(map.stashed() <= STASH_CAPACITY);
+        }
+        assert_eq!(map.insert(6, 6), Err((6, 6)));
+        assert_eq!(map.stashed(), STASH_CAPACITY);
+        assert_eq!(map.len(), 6);
+        assert!((0..6).all(|i| map.get(&i) == Some(&i)));
+        assert_eq!(map.get(&6), None)
+    }
+
+    #[test]
+    fn a_failed_eviction_chain_is_undone() {
+        let mut map = CuckooMap::with_options_and_hasher(4, 3, ConstantHasher);
+        map.insert("Esther", 0).unwrap();
+        map.insert("Ben", 1).unwrap();
+        let tables = map.tables.clone();
+        assert_eq!(map.place(("Bob", 2)), Err(("Bob", 2)));
+        assert_eq!(map.tables, tables)
+    }
+}
@@ -0,0 +1,325 @@
+//! Cuckoo Filter
+//!
+//! `cuckoo_filter` provides [CuckooFilter](struct.CuckooFilter.html) - an approximate set that
+//! supports removal, built from the eviction scheme of [CuckooMap](struct.CuckooMap.html).
+//!
+//! Instead of keys, the filter stores a short fingerprint of each key - here 16 bits - in one
+//! of two candidate buckets, each holding up to four fingerprints. The trick is in choosing the
+//! second bucket from the first and the fingerprint alone:
+//!
+//! ```text
+//!  i₁ = hash(key)
+//!  i₂ = i₁ ⊕ hash(fingerprint)
+//! ```
+//!
+//! Since `⊕` undoes itself, `i₁ = i₂ ⊕ hash(fingerprint)` too - so a fingerprint can be evicted
+//! to its alternate bucket without ever knowing which key it came from.
+//!
+//! A lookup compares the key's fingerprint against the eight slots of its two buckets, and a
+//! removal deletes one matching copy. Two different keys only collide if they share both a
+//! bucket and a fingerprint, which gives a false positive rate of roughly `8 / 2¹⁶`.
+
+use super::bloom::FilterError;
+use super::hashing::{mix, SipHash, SlotHasher};
+use super::membership::ApproxMembership;
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+const TARGET_LOAD_FACTOR: f64 = 0.95;
+const EMPTY: u16 = 0;
+
+/// A cuckoo filter
+///
+/// An approximate set storing 16-bit fingerprints in buckets of four, with each key able to live
+/// in one of two buckets.
+///
+/// Should be expected to have performance characteristics of `O(1)` for lookups and removals,
+/// and average performance characteristics of `O(1)` for inserts until the filter nears capacity.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = CuckooFilter::new(1000);
+/// phone_book.insert("John").unwrap();
+/// phone_book.remove("John");
+///
+/// assert!(!phone_book.contains("John"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CuckooFilter<S = SipHash> {
+    buckets: Vec<[u16; BUCKET_SIZE]>,
+    // A fingerprint evicted by an insert that ran out of kicks, kept so it is never lost
+    victim: Option<(usize, u16)>,
+    len: usize,
+    kicks: usize,
+    hasher: S,
+}
+
+impl CuckooFilter<SipHash> {
+    /// Creates a filter with room for at least `capacity` keys
+    ///
+    /// See [with_hasher](struct.CuckooFilter.html#method.with_hasher).
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, SipHash)
+    }
+}
+
+impl<S> CuckooFilter<S> {
+    /// Creates a filter with room for at least `capacity` keys, hashing keys with `hasher`
+    ///
+    /// Buckets are allocated so that `capacity` keys fill the filter to a load factor of about
+    /// `0.95`, beyond which inserts start to fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the number of keys the filter is sized for
+    /// * `hasher` - the hash function buckets and fingerprints are derived from
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        let needed = (capacity as f64 / BUCKET_SIZE as f64 / TARGET_LOAD_FACTOR).ceil() as usize;
+        CuckooFilter {
+            buckets: vec![[EMPTY; BUCKET_SIZE]; needed.max(1).next_power_of_two()],
+            victim: None,
+            len: 0,
+            kicks: 0,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the filter's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of fingerprints the filter can hold
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    /// Returns the fraction of fingerprint slots in use
+    pub fn load_factor(&self) -> f64 {
+        super::load_factor(self.len, self.capacity())
+    }
+
+    /// Removes every fingerprint
+    pub fn clear(&mut self) {
+        for bucket in self.buckets.iter_mut() {
+            *bucket = [EMPTY; BUCKET_SIZE];
+        }
+        self.victim = None;
+        self.len = 0;
+    }
+
+    fn mask(&self) -> usize {
+        self.buckets.len() - 1
+    }
+
+    fn locate<K>(&self, key: &K) -> (usize, usize, u16)
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let hash = self.hasher.hash(key);
+        // Zero marks an empty slot, so it is never a fingerprint
+        let fingerprint = match (mix(hash, 1) >> 48) as u16 {
+            EMPTY => 1,
+            fingerprint => fingerprint,
+        };
+        let first = hash as usize & self.mask();
+        (first, self.alternate(first, fingerprint), fingerprint)
+    }
+
+    fn alternate(&self, bucket: usize, fingerprint: u16) -> usize {
+        (bucket ^ mix(u64::from(fingerprint), 2) as usize) & self.mask()
+    }
+
+    fn try_place(&mut self, bucket: usize, fingerprint: u16) -> bool {
+        match self.buckets[bucket].iter_mut().find(|slot| **slot == EMPTY) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bucket_contains(&self, bucket: usize, fingerprint: u16) -> bool {
+        self.buckets[bucket].contains(&fingerprint)
+    }
+}
+
+impl<S> ApproxMembership for CuckooFilter<S> {
+    type Hasher = S;
+
+    /// Stores the key's fingerprint, evicting others to their alternate buckets if need be
+    ///
+    /// Fails with [FilterError::Full](enum.FilterError.html#variant.Full) once an eviction chain
+    /// has run out of room - after which a removal is needed before the next insert can succeed.
+    fn insert<K>(&mut self, key: &K) -> Result<(), FilterError>
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        if self.victim.is_some() {
+            return Err(FilterError::Full);
+        }
+
+        let (first, second, mut fingerprint) = self.locate(key);
+        if self.try_place(first, fingerprint) || self.try_place(second, fingerprint) {
+            self.len += 1;
+            return Ok(());
+        }
+
+        // Alternate between the two buckets when choosing where to start evicting
+        let mut bucket = if self.kicks.is_multiple_of(2) {
+            first
+        } else {
+            second
+        };
+        for _ in 0..MAX_KICKS {
+            let slot = self.kicks % BUCKET_SIZE;
+            self.kicks = self.kicks.wrapping_add(1);
+            std::mem::swap(&mut fingerprint, &mut self.buckets[bucket][slot]);
+            bucket = self.alternate(bucket, fingerprint);
+            if self.try_place(bucket, fingerprint) {
+                self.len += 1;
+                return Ok(());
+            }
+        }
+
+        // The key itself is in the table, but some other fingerprint is homeless - park it
+        self.victim = Some((bucket, fingerprint));
+        self.len += 1;
+        Ok(())
+    }
+
+    fn contains<K>(&self, key: &K) -> bool
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let (first, second, fingerprint) = self.locate(key);
+        let parked = self.victim.is_some_and(|(bucket, victim)| {
+            victim == fingerprint && (bucket == first || bucket == second)
+        });
+        parked
+            || self.bucket_contains(first, fingerprint)
+            || self.bucket_contains(second, fingerprint)
+    }
+
+    fn remove<K>(&mut self, key: &K) -> bool
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        let (first, second, fingerprint) = self.locate(key);
+
+        let mut removed = false;
+        for bucket in [first, second] {
+            if let Some(slot) = self.buckets[bucket]
+                .iter_mut()
+                .find(|slot| **slot == fingerprint)
+            {
+                *slot = EMPTY;
+                removed = true;
+                break;
+            }
+        }
+        if !removed {
+            match self.victim {
+                Some((bucket, victim))
+                    if victim == fingerprint && (bucket == first || bucket == second) =>
+                {
+                    self.victim = None;
+                    self.len -= 1;
+                    return true;
+                }
+                _ => return false,
+            }
+        }
+        self.len -= 1;
+
+        // A slot has opened up, so the parked fingerprint may now fit
+        if let Some((bucket, victim)) = self.victim.take() {
+            if !self.try_place(bucket, victim)
+                && !self.try_place(self.alternate(bucket, victim), victim)
+            {
+                self.victim = Some((bucket, victim));
+            }
+        }
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.buckets.len() * std::mem::size_of::<[u16; BUCKET_SIZE]>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::counting_bloom::CountingBloomFilter;
+    use super::*;
+
+    #[test]
+    fn removed_keys_are_forgotten() {
+        let mut filter = CuckooFilter::new(100);
+        filter.insert("John").unwrap();
+        filter.insert("Ringo").unwrap();
+        assert!(filter.remove("John"));
+        assert!(!filter.contains("John"));
+        assert!(filter.contains("Ringo"));
+        assert!(!filter.remove("John"));
+        assert_eq!(filter.len(), 1)
+    }
+
+    #[test]
+    fn inserted_keys_are_found_near_capacity() {
+        let mut filter = CuckooFilter::new(10_000);
+        for i in 0..10_000 {
+            filter.insert(&i).unwrap();
+        }
+        assert!((0..10_000).all(|i| filter.contains(&i)));
+        assert!(filter.measure_false_positive_rate(&(10_000..60_000).collect::<Vec<_>>()) < 0.001)
+    }
+
+    #[test]
+    fn overfilling_fails_without_losing_keys() {
+        let mut filter = CuckooFilter::new(64);
+        let mut inserted = 0;
+        while filter.insert(&inserted).is_ok() {
+            inserted += 1;
+        }
+        assert!(filter.load_factor() > 0.8);
+        assert!((0..inserted).all(|i| filter.contains(&i)));
+
+        assert!(filter.remove(&0));
+        assert!((1..inserted).all(|i| filter.contains(&i)))
+    }
+
+    #[test]
+    fn filters_can_be_swapped_behind_the_trait() {
+        fn churn<F: ApproxMembership>(filter: &mut F) -> f64
+        where
+            F::Hasher: SlotHasher<u32>,
+        {
+            for i in 0..1000u32 {
+                filter.insert(&i).unwrap();
+            }
+            for i in 0..500u32 {
+                filter.remove(&i);
+            }
+            assert_eq!(filter.len(), 500);
+            assert!((500..1000u32).all(|i| filter.contains(&i)));
+            filter.measure_false_positive_rate(&(1000..11_000u32).collect::<Vec<_>>())
+        }
+
+        let mut cuckoo = CuckooFilter::new(1000);
+        let mut counting = CountingBloomFilter::new(1000, 0.001);
+        assert!(churn(&mut cuckoo) < 0.01);
+        assert!(churn(&mut counting) < 0.01);
+        assert!(cuckoo.memory_usage() < counting.memory_usage())
+    }
+}
@@ -0,0 +1,189 @@
+//! Distribution
+//!
+//! `distribution` turns the question 'does this hash function give a good distribution?' into a
+//! number, by placing a corpus of keys into a fixed number of slots and measuring the result.
+//!
+//! The headline measure is Pearson's chi-squared statistic. If `n` keys are spread across `m`
+//! slots by an ideal hash function, each slot should expect `n / m` of them - and the statistic
+//! sums how far each slot strays from that expectation:
+//!
+//! ```text
+//!          (observed - expected)²
+//!   χ² = Σ ----------------------
+//!                expected
+//! ```
+//!
+//! For a uniform hash the statistic hovers around `m - 1` (the degrees of freedom), so dividing
+//! by that gives a score near `1.0` for a good hasher, and far above it for a poor one.
+
+use super::hashing::SlotHasher;
+use super::load_factor;
+
+/// The result of placing a corpus of keys into slots with a given hasher
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistributionReport {
+    /// The number of keys placed in each slot
+    pub histogram: Vec<usize>,
+    /// The number of keys analysed
+    pub keys: usize,
+    /// The number of keys that landed in an already occupied slot
+    pub collisions: usize,
+    /// The most keys placed in any one slot
+    pub max_bucket: usize,
+    /// Pearson's chi-squared statistic against a uniform distribution
+    pub chi_squared: f64,
+    /// The chi-squared statistic over its degrees of freedom - close to `1.0` when uniform
+    pub uniformity: f64,
+}
+
+impl DistributionReport {
+    /// Returns the number of slots holding no keys
+    pub fn empty_slots(&self) -> usize {
+        self.histogram.iter().filter(|count| **count == 0).count()
+    }
+
+    /// Returns the load factor the keys would produce in a table of this many slots
+    pub fn load_factor(&self) -> f64 {
+        load_factor(self.keys, self.histogram.len())
+    }
+}
+
+/// A hash distribution analysis
+///
+/// Given a hasher, a corpus of keys and a slot count, places each key at `hash % slots` -
+/// returns a histogram of the slots alongside collision and uniformity measures.
+///
+/// Should be expected to have performance characteristics of `O(n + s)`, where `n` is the
+/// number of keys and `s` the number of slots.
+///
+/// # Arguments
+///
+/// * `hasher` - the hash function under test
+/// * `keys` - a corpus of keys (duplicates are counted every time they appear)
+/// * `slots` - the number of slots to distribute across
+///
+/// # Panics
+///
+/// Panics if `slots` is zero.
+///
+/// # Examples
+///
+/// ```rust
+/// let names = ["Esther", "Ben", "Bob", "Dan"];
+/// let report = analyze_distribution(&PrimeSumHasher, names.iter().copied(), 10);
+/// assert_eq!(report.collisions, 0);
+/// ```
+pub fn analyze_distribution<K, H, I>(hasher: &H, keys: I, slots: usize) -> DistributionReport
+where
+    H: SlotHasher<K>,
+    I: IntoIterator<Item = K>,
+{
+    assert!(slots > 0, "cannot distribute keys across zero slots");
+
+    let mut histogram = vec![0; slots];
+    let mut collisions = 0;
+    let mut count = 0;
+
+    for key in keys {
+        let slot = (hasher.hash(&key) % slots as u64) as usize;
+        if histogram[slot] > 0 {
+            collisions += 1;
+        }
+        histogram[slot] += 1;
+        count += 1;
+    }
+
+    let expected = count as f64 / slots as f64;
+    let chi_squared = match count {
+        0 => 0.0,
+        _ => histogram
+            .iter()
+            .map(|observed| (*observed as f64 - expected).powi(2) / expected)
+            .sum(),
+    };
+    let uniformity = match slots {
+        1 => 1.0,
+        _ => chi_squared / (slots - 1) as f64,
+    };
+
+    DistributionReport {
+        max_bucket: histogram.iter().copied().max().unwrap_or(0),
+        histogram,
+        keys: count,
+        collisions,
+        chi_squared,
+        uniformity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::hashing::{
+        ConstantHasher, FirstCharHasher, Fnv1a, LengthHasher, PrimeSumHasher, SipHash,
+    };
+    use super::*;
+
+    #[test]
+    fn histogram_accounts_for_every_key() {
+        let report = analyze_distribution(&SipHash, 0..1000, 16);
+        assert_eq!(report.histogram.len(), 16);
+        assert_eq!(report.histogram.iter().sum::<usize>(), 1000);
+        assert_eq!(report.keys, 1000);
+        assert_eq!(report.load_factor(), 62.5)
+    }
+
+    #[test]
+    fn constant_hasher_collides_every_key_after_the_first() {
+        let names = ["Esther", "Ben", "Bob", "Dan"];
+        let report = analyze_distribution(&ConstantHasher, names.iter().copied(), 10);
+        assert_eq!(report.collisions, 3);
+        assert_eq!(report.max_bucket, 4);
+        assert_eq!(report.empty_slots(), 9);
+        assert_eq!(report.histogram[1], 4)
+    }
+
+    #[test]
+    fn uniformity_is_near_one_for_general_purpose_hashers() {
+        let keys: Vec<String> = (0..10_000).map(|i| format!("key-{}", i)).collect();
+        for report in &[
+            analyze_distribution(&SipHash, keys.iter(), 64),
+            analyze_distribution(&Fnv1a, keys.iter(), 64),
+        ] {
+            assert!(report.uniformity < 2.0, "{:?}", report.uniformity);
+        }
+    }
+
+    #[test]
+    fn uniformity_is_poor_for_exercise_hashers() {
+        let keys: Vec<String> = (0..10_000).map(|i| format!("key-{}", i)).collect();
+        for report in &[
+            analyze_distribution(&ConstantHasher, keys.iter(), 64),
+            analyze_distribution(&LengthHasher, keys.iter(), 64),
+            analyze_distribution(&FirstCharHasher, keys.iter(), 64),
+        ] {
+            assert!(report.uniformity > 10.0, "{:?}", report.uniformity);
+        }
+    }
+
+    #[test]
+    fn exercise_5_7_book_titles_place_without_collision_under_d() {
+        let titles = ["Maus", "Fun Home", "Watchmen"];
+        let report = analyze_distribution(&PrimeSumHasher, titles.iter().copied(), 10);
+        assert_eq!(report.collisions, 0);
+        assert_eq!(report.max_bucket, 1)
+    }
+
+    #[test]
+    fn empty_corpus_reports_nothing() {
+        let report = analyze_distribution(&SipHash, Vec::<u64>::new(), 8);
+        assert_eq!(report.collisions, 0);
+        assert_eq!(report.max_bucket, 0);
+        assert_eq!(report.chi_squared, 0.0)
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_slots_is_rejected() {
+        analyze_distribution(&SipHash, 0..10, 0);
+    }
+}
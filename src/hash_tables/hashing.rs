@@ -0,0 +1,295 @@
+//! Hashing
+//!
+//! `hashing` provides the [SlotHasher](trait.SlotHasher.html) trait that the tables of this module
+//! are generic over, along with a handful of implementations.
+//!
+//! A `SlotHasher` maps a key to a 64-bit value - it is then up to the table to reduce that value to
+//! one of its slots (most simply, `hash % slots`). Splitting the work this way lets the four string
+//! hashers from the exercises be expressed exactly as described:
+//!
+//! * [ConstantHasher](struct.ConstantHasher.html) - A. Return '1' for all input
+//! * [LengthHasher](struct.LengthHasher.html) - B. Use the length of the string as the index
+//! * [FirstCharHasher](struct.FirstCharHasher.html) - C. Use the first character of the string as the index
+//! * [PrimeSumHasher](struct.PrimeSumHasher.html) - D. Map every letter to a prime number and sum them
+//!
+//! None of the above should be used in earnest! For real keys, two general purpose hashers are also
+//! provided: [Fnv1a](struct.Fnv1a.html), which is small and fast, and [SipHash](struct.SipHash.html),
+//! which is what the standard library's `HashMap` uses and is the default for the tables here.
+//!
+//! Structures kept on disk hash keys they have already encoded, through
+//! [ByteHasher](trait.ByteHasher.html) - which hashes exactly the bytes it is given, so that a
+//! key hashes the same on every machine.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// The first twenty-six primes - one per letter, so that `a = 2, b = 3, c = 5, ... z = 101`
+const LETTER_PRIMES: [u64; 26] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101,
+];
+
+/// A hash function that the tables of this module can be built over
+///
+/// Implementations must be consistent - the same key must always produce the same value - and
+/// keys that compare equal must produce the same value.
+pub trait SlotHasher<K: ?Sized> {
+    /// Hashes `key` to a 64-bit value, which tables reduce to a slot index
+    fn hash(&self, key: &K) -> u64;
+}
+
+/// A hash function over bytes exactly as given, for keys that are already encoded
+///
+/// A [SlotHasher](trait.SlotHasher.html) reaches most keys through their `Hash` implementations,
+/// which write lengths and integers in the byte order and width of the target - so the same key
+/// can hash differently on another machine. A `ByteHasher` sees only the bytes it is handed,
+/// giving the same hash on every target for the same encoding.
+pub trait ByteHasher {
+    /// Hashes `bytes` to a 64-bit value
+    fn hash_bytes(&self, bytes: &[u8]) -> u64;
+}
+
+/// A hasher that can be named in encoded bytes
+///
+/// Structures which write themselves out as bytes record their hasher's tag, so those bytes are
+/// never decoded with a different hash function than the one that produced them. Only hashers
+/// whose hashes stay the same from one build to the next should have a tag - which is why
+/// [SipHash](struct.SipHash.html) has none.
+pub trait TaggedHasher {
+    /// Eight bytes naming the hash function, distinct from every other hasher's
+    const TAG: [u8; 8];
+}
+
+/// Exercise hasher A - returns `1` for all input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConstantHasher;
+
+impl<K: ?Sized> SlotHasher<K> for ConstantHasher {
+    fn hash(&self, _key: &K) -> u64 {
+        1
+    }
+}
+
+impl ByteHasher for ConstantHasher {
+    fn hash_bytes(&self, _bytes: &[u8]) -> u64 {
+        1
+    }
+}
+
+impl TaggedHasher for ConstantHasher {
+    const TAG: [u8; 8] = *b"CONSTANT";
+}
+
+/// Exercise hasher B - returns the length of the string, in characters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LengthHasher;
+
+impl<K: AsRef<str> + ?Sized> SlotHasher<K> for LengthHasher {
+    fn hash(&self, key: &K) -> u64 {
+        key.as_ref().chars().count() as u64
+    }
+}
+
+impl TaggedHasher for LengthHasher {
+    const TAG: [u8; 8] = *b"LENGTH  ";
+}
+
+/// Exercise hasher C - returns the first character of the string (or `0` for an empty string)
+///
+/// All strings starting with the same character are hashed together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FirstCharHasher;
+
+impl<K: AsRef<str> + ?Sized> SlotHasher<K> for FirstCharHasher {
+    fn hash(&self, key: &K) -> u64 {
+        key.as_ref().chars().next().map_or(0, |c| c as u64)
+    }
+}
+
+impl TaggedHasher for FirstCharHasher {
+    const TAG: [u8; 8] = *b"1STCHAR ";
+}
+
+/// Exercise hasher D - maps every letter to a prime number and returns their sum
+///
+/// Letters are mapped case-insensitively (`a = 2, b = 3, c = 5, d = 7, e = 11, ...`) and any other
+/// characters are ignored. The table reduces the sum modulo its size, so with ten slots `"bag"`
+/// lands at `3 + 2 + 17 % 10 = 22 % 10 = 2`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrimeSumHasher;
+
+impl<K: AsRef<str> + ?Sized> SlotHasher<K> for PrimeSumHasher {
+    fn hash(&self, key: &K) -> u64 {
+        key.as_ref()
+            .chars()
+            .filter(char::is_ascii_alphabetic)
+            .map(|c| LETTER_PRIMES[(c.to_ascii_lowercase() as u8 - b'a') as usize])
+            .sum()
+    }
+}
+
+impl TaggedHasher for PrimeSumHasher {
+    const TAG: [u8; 8] = *b"PRIMESUM";
+}
+
+/// The 64-bit FNV-1a hash, fed through the key's `Hash` implementation
+///
+/// FNV-1a folds in one byte at a time - XOR the byte, then multiply by the FNV prime. It is
+/// quick for short keys, but offers no protection against deliberately colliding input. Being
+/// fixed, its hashes stay the same from one Rust release to the next - so long as the keys' own
+/// `Hash` implementations do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fnv1a;
+
+impl<K: Hash + ?Sized> SlotHasher<K> for Fnv1a {
+    fn hash(&self, key: &K) -> u64 {
+        let mut hasher = Fnv1aHasher(FNV_OFFSET_BASIS);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl ByteHasher for Fnv1a {
+    fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        let mut hasher = Fnv1aHasher(FNV_OFFSET_BASIS);
+        hasher.write(bytes);
+        hasher.finish()
+    }
+}
+
+impl TaggedHasher for Fnv1a {
+    const TAG: [u8; 8] = *b"FNV-1A64";
+}
+
+struct Fnv1aHasher(u64);
+
+impl Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// SipHash, as provided by the standard library's `DefaultHasher`
+///
+/// The keys are fixed, so hashes are the same from one run of a program to the next (which is what
+/// we want when comparing distributions) - but for that same reason it does not carry the standard
+/// library's protection against collision attacks. Nor are they promised to stay the same across
+/// Rust releases, as `DefaultHasher` may change its algorithm - so hashes which are written to disk
+/// and read back by a later build should come from [Fnv1a](struct.Fnv1a.html) instead, and it is
+/// not a [TaggedHasher](trait.TaggedHasher.html).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SipHash;
+
+impl<K: Hash + ?Sized> SlotHasher<K> for SipHash {
+    fn hash(&self, key: &K) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+// Derives a new, well-mixed hash from `hash` and `seed` (the SplitMix64 finalizer).
+//
+// Structures that need several independent hash functions - or a fresh one after a failed
+// build - derive them from a single `SlotHasher` value this way, by varying the seed.
+pub(crate) fn mix(hash: u64, seed: u64) -> u64 {
+    let mut z = hash ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::distribution::analyze_distribution;
+    use super::*;
+
+    // The chi-squared statistic a uniform hash stays under 95% of the time, with the nine
+    // degrees of freedom of the exercises' ten slots
+    const CRITICAL_CHI_SQUARED: f64 = 16.919;
+
+    // Treats a hasher as giving a good distribution across the exercises' ten slots if the
+    // keys' placement passes a chi-squared test of uniformity at the 5% significance level
+    fn good_distribution<H: for<'a> SlotHasher<&'a str>>(hasher: &H, keys: &[&str]) -> bool {
+        analyze_distribution(hasher, keys.iter().copied(), 10).chi_squared < CRITICAL_CHI_SQUARED
+    }
+
+    #[test]
+    fn prime_sum_hasher_matches_worked_example() {
+        assert_eq!(PrimeSumHasher.hash("bag") % 10, 2)
+    }
+
+    #[test]
+    fn exercise_hashers_are_consistent() {
+        for key in &["Esther", "Fun Home", "AAA", ""] {
+            assert_eq!(ConstantHasher.hash(*key), ConstantHasher.hash(*key));
+            assert_eq!(LengthHasher.hash(*key), LengthHasher.hash(*key));
+            assert_eq!(FirstCharHasher.hash(*key), FirstCharHasher.hash(*key));
+            assert_eq!(PrimeSumHasher.hash(*key), PrimeSumHasher.hash(*key));
+        }
+    }
+
+    #[test]
+    fn exercise_5_5_phonebook_is_c_and_d() {
+        let names = ["Esther", "Ben", "Bob", "Dan"];
+        assert!(!good_distribution(&ConstantHasher, &names));
+        assert!(!good_distribution(&LengthHasher, &names));
+        assert!(good_distribution(&FirstCharHasher, &names));
+        assert!(good_distribution(&PrimeSumHasher, &names))
+    }
+
+    #[test]
+    fn exercise_5_6_battery_sizes_are_b_and_d() {
+        let sizes = ["A", "AA", "AAA", "AAAA"];
+        assert!(!good_distribution(&ConstantHasher, &sizes));
+        assert!(good_distribution(&LengthHasher, &sizes));
+        assert!(!good_distribution(&FirstCharHasher, &sizes));
+        assert!(good_distribution(&PrimeSumHasher, &sizes))
+    }
+
+    #[test]
+    fn exercise_5_7_book_titles_are_b_c_and_d() {
+        let titles = ["Maus", "Fun Home", "Watchmen"];
+        assert!(!good_distribution(&ConstantHasher, &titles));
+        assert!(good_distribution(&LengthHasher, &titles));
+        assert!(good_distribution(&FirstCharHasher, &titles));
+        assert!(good_distribution(&PrimeSumHasher, &titles))
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_vector_for_raw_bytes() {
+        let mut hasher = Fnv1aHasher(FNV_OFFSET_BASIS);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c)
+    }
+
+    #[test]
+    fn fnv1a_hashes_bytes_without_a_length_prefix() {
+        assert_eq!(Fnv1a.hash_bytes(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(Fnv1a.hash_bytes(b""), FNV_OFFSET_BASIS)
+    }
+
+    #[test]
+    fn mix_separates_seeds_for_the_same_hash() {
+        assert_eq!(mix(42, 1), mix(42, 1));
+        assert_ne!(mix(42, 1), mix(42, 2));
+        assert_ne!(mix(42, 1), mix(43, 1))
+    }
+
+    #[test]
+    fn general_purpose_hashers_agree_for_borrowed_forms() {
+        let owned = String::from("Watchmen");
+        assert_eq!(Fnv1a.hash(&owned), Fnv1a.hash("Watchmen"));
+        assert_eq!(SipHash.hash(&owned), SipHash.hash("Watchmen"))
+    }
+}
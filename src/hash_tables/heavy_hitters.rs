@@ -0,0 +1,202 @@
+//! Heavy Hitters
+//!
+//! `heavy_hitters` provides [HeavyHitters](struct.HeavyHitters.html) - the `k` most frequent
+//! keys of a stream, answered without a counter for every key.
+//!
+//! A [CountMinSketch](struct.CountMinSketch.html) can estimate any key's count, but it cannot list
+//! the keys it has seen. So alongside the sketch we keep a small [HashTable](struct.HashTable.html)
+//! of at most `k` candidates. Each time a key is added, its estimate is read back from the sketch -
+//! if the key is a candidate, its count is refreshed, and if not, it replaces the weakest candidate
+//! as soon as its estimate is larger.
+//!
+//! A key that is truly hot soon overtakes the weakest candidate no matter when it first appears, so
+//! memory stays at `O(k)` plus the fixed size of the sketch.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+use super::count_min::{CountMinSketch, CounterUpdate};
+use super::hashing::{SipHash, SlotHasher};
+use super::open_addressing::HashTable;
+
+/// A top-`k` heavy hitters tracker
+///
+/// Tracks the `k` keys with the highest estimated counts, with estimates drawn from a
+/// conservatively updated [CountMinSketch](struct.CountMinSketch.html).
+///
+/// Should be expected to have performance characteristics of `O(d)` for adds of current
+/// candidates, and `O(d + k)` for adds of other keys, where `d` is the depth of the sketch.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut hot_pages = HeavyHitters::new(2, 0.001, 0.01);
+/// for page in &["/home", "/about", "/home", "/blog", "/home", "/blog"] {
+///     hot_pages.increment(page.to_string());
+/// }
+///
+/// assert_eq!(hot_pages.top()[0], (&"/home".to_string(), 3));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeavyHitters<K, S = SipHash> {
+    sketch: CountMinSketch<S>,
+    candidates: HashTable<K, u64, S>,
+    k: usize,
+}
+
+impl<K: Hash + Eq + Clone> HeavyHitters<K, SipHash> {
+    /// Creates a tracker of the `k` hottest keys, with a sketch sized by `epsilon` and `delta`
+    ///
+    /// See [with_hasher](struct.HeavyHitters.html#method.with_hasher).
+    pub fn new(k: usize, epsilon: f64, delta: f64) -> Self {
+        Self::with_hasher(k, epsilon, delta, SipHash)
+    }
+}
+
+impl<K: Eq + Clone, S: SlotHasher<K> + Clone> HeavyHitters<K, S> {
+    /// Creates a tracker of the `k` hottest keys, with a sketch sized by `epsilon` and `delta`,
+    /// hashing keys with `hasher`
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - the number of keys to track
+    /// * `epsilon` - the sketch's tolerated overestimate, as a fraction of the total count
+    /// * `delta` - the chance of the sketch exceeding that tolerance
+    /// * `hasher` - the hash function shared by the sketch and the candidate table
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is zero, or under the same conditions as
+    /// [CountMinSketch::with_hasher](struct.CountMinSketch.html#method.with_hasher).
+    pub fn with_hasher(k: usize, epsilon: f64, delta: f64, hasher: S) -> Self {
+        assert!(k > 0, "k must be greater than 0");
+
+        let mut sketch = CountMinSketch::with_hasher(epsilon, delta, hasher.clone());
+        sketch.set_update(CounterUpdate::Conservative);
+        HeavyHitters {
+            sketch,
+            candidates: HashTable::with_hasher(hasher),
+            k,
+        }
+    }
+
+    /// Returns the number of keys tracked
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the underlying sketch, which estimates the count of any key
+    pub fn sketch(&self) -> &CountMinSketch<S> {
+        &self.sketch
+    }
+
+    /// Adds `count` occurrences of a key
+    pub fn add(&mut self, key: K, count: u64) {
+        self.sketch.add(&key, count);
+        let estimate = self.sketch.estimate(&key);
+
+        if let Some(tracked) = self.candidates.get_mut(&key) {
+            *tracked = estimate;
+            return;
+        }
+        if self.candidates.len() < self.k {
+            self.candidates.insert(key, estimate);
+            return;
+        }
+
+        let weakest = self
+            .candidates
+            .iter()
+            .min_by_key(|(_, tracked)| **tracked)
+            .map(|(weakest, tracked)| (weakest.clone(), *tracked));
+        if let Some((weakest, tracked)) = weakest {
+            if estimate > tracked {
+                self.candidates.remove(&weakest);
+                self.candidates.insert(key, estimate);
+            }
+        }
+    }
+
+    /// Adds a single occurrence of a key
+    pub fn increment(&mut self, key: K) {
+        self.add(key, 1)
+    }
+
+    /// Estimates the number of occurrences of any key
+    pub fn estimate<Q>(&self, key: &Q) -> u64
+    where
+        Q: ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.sketch.estimate(key)
+    }
+
+    /// Returns `true` if the key is currently among the tracked candidates
+    pub fn is_candidate<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.candidates.contains_key(key)
+    }
+
+    /// Returns the tracked keys and their estimated counts, hottest first
+    pub fn top(&self) -> Vec<(&K, u64)> {
+        let mut top: Vec<(&K, u64)> = self
+            .candidates
+            .iter()
+            .map(|(key, _)| (key, self.sketch.estimate(key)))
+            .collect();
+        top.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        top
+    }
+
+    /// Forgets every key
+    pub fn clear(&mut self) {
+        self.sketch.clear();
+        self.candidates.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_hottest_keys_of_a_skewed_stream() {
+        let mut hitters = HeavyHitters::new(5, 0.001, 0.01);
+        // Key `i` appears `1000 / (i + 1)` times, interleaved so hot keys arrive late too
+        for round in 0..1000u64 {
+            for key in (0..1000u64).rev() {
+                if round < 1000 / (key + 1) {
+                    hitters.increment(key);
+                }
+            }
+        }
+
+        let top: Vec<u64> = hitters.top().iter().map(|(key, _)| **key).collect();
+        assert_eq!(top, vec![0, 1, 2, 3, 4]);
+        assert!(hitters.top()[0].1 >= 1000)
+    }
+
+    #[test]
+    fn tracks_at_most_k_keys() {
+        let mut hitters = HeavyHitters::new(3, 0.01, 0.01);
+        for key in 0..100 {
+            hitters.add(key, key);
+        }
+        assert_eq!(hitters.top().len(), 3);
+        assert!(hitters.is_candidate(&99));
+        assert!(!hitters.is_candidate(&0))
+    }
+
+    #[test]
+    fn estimates_cover_untracked_keys() {
+        let mut hitters = HeavyHitters::new(1, 0.001, 0.01);
+        hitters.add("John".to_string(), 10);
+        hitters.add("Ringo".to_string(), 4);
+        assert!(!hitters.is_candidate("Ringo"));
+        assert!(hitters.estimate("Ringo") >= 4)
+    }
+}
@@ -0,0 +1,366 @@
+//! Inverted Index
+//!
+//! `inverted_index` provides [InvertedIndex](struct.InvertedIndex.html) - full-text search over a
+//! collection of documents, with a [HashTable](struct.HashTable.html) at its core.
+//!
+//! Scanning every document for every search would be `O(n)` in the size of the whole collection.
+//! Instead, each document is split into lowercase *terms* once, as it is added, and the index maps
+//! every term to its *posting list* - the documents it appears in, and where:
+//!
+//! ```text
+//!  0: "Restart the cache"        "cache"   --> [ 0 @ [2], 1 @ [1] ]
+//!  1: "Flush the cache"          "flush"   --> [ 1 @ [0] ]
+//!                                "restart" --> [ 0 @ [0] ]
+//!                                "the"     --> [ 0 @ [1], 1 @ [1] ]
+//! ```
+//!
+//! Finding a term's documents is then a single hash table lookup. Posting lists are kept in
+//! document order, so an 'all of these terms' search walks the shortest list and checks each of
+//! its documents against the others, an 'any of these terms' search merges the lists, and a phrase
+//! search narrows the 'all' results to documents where the terms' positions follow one another.
+//!
+//! Matches are ranked by TF-IDF: a term counts for more the larger the share of a document it makes
+//! up (its *term frequency*), and the fewer documents it appears in (its *inverse document
+//! frequency*) - so 'the' hardly moves the ranking, while a rare word like 'flush' decides it.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+use super::hashing::{SipHash, SlotHasher};
+use super::open_addressing::HashTable;
+
+/// The occurrences of a term in one document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posting {
+    /// The document's id, as returned by [add_document](struct.InvertedIndex.html#method.add_document)
+    pub document: usize,
+    /// The term's positions in the document, counted in terms from `0`
+    pub positions: Vec<usize>,
+}
+
+/// A document matching a search, and its TF-IDF score
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchHit {
+    /// The document's id, as returned by [add_document](struct.InvertedIndex.html#method.add_document)
+    pub document: usize,
+    /// The summed TF-IDF weight of the search terms in the document - higher is more relevant
+    pub score: f64,
+}
+
+/// Splits text into lowercase terms, breaking on anything that is not a letter or digit
+///
+/// # Examples
+///
+/// ```rust
+/// assert_eq!(tokenize("Restart the DB-proxy!"), vec!["restart", "the", "db", "proxy"]);
+/// ```
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// A full-text search index
+///
+/// Maps each term to a posting list of the documents containing it, answering searches for all
+/// of a set of terms, any of them, or an exact phrase, with the matching documents ranked by TF-IDF.
+///
+/// Should be expected to have performance characteristics of `O(t)` for adding a document of `t`
+/// terms, and `O(p)` for searches, where `p` is the combined length of the search terms' posting
+/// lists.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut runbooks = InvertedIndex::new();
+/// let restart = runbooks.add_document("Restart the cache after a deploy");
+/// let flush = runbooks.add_document("Flush the cache when it serves stale pages");
+///
+/// assert_eq!(runbooks.search_all("cache stale")[0].document, flush);
+/// assert_eq!(runbooks.search_phrase("restart the cache")[0].document, restart);
+/// ```
+#[derive(Debug, Clone)]
+pub struct InvertedIndex<S = SipHash> {
+    postings: HashTable<String, Vec<Posting>, S>,
+    document_lengths: Vec<usize>,
+}
+
+impl InvertedIndex<SipHash> {
+    /// Creates an empty index
+    pub fn new() -> Self {
+        Self::with_hasher(SipHash)
+    }
+}
+
+impl<S: SlotHasher<String> + SlotHasher<str>> InvertedIndex<S> {
+    /// Creates an empty index, hashing terms with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        InvertedIndex {
+            postings: HashTable::with_hasher(hasher),
+            document_lengths: Vec::new(),
+        }
+    }
+
+    /// Returns a reference to the index's hasher
+    pub fn hasher(&self) -> &S {
+        self.postings.hasher()
+    }
+
+    /// Returns the number of documents added
+    pub fn len(&self) -> usize {
+        self.document_lengths.len()
+    }
+
+    /// Returns `true` if no documents have been added
+    pub fn is_empty(&self) -> bool {
+        self.document_lengths.is_empty()
+    }
+
+    /// Returns the number of distinct terms across every document
+    pub fn num_terms(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Tokenizes and indexes a document - returns its id, counting up from `0`
+    pub fn add_document(&mut self, text: &str) -> usize {
+        let document = self.document_lengths.len();
+        let terms = tokenize(text);
+        self.document_lengths.push(terms.len());
+
+        for (position, term) in terms.into_iter().enumerate() {
+            if self.postings.get(term.as_str()).is_none() {
+                self.postings.insert(term.clone(), Vec::new());
+            }
+            let list = self.postings.get_mut(term.as_str()).unwrap();
+            match list.last_mut() {
+                Some(posting) if posting.document == document => posting.positions.push(position),
+                _ => list.push(Posting {
+                    document,
+                    positions: vec![position],
+                }),
+            }
+        }
+        document
+    }
+
+    /// Returns the posting list of a term, in document order - empty if no document contains it
+    ///
+    /// The term is matched exactly, so should already be lowercase.
+    pub fn postings<Q>(&self, term: &Q) -> &[Posting]
+    where
+        String: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.postings.get(term).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the number of documents containing a term
+    pub fn document_frequency(&self, term: &str) -> usize {
+        self.postings(term).len()
+    }
+
+    /// Returns the documents containing every term of `query`, most relevant first
+    pub fn search_all(&self, query: &str) -> Vec<SearchHit> {
+        let terms = unique(tokenize(query));
+        let documents = self.intersect(&terms);
+        self.rank(&terms, documents)
+    }
+
+    /// Returns the documents containing any term of `query`, most relevant first
+    pub fn search_any(&self, query: &str) -> Vec<SearchHit> {
+        let terms = unique(tokenize(query));
+        let mut documents: Vec<usize> = terms
+            .iter()
+            .flat_map(|term| self.postings(term.as_str()).iter())
+            .map(|posting| posting.document)
+            .collect();
+        documents.sort_unstable();
+        documents.dedup();
+        self.rank(&terms, documents)
+    }
+
+    /// Returns the documents containing the terms of `query` consecutively and in order, most
+    /// relevant first
+    pub fn search_phrase(&self, query: &str) -> Vec<SearchHit> {
+        let phrase = tokenize(query);
+        let terms = unique(phrase.clone());
+        let documents = self
+            .intersect(&terms)
+            .into_iter()
+            .filter(|&document| self.contains_phrase(document, &phrase))
+            .collect();
+        self.rank(&terms, documents)
+    }
+
+    /// Returns the documents containing every term, walking the shortest posting list
+    fn intersect(&self, terms: &[String]) -> Vec<usize> {
+        let mut lists: Vec<&[Posting]> = terms
+            .iter()
+            .map(|term| self.postings(term.as_str()))
+            .collect();
+        lists.sort_by_key(|list| list.len());
+
+        let (shortest, rest) = match lists.split_first() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+        shortest
+            .iter()
+            .map(|posting| posting.document)
+            .filter(|&document| {
+                rest.iter()
+                    .all(|list| find_posting(list, document).is_some())
+            })
+            .collect()
+    }
+
+    fn contains_phrase(&self, document: usize, phrase: &[String]) -> bool {
+        let positions: Vec<&[usize]> = phrase
+            .iter()
+            .filter_map(|term| find_posting(self.postings(term.as_str()), document))
+            .map(|posting| posting.positions.as_slice())
+            .collect();
+        if positions.len() < phrase.len() || positions.is_empty() {
+            return false;
+        }
+
+        positions[0].iter().any(|&start| {
+            positions[1..]
+                .iter()
+                .enumerate()
+                .all(|(offset, later)| later.binary_search(&(start + offset + 1)).is_ok())
+        })
+    }
+
+    /// Scores each document by the summed TF-IDF of the terms, sorting the highest scores first
+    fn rank(&self, terms: &[String], documents: Vec<usize>) -> Vec<SearchHit> {
+        let mut hits: Vec<SearchHit> = documents
+            .into_iter()
+            .map(|document| SearchHit {
+                document,
+                score: terms.iter().map(|term| self.tf_idf(term, document)).sum(),
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then(a.document.cmp(&b.document))
+        });
+        hits
+    }
+
+    fn tf_idf(&self, term: &str, document: usize) -> f64 {
+        let postings = self.postings(term);
+        let occurrences = match find_posting(postings, document) {
+            Some(posting) => posting.positions.len(),
+            None => return 0.0,
+        };
+        let tf = occurrences as f64 / self.document_lengths[document] as f64;
+        let idf = (1.0 + self.len() as f64 / postings.len() as f64).ln();
+        tf * idf
+    }
+}
+
+impl Default for InvertedIndex<SipHash> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Finds a document's posting by binary search of a posting list
+fn find_posting(list: &[Posting], document: usize) -> Option<&Posting> {
+    list.binary_search_by_key(&document, |posting| posting.document)
+        .ok()
+        .map(|index| &list[index])
+}
+
+fn unique(mut terms: Vec<String>) -> Vec<String> {
+    terms.sort_unstable();
+    terms.dedup();
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runbooks() -> InvertedIndex {
+        let mut index = InvertedIndex::new();
+        index.add_document("Restart the cache after a deploy");
+        index.add_document("Flush the cache when the cache serves stale pages");
+        index.add_document("Rotate the database credentials, then restart the workers");
+        index
+    }
+
+    fn documents(hits: &[SearchHit]) -> Vec<usize> {
+        hits.iter().map(|hit| hit.document).collect()
+    }
+
+    #[test]
+    fn tokenize_lowercases_and_splits_on_punctuation() {
+        assert_eq!(
+            tokenize("  Re-run: DB_migrate (v2)  "),
+            vec!["re", "run", "db", "migrate", "v2"]
+        );
+        assert!(tokenize("--- !!").is_empty())
+    }
+
+    #[test]
+    fn postings_record_documents_and_positions() {
+        let index = runbooks();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.document_frequency("cache"), 2);
+        assert_eq!(
+            index.postings("cache")[1],
+            Posting {
+                document: 1,
+                positions: vec![2, 5]
+            }
+        );
+        assert!(index.postings("reboot").is_empty())
+    }
+
+    #[test]
+    fn all_requires_every_term_and_any_requires_one() {
+        let index = runbooks();
+        assert_eq!(documents(&index.search_all("restart cache")), vec![0]);
+        assert_eq!(documents(&index.search_all("restart reboot")), vec![]);
+
+        let mut any = documents(&index.search_any("restart flush"));
+        any.sort_unstable();
+        assert_eq!(any, vec![0, 1, 2]);
+        assert!(index.search_any("").is_empty())
+    }
+
+    #[test]
+    fn phrases_must_appear_in_order() {
+        let index = runbooks();
+        assert_eq!(documents(&index.search_phrase("the cache")), vec![1, 0]);
+        assert_eq!(
+            documents(&index.search_phrase("restart the workers")),
+            vec![2]
+        );
+        assert!(index.search_phrase("cache the").is_empty())
+    }
+
+    #[test]
+    fn rare_and_repeated_terms_rank_higher() {
+        let index = runbooks();
+        // 'cache' makes up a larger share of document 1 than of document 0
+        assert_eq!(documents(&index.search_any("cache")), vec![1, 0]);
+        // 'the' is in every document, so 'stale' decides the ranking
+        assert_eq!(index.search_any("the stale")[0].document, 1);
+        // and appearing just as often, a term found in fewer documents scores higher
+        let score = |query, document| {
+            let hits = index.search_any(query);
+            hits.iter()
+                .find(|hit| hit.document == document)
+                .unwrap()
+                .score
+        };
+        assert!(score("the", 0) < score("cache", 0))
+    }
+}
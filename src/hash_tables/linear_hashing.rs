@@ -0,0 +1,991 @@
+//! Linear Hashing On Disk
+//!
+//! `linear_hashing` provides [LinearHashIndex](struct.LinearHashIndex.html) - a hash index kept in
+//! files, for when the records to index won't fit in memory.
+//!
+//! On disk, the unit of work is the page - a fixed-size block, here `4096` bytes, read and written
+//! whole. Each bucket of the table is one *primary* page, and when a bucket's records outgrow it,
+//! further *overflow* pages are chained on behind it:
+//!
+//! ```text
+//!  bucket 0:  [ primary ]
+//!  bucket 1:  [ primary ] --> [ overflow ] --> [ overflow ]
+//!  bucket 2:  [ primary ] --> [ overflow ]
+//! ```
+//!
+//! Long chains mean many page reads per lookup, so the table must grow - but rehashing every
+//! bucket at once would mean rewriting the whole file. Linear hashing grows one bucket at a time
+//! instead. A *split pointer* walks through the buckets in order, and each time the load factor
+//! crosses its maximum, the bucket under the pointer is split in two: its records are rehashed with
+//! one more bit of their hash, and either stay or move to a new bucket appended at the end.
+//!
+//! ```text
+//!  level 0, 4 buckets, split = 1        bucket = hash mod 4, or hash mod 8 if that is < split
+//!
+//!  [ 0 ][ 1 ][ 2 ][ 3 ][ 4 ]            bucket 0 has split into 0 and 4
+//!         ^
+//! ```
+//!
+//! Once the pointer has passed every bucket, the table has doubled, the level goes up, and the
+//! pointer starts again from bucket `0`. The bucket being split is rarely the overflowing one, but
+//! every bucket gets its turn - so chains stay short on average, at the cost of one page split at
+//! a time.
+//!
+//! To survive a crash mid-write, every change is first appended to a *log* as the complete new
+//! contents of each page it touches, followed by a checksum, and flushed to disk - only then are
+//! the pages overwritten in place. Reopening the index replays any complete log entries, repairing
+//! pages that were only half written; an entry cut short by the crash fails its checksum and is
+//! discarded, as if the change had never begun. Every so often the page files are flushed and the
+//! log is emptied - a *checkpoint* - so that it never grows large.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use super::codec::ByteCodec;
+use super::hashing::{ByteHasher, Fnv1a};
+
+const PAGE_SIZE: usize = 4096;
+const PAGE_HEADER: usize = 10;
+const RECORD_HEADER: usize = 12;
+const INITIAL_BUCKETS: u64 = 4;
+const MAX_LOAD_FACTOR: f64 = 0.8;
+const CHECKPOINT_LOG_BYTES: u64 = 1 << 20;
+const MAGIC: &[u8; 8] = b"LINHASH1";
+const FRAME_MAGIC: u32 = 0x4c48_4c47;
+const FRAME_HEADER: usize = 16;
+
+/// A persistent hash index using linear hashing
+///
+/// Maps keys to values in a directory of files - a file of primary bucket pages, a file of overflow
+/// pages and a write-ahead log - reading pages on demand rather than holding the index in memory.
+///
+/// Should be expected to have average performance characteristics of `O(1)` page reads for
+/// lookups, and `O(1)` page writes plus a log flush for inserts and removals.
+///
+/// Keys and values are stored as their [ByteCodec](trait.ByteCodec.html) encodings, and a key and
+/// its value must fit in a single page together. Keys are hashed from their encoding by a
+/// [ByteHasher](trait.ByteHasher.html), which sees those bytes and nothing else - so the default,
+/// [Fnv1a](struct.Fnv1a.html), places every key in the same bucket on any target and with any
+/// Rust release, and an index can be moved between machines.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = LinearHashIndex::open("phone_book")?;
+/// phone_book.insert(&"John".to_string(), &4283058824u64)?;
+/// drop(phone_book);
+///
+/// let phone_book = LinearHashIndex::<String, u64>::open("phone_book")?;
+/// assert_eq!(phone_book.get(&"John".to_string())?, Some(4283058824));
+/// ```
+#[derive(Debug)]
+pub struct LinearHashIndex<K, V, S = Fnv1a> {
+    primary: File,
+    overflow: File,
+    log: File,
+    log_len: u64,
+    header: Header,
+    hasher: S,
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K: ByteCodec, V: ByteCodec> LinearHashIndex<K, V, Fnv1a> {
+    /// Opens the index stored in `dir`, creating an empty one if there is none
+    ///
+    /// See [open_with_hasher](struct.LinearHashIndex.html#method.open_with_hasher).
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        Self::open_with_hasher(dir, Fnv1a)
+    }
+}
+
+impl<K: ByteCodec, V: ByteCodec, S: ByteHasher> LinearHashIndex<K, V, S> {
+    /// Opens the index stored in `dir`, hashing keys with `hasher`, and creating an empty index if
+    /// there is none
+    ///
+    /// Any changes logged but not yet checkpointed when the index was last used are replayed, so
+    /// that an index left behind by a crash reopens with every completed change in place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files cannot be created or read, or hold something other than an
+    /// index.
+    pub fn open_with_hasher<P: AsRef<Path>>(dir: P, hasher: S) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let open = |name: &str| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(dir.join(name))
+        };
+
+        let mut index = LinearHashIndex {
+            primary: open("primary")?,
+            overflow: open("overflow")?,
+            log: open("log")?,
+            log_len: 0,
+            header: Header::new(),
+            hasher,
+            marker: PhantomData,
+        };
+
+        index.recover()?;
+        if index.primary.metadata()?.len() == 0 {
+            let mut tx = Transaction::new(index.header);
+            for bucket in 0..INITIAL_BUCKETS {
+                tx.pages.insert(PageRef::bucket(bucket), Page::empty());
+            }
+            index.commit(tx)?;
+        } else {
+            index.header = Header::decode(&index.read_raw(PageRef::HEADER)?)?;
+        }
+        Ok(index)
+    }
+
+    /// Returns the number of entries
+    pub fn len(&self) -> usize {
+        self.header.len as usize
+    }
+
+    /// Returns `true` if the index holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// Returns the number of buckets, each with its own primary page
+    pub fn num_buckets(&self) -> u64 {
+        self.header.num_buckets
+    }
+
+    /// Returns the number of overflow pages in use
+    pub fn overflow_pages(&self) -> u64 {
+        self.header.overflow_pages - self.header.free_pages
+    }
+
+    /// Returns the bytes of records stored over the bytes available in the primary pages
+    pub fn load_factor(&self) -> f64 {
+        let capacity = self.header.num_buckets as usize * (PAGE_SIZE - PAGE_HEADER);
+        super::load_factor(self.header.used_bytes as usize, capacity)
+    }
+
+    /// Returns a reference to the index's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the value stored for `key`, if present
+    pub fn get(&self, key: &K) -> io::Result<Option<V>> {
+        let (hash, key) = self.hash_key(key);
+        for (_, page) in self.chain(self.bucket_for(hash))? {
+            if let Some(position) = page.find(hash, &key) {
+                return decode(&page.records[position].value).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns `true` if the index holds an entry for `key`
+    pub fn contains_key(&self, key: &K) -> io::Result<bool> {
+        let (hash, key) = self.hash_key(key);
+        let chain = self.chain(self.bucket_for(hash))?;
+        Ok(chain
+            .iter()
+            .any(|(_, page)| page.find(hash, &key).is_some()))
+    }
+
+    /// Inserts a key-value pair - returns the previous value if the key was already present
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` if the key and value are too large to share a page,
+    /// or any error met while reading or writing the files.
+    pub fn insert(&mut self, key: &K, value: &V) -> io::Result<Option<V>> {
+        let (hash, key) = self.hash_key(key);
+        let mut encoded = Vec::new();
+        value.encode(&mut encoded);
+        let record = Record {
+            hash,
+            key,
+            value: encoded,
+        };
+        if record.size() > PAGE_SIZE - PAGE_HEADER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "key and value do not fit in a page",
+            ));
+        }
+
+        let mut tx = Transaction::new(self.header);
+        let mut chain = self.load_chain(&mut tx, self.bucket_for(hash))?;
+        let previous = self.take_record(&mut tx, &mut chain, hash, &record.key)?;
+
+        tx.header.len += 1;
+        tx.header.used_bytes += record.size() as u64;
+        let room = chain
+            .iter()
+            .find(|page| tx.pages[page].used() + record.size() <= PAGE_SIZE);
+        match room {
+            Some(page) => tx.pages.get_mut(page).unwrap().records.push(record),
+            None => {
+                let last = *chain.last().unwrap();
+                let fresh = self.allocate(&mut tx)?;
+                tx.pages.get_mut(&last).unwrap().next = Some(fresh.index);
+                tx.pages.get_mut(&fresh).unwrap().records.push(record);
+            }
+        }
+        self.commit(tx)?;
+
+        if self.load_factor() > MAX_LOAD_FACTOR {
+            self.split()?;
+        }
+        previous.map(|record| decode(&record.value)).transpose()
+    }
+
+    /// Removes the entry for `key` - returns the removed value
+    ///
+    /// An overflow page left empty is unlinked from its chain and kept for reuse.
+    pub fn remove(&mut self, key: &K) -> io::Result<Option<V>> {
+        let (hash, key) = self.hash_key(key);
+        let mut tx = Transaction::new(self.header);
+        let mut chain = self.load_chain(&mut tx, self.bucket_for(hash))?;
+        match self.take_record(&mut tx, &mut chain, hash, &key)? {
+            Some(record) => {
+                self.commit(tx)?;
+                decode(&record.value).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Flushes the page files to disk and empties the log
+    ///
+    /// Happens automatically whenever the log passes `1MiB`, but calling it before closing the index
+    /// saves the next open from replaying the log.
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        self.primary.sync_data()?;
+        self.overflow.sync_data()?;
+        self.log.set_len(0)?;
+        self.log.sync_data()?;
+        self.log_len = 0;
+        Ok(())
+    }
+
+    fn hash_key(&self, key: &K) -> (u64, Vec<u8>) {
+        let mut encoded = Vec::new();
+        key.encode(&mut encoded);
+        (self.hasher.hash_bytes(&encoded), encoded)
+    }
+
+    /// Chooses a bucket from the lowest bits of the hash - one bit more for buckets already split
+    fn bucket_for(&self, hash: u64) -> u64 {
+        let round = INITIAL_BUCKETS << self.header.level;
+        match hash % round {
+            bucket if bucket < self.header.split => hash % (round * 2),
+            bucket => bucket,
+        }
+    }
+
+    /// Reads a bucket's pages from disk, in chain order
+    fn chain(&self, bucket: u64) -> io::Result<Vec<(PageRef, Page)>> {
+        let mut chain = Vec::new();
+        let mut next = Some(PageRef::bucket(bucket));
+        while let Some(page_ref) = next {
+            let page = Page::decode(&self.read_raw(page_ref)?)?;
+            next = page.next.map(PageRef::overflow);
+            chain.push((page_ref, page));
+        }
+        Ok(chain)
+    }
+
+    /// Loads a bucket's pages into the transaction, returning their references in chain order
+    fn load_chain(&self, tx: &mut Transaction, bucket: u64) -> io::Result<Vec<PageRef>> {
+        let mut chain = Vec::new();
+        for (page_ref, page) in self.chain(bucket)? {
+            tx.pages.insert(page_ref, page);
+            chain.push(page_ref);
+        }
+        Ok(chain)
+    }
+
+    /// Removes a key's record from a loaded chain, releasing an overflow page it leaves empty - and
+    /// dropping that page from `chain`, so that nothing is written to it once it's free
+    fn take_record(
+        &self,
+        tx: &mut Transaction,
+        chain: &mut Vec<PageRef>,
+        hash: u64,
+        key: &[u8],
+    ) -> io::Result<Option<Record>> {
+        for (i, page_ref) in chain.iter().enumerate() {
+            let page = tx.pages.get_mut(page_ref).unwrap();
+            let position = match page.find(hash, key) {
+                Some(position) => position,
+                None => continue,
+            };
+
+            let record = page.records.swap_remove(position);
+            tx.header.len -= 1;
+            tx.header.used_bytes -= record.size() as u64;
+
+            if i > 0 && page.records.is_empty() {
+                let next = page.next;
+                tx.pages.get_mut(&chain[i - 1]).unwrap().next = next;
+                self.release(tx, *page_ref);
+                chain.remove(i);
+            }
+            return Ok(Some(record));
+        }
+        Ok(None)
+    }
+
+    /// Takes an overflow page from the free list, or appends a new one
+    fn allocate(&self, tx: &mut Transaction) -> io::Result<PageRef> {
+        let page_ref = match tx.header.free_head {
+            Some(free) => {
+                let page_ref = PageRef::overflow(free);
+                let next = match tx.pages.get(&page_ref) {
+                    Some(page) => page.next,
+                    None => Page::decode(&self.read_raw(page_ref)?)?.next,
+                };
+                tx.header.free_head = next;
+                tx.header.free_pages -= 1;
+                page_ref
+            }
+            None => {
+                tx.header.overflow_pages += 1;
+                PageRef::overflow(tx.header.overflow_pages - 1)
+            }
+        };
+        tx.pages.insert(page_ref, Page::empty());
+        Ok(page_ref)
+    }
+
+    /// Pushes an overflow page onto the free list
+    fn release(&self, tx: &mut Transaction, page_ref: PageRef) {
+        let mut page = Page::empty();
+        page.next = tx.header.free_head;
+        tx.pages.insert(page_ref, page);
+        tx.header.free_head = Some(page_ref.index);
+        tx.header.free_pages += 1;
+    }
+
+    /// Splits the bucket under the split pointer, rehashing its records between it and a new
+    /// bucket at the end of the table
+    fn split(&mut self) -> io::Result<()> {
+        let mut tx = Transaction::new(self.header);
+        let round = INITIAL_BUCKETS << tx.header.level;
+        let (old, new) = (tx.header.split, tx.header.num_buckets);
+
+        let chain = self.load_chain(&mut tx, old)?;
+        let mut records = Vec::new();
+        for page_ref in &chain {
+            records.append(&mut tx.pages.get_mut(page_ref).unwrap().records);
+        }
+        for page_ref in &chain[1..] {
+            self.release(&mut tx, *page_ref);
+        }
+
+        let (stay, moving): (Vec<_>, Vec<_>) = records
+            .into_iter()
+            .partition(|record| record.hash % (round * 2) == old);
+        tx.pages.insert(PageRef::bucket(old), Page::empty());
+        tx.pages.insert(PageRef::bucket(new), Page::empty());
+        self.fill(&mut tx, PageRef::bucket(old), stay)?;
+        self.fill(&mut tx, PageRef::bucket(new), moving)?;
+
+        tx.header.num_buckets += 1;
+        tx.header.split += 1;
+        if tx.header.split == round {
+            tx.header.level += 1;
+            tx.header.split = 0;
+        }
+        self.commit(tx)
+    }
+
+    /// Packs records into an empty primary page, chaining on overflow pages as each fills
+    fn fill(&self, tx: &mut Transaction, primary: PageRef, records: Vec<Record>) -> io::Result<()> {
+        let mut current = primary;
+        for record in records {
+            if tx.pages[&current].used() + record.size() > PAGE_SIZE {
+                let fresh = self.allocate(tx)?;
+                tx.pages.get_mut(&current).unwrap().next = Some(fresh.index);
+                current = fresh;
+            }
+            tx.pages.get_mut(&current).unwrap().records.push(record);
+        }
+        Ok(())
+    }
+
+    /// Logs a transaction's pages and flushes the log, then writes the pages in place
+    fn commit(&mut self, tx: Transaction) -> io::Result<()> {
+        let mut images = vec![(PageRef::HEADER, tx.header.encode())];
+        images.extend(
+            tx.pages
+                .iter()
+                .map(|(page_ref, page)| (*page_ref, page.encode())),
+        );
+
+        let mut payload = Vec::with_capacity(images.len() * (9 + PAGE_SIZE));
+        for (page_ref, image) in &images {
+            payload.push(page_ref.file as u8);
+            page_ref.index.encode(&mut payload);
+            payload.extend_from_slice(image);
+        }
+        let mut frame = Vec::with_capacity(FRAME_HEADER + payload.len());
+        FRAME_MAGIC.encode(&mut frame);
+        (payload.len() as u32).encode(&mut frame);
+        Fnv1a.hash_bytes(&payload).encode(&mut frame);
+        frame.extend_from_slice(&payload);
+
+        self.log.seek(SeekFrom::Start(self.log_len))?;
+        self.log.write_all(&frame)?;
+        self.log.sync_data()?;
+        self.log_len += frame.len() as u64;
+
+        // From here on, a crash is repaired by replaying the log
+        for (page_ref, image) in &images {
+            self.write_raw(*page_ref, image)?;
+        }
+        self.header = tx.header;
+
+        if self.log_len > CHECKPOINT_LOG_BYTES {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites every page image from the complete frames of the log, then checkpoints
+    fn recover(&mut self) -> io::Result<()> {
+        let mut log = Vec::new();
+        self.log.seek(SeekFrom::Start(0))?;
+        self.log.read_to_end(&mut log)?;
+
+        let mut rest = &log[..];
+        while let Some(payload) = next_frame(&mut rest) {
+            for image in payload.chunks(9 + PAGE_SIZE) {
+                let file = match image[0] {
+                    0 => PageFile::Primary,
+                    _ => PageFile::Overflow,
+                };
+                let index = u64::decode(&mut &image[1..9]).unwrap_or_default();
+                self.write_raw(PageRef { file, index }, &image[9..])?;
+            }
+        }
+        self.checkpoint()
+    }
+
+    fn file(&self, file: PageFile) -> &File {
+        match file {
+            PageFile::Primary => &self.primary,
+            PageFile::Overflow => &self.overflow,
+        }
+    }
+
+    // Pages are read and written at their offsets, never through the file's cursor - lookups only
+    // borrow the index, so two threads may be reading pages of the same file at once
+    fn read_raw(&self, page_ref: PageRef) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; PAGE_SIZE];
+        read_at(self.file(page_ref.file), &mut bytes, page_ref.offset())?;
+        Ok(bytes)
+    }
+
+    fn write_raw(&self, page_ref: PageRef, bytes: &[u8]) -> io::Result<()> {
+        write_at(self.file(page_ref.file), bytes, page_ref.offset())
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, bytes: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(bytes, offset)
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, bytes: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(bytes, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, mut bytes: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !bytes.is_empty() {
+        match file.seek_read(bytes, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                bytes = &mut bytes[read..];
+                offset += read as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, mut bytes: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !bytes.is_empty() {
+        match file.seek_write(bytes, offset)? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            written => {
+                bytes = &bytes[written..];
+                offset += written as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits the next complete, uncorrupted frame off the front of the log, returning its payload
+fn next_frame<'a>(log: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let mut header = log.get(..FRAME_HEADER)?;
+    let magic = u32::decode(&mut header)?;
+    let len = u32::decode(&mut header)? as usize;
+    let checksum = u64::decode(&mut header)?;
+
+    let payload = log.get(FRAME_HEADER..FRAME_HEADER + len)?;
+    let valid = magic == FRAME_MAGIC
+        && len.is_multiple_of(9 + PAGE_SIZE)
+        && Fnv1a.hash_bytes(payload) == checksum;
+    if !valid {
+        return None;
+    }
+    *log = &log[FRAME_HEADER + len..];
+    Some(payload)
+}
+
+fn decode<T: ByteCodec>(bytes: &[u8]) -> io::Result<T> {
+    T::decode(&mut &bytes[..]).ok_or_else(|| invalid_data("stored value cannot be decoded"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PageFile {
+    Primary = 0,
+    Overflow = 1,
+}
+
+/// The location of a page - the header is page `0` of the primary file, followed by one page per
+/// bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct PageRef {
+    file: PageFile,
+    index: u64,
+}
+
+impl PageRef {
+    const HEADER: PageRef = PageRef {
+        file: PageFile::Primary,
+        index: 0,
+    };
+
+    fn bucket(bucket: u64) -> Self {
+        PageRef {
+            file: PageFile::Primary,
+            index: bucket + 1,
+        }
+    }
+
+    fn overflow(index: u64) -> Self {
+        PageRef {
+            file: PageFile::Overflow,
+            index,
+        }
+    }
+
+    fn offset(&self) -> u64 {
+        self.index * PAGE_SIZE as u64
+    }
+}
+
+/// The pages touched by a change, and the header as it will be once the change is committed
+struct Transaction {
+    header: Header,
+    pages: BTreeMap<PageRef, Page>,
+}
+
+impl Transaction {
+    fn new(header: Header) -> Self {
+        Transaction {
+            header,
+            pages: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    level: u32,
+    split: u64,
+    num_buckets: u64,
+    len: u64,
+    used_bytes: u64,
+    overflow_pages: u64,
+    free_pages: u64,
+    free_head: Option<u64>,
+}
+
+impl Header {
+    fn new() -> Self {
+        Header {
+            level: 0,
+            split: 0,
+            num_buckets: INITIAL_BUCKETS,
+            len: 0,
+            used_bytes: 0,
+            overflow_pages: 0,
+            free_pages: 0,
+            free_head: None,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        (PAGE_SIZE as u32).encode(&mut bytes);
+        self.level.encode(&mut bytes);
+        for field in &[
+            self.split,
+            self.num_buckets,
+            self.len,
+            self.used_bytes,
+            self.overflow_pages,
+            self.free_pages,
+            encode_link(self.free_head),
+        ] {
+            field.encode(&mut bytes);
+        }
+        bytes.resize(PAGE_SIZE, 0);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        if &bytes[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not a linear hash index"));
+        }
+
+        let mut rest = &bytes[MAGIC.len()..];
+        let header = (|| {
+            if u32::decode(&mut rest)? as usize != PAGE_SIZE {
+                return None;
+            }
+            let level = u32::decode(&mut rest)?;
+            let mut next = || u64::decode(&mut rest);
+            Some(Header {
+                level,
+                split: next()?,
+                num_buckets: next()?,
+                len: next()?,
+                used_bytes: next()?,
+                overflow_pages: next()?,
+                free_pages: next()?,
+                free_head: decode_link(next()?),
+            })
+        })();
+        header.ok_or_else(|| invalid_data("index header is corrupt"))
+    }
+}
+
+/// A page - a link to the next page of its chain, then its records
+#[derive(Debug, Clone)]
+struct Page {
+    next: Option<u64>,
+    records: Vec<Record>,
+}
+
+#[derive(Debug, Clone)]
+struct Record {
+    hash: u64,
+    key: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl Record {
+    fn size(&self) -> usize {
+        RECORD_HEADER + self.key.len() + self.value.len()
+    }
+}
+
+impl Page {
+    fn empty() -> Self {
+        Page {
+            next: None,
+            records: Vec::new(),
+        }
+    }
+
+    fn used(&self) -> usize {
+        PAGE_HEADER + self.records.iter().map(Record::size).sum::<usize>()
+    }
+
+    fn find(&self, hash: u64, key: &[u8]) -> Option<usize> {
+        self.records
+            .iter()
+            .position(|record| record.hash == hash && record.key == key)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PAGE_SIZE);
+        encode_link(self.next).encode(&mut bytes);
+        (self.records.len() as u16).encode(&mut bytes);
+        for record in &self.records {
+            record.hash.encode(&mut bytes);
+            (record.key.len() as u16).encode(&mut bytes);
+            (record.value.len() as u16).encode(&mut bytes);
+            bytes.extend_from_slice(&record.key);
+            bytes.extend_from_slice(&record.value);
+        }
+        bytes.resize(PAGE_SIZE, 0);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut rest = bytes;
+        let page = (|| {
+            let next = decode_link(u64::decode(&mut rest)?);
+            let count = u16::decode(&mut rest)?;
+            let mut records = Vec::with_capacity(usize::from(count));
+            for _ in 0..count {
+                let hash = u64::decode(&mut rest)?;
+                let key_len = usize::from(u16::decode(&mut rest)?);
+                let value_len = usize::from(u16::decode(&mut rest)?);
+                let key = rest.get(..key_len)?.to_vec();
+                let value = rest.get(key_len..key_len + value_len)?.to_vec();
+                rest = &rest[key_len + value_len..];
+                records.push(Record { hash, key, value });
+            }
+            Some(Page { next, records })
+        })();
+        page.ok_or_else(|| invalid_data("index page is corrupt"))
+    }
+}
+
+/// Links are stored one higher than the page they point to, so that a zeroed page links nowhere
+fn encode_link(link: Option<u64>) -> u64 {
+    link.map_or(0, |index| index + 1)
+}
+
+fn decode_link(stored: u64) -> Option<u64> {
+    stored.checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::hashing::ConstantHasher;
+    use super::*;
+    use std::path::PathBuf;
+
+    // A fresh directory per test, removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "linear-hashing-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn value_for(key: u64) -> String {
+        format!("value-{}", key)
+    }
+
+    #[test]
+    fn insert_get_and_remove() {
+        let dir = TempDir::new("basics");
+        let mut index = LinearHashIndex::open(&dir.0).unwrap();
+        assert_eq!(index.insert(&"John".to_string(), &1u64).unwrap(), None);
+        assert_eq!(index.insert(&"John".to_string(), &2u64).unwrap(), Some(1));
+        assert_eq!(index.get(&"John".to_string()).unwrap(), Some(2));
+        assert_eq!(index.remove(&"John".to_string()).unwrap(), Some(2));
+        assert_eq!(index.get(&"John".to_string()).unwrap(), None);
+        assert!(index.is_empty())
+    }
+
+    #[test]
+    fn grows_by_splitting_one_bucket_at_a_time() {
+        let dir = TempDir::new("splits");
+        let mut index = LinearHashIndex::open(&dir.0).unwrap();
+        for key in 0..5000u64 {
+            index.insert(&key, &value_for(key)).unwrap();
+        }
+
+        assert_eq!(index.len(), 5000);
+        assert!(index.num_buckets() > INITIAL_BUCKETS);
+        assert!(index.load_factor() <= MAX_LOAD_FACTOR);
+        assert!((0..5000u64).all(|key| index.get(&key).unwrap() == Some(value_for(key))))
+    }
+
+    #[test]
+    fn emptied_overflow_pages_are_reused() {
+        let dir = TempDir::new("overflow");
+        let mut index = LinearHashIndex::open(&dir.0).unwrap();
+        let big = "x".repeat(1000);
+        for key in 0..40u64 {
+            index.insert(&key, &big).unwrap();
+        }
+        let in_use = index.overflow_pages();
+        assert!(in_use > 0);
+
+        for key in 0..40u64 {
+            index.remove(&key).unwrap();
+        }
+        assert_eq!(index.overflow_pages(), 0);
+        for key in 0..40u64 {
+            index.insert(&key, &big).unwrap();
+        }
+        assert_eq!(index.overflow_pages(), in_use)
+    }
+
+    #[test]
+    fn keys_hash_from_their_encoding_alone() {
+        let dir = TempDir::new("portable");
+        let index = LinearHashIndex::<String, u64>::open(&dir.0).unwrap();
+        // FNV-1a over the `u32` length and bytes of "John", the same on every target
+        let (hash, encoded) = index.hash_key(&"John".to_string());
+        assert_eq!(encoded, b"\x04\0\0\0John");
+        assert_eq!(hash, 0x1a1a_2778_fd80_1998)
+    }
+
+    #[test]
+    fn overwriting_the_only_record_on_an_overflow_page_keeps_it() {
+        let dir = TempDir::new("overwrite");
+        let mut index = LinearHashIndex::open_with_hasher(&dir.0, ConstantHasher).unwrap();
+        let big = "x".repeat(1000);
+        for key in 0..4u64 {
+            index.insert(&key, &big).unwrap();
+        }
+        assert_eq!(index.overflow_pages(), 1);
+
+        assert_eq!(index.insert(&3, &big).unwrap(), Some(big.clone()));
+        assert_eq!(index.len(), 4);
+        assert!((0..4u64).all(|key| index.get(&key).unwrap() == Some(big.clone())))
+    }
+
+    #[test]
+    fn threads_can_read_at_once() {
+        let dir = TempDir::new("readers");
+        let mut index = LinearHashIndex::open(&dir.0).unwrap();
+        for key in 0..2000u64 {
+            index.insert(&key, &value_for(key)).unwrap();
+        }
+
+        let index = &index;
+        std::thread::scope(|scope| {
+            for thread in 0..4u64 {
+                scope.spawn(move || {
+                    for key in (thread..2000).step_by(4) {
+                        assert_eq!(index.get(&key).unwrap(), Some(value_for(key)));
+                    }
+                });
+            }
+        })
+    }
+
+    #[test]
+    fn reopening_finds_everything() {
+        let dir = TempDir::new("reopen");
+        {
+            let mut index = LinearHashIndex::open(&dir.0).unwrap();
+            for key in 0..3000u64 {
+                index.insert(&key, &value_for(key)).unwrap();
+            }
+            index.remove(&7).unwrap();
+            index.checkpoint().unwrap();
+        }
+
+        let index = LinearHashIndex::<u64, String>::open(&dir.0).unwrap();
+        assert_eq!(index.len(), 2999);
+        assert_eq!(index.get(&7).unwrap(), None);
+        assert!((8..3000u64).all(|key| index.get(&key).unwrap() == Some(value_for(key))))
+    }
+
+    #[test]
+    fn recovery_repairs_torn_page_writes() {
+        let dir = TempDir::new("torn");
+        {
+            let mut index = LinearHashIndex::open(&dir.0).unwrap();
+            for key in 0..50u64 {
+                index.insert(&key, &value_for(key)).unwrap();
+            }
+            // Dropped without a checkpoint, as if the process had crashed
+        }
+
+        // Scribble over every bucket page, as a crash part way through writing them might
+        let mut primary = OpenOptions::new()
+            .write(true)
+            .open(dir.0.join("primary"))
+            .unwrap();
+        let pages = primary.metadata().unwrap().len() / PAGE_SIZE as u64;
+        for page in 1..pages {
+            primary
+                .seek(SeekFrom::Start(page * PAGE_SIZE as u64 + 100))
+                .unwrap();
+            primary.write_all(&[0xff; 500]).unwrap();
+        }
+
+        let index = LinearHashIndex::<u64, String>::open(&dir.0).unwrap();
+        assert_eq!(index.len(), 50);
+        assert!((0..50u64).all(|key| index.get(&key).unwrap() == Some(value_for(key))))
+    }
+
+    #[test]
+    fn recovery_discards_a_half_written_log_entry() {
+        let dir = TempDir::new("partial");
+        {
+            let mut index = LinearHashIndex::open(&dir.0).unwrap();
+            index.insert(&1u64, &value_for(1)).unwrap();
+        }
+
+        // A change whose log entry never finished being written
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(dir.0.join("log"))
+            .unwrap();
+        let mut frame = Vec::new();
+        FRAME_MAGIC.encode(&mut frame);
+        ((9 + PAGE_SIZE) as u32).encode(&mut frame);
+        frame.extend_from_slice(&[0; 100]);
+        log.write_all(&frame).unwrap();
+
+        let mut index = LinearHashIndex::<u64, String>::open(&dir.0).unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.get(&1).unwrap(), Some(value_for(1)));
+        index.insert(&2, &value_for(2)).unwrap();
+        assert_eq!(index.get(&2).unwrap(), Some(value_for(2)))
+    }
+
+    #[test]
+    fn oversized_records_are_rejected() {
+        let dir = TempDir::new("oversized");
+        let mut index = LinearHashIndex::open(&dir.0).unwrap();
+        let error = index.insert(&1u64, &"x".repeat(PAGE_SIZE)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput)
+    }
+
+    #[test]
+    fn foreign_files_are_rejected() {
+        let dir = TempDir::new("foreign");
+        fs::create_dir_all(&dir.0).unwrap();
+        fs::write(dir.0.join("primary"), vec![7; PAGE_SIZE]).unwrap();
+        let error = LinearHashIndex::<u64, u64>::open(&dir.0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData)
+    }
+}
@@ -0,0 +1,464 @@
+//! LRU Cache
+//!
+//! `lru` provides [LruCache](struct.LruCache.html) - the 'caches' application listed at the top of
+//! this module, built from the module's own [HashTable](struct.HashTable.html).
+//!
+//! A cache holds a bounded number of entries, so once it is full something must make way for each
+//! new arrival. A least-recently-used (LRU) cache evicts whichever entry has gone longest without
+//! being touched. Two structures work together to make every operation `O(1)`:
+//!
+//! ```text
+//!   HashTable             nodes
+//!
+//!   "foo" ==> 0           0: [foo]  prev: 2  next: 1
+//!   "baz" ==> 1           1: [baz]  prev: 0  next: -   <== tail (least recently used)
+//!   "bar" ==> 2           2: [bar]  prev: -  next: 0   <== head (most recently used)
+//! ```
+//!
+//! The table finds an entry's node without searching, and the list keeps the nodes in order of
+//! use - touching an entry unlinks its node and relinks it at the head, while eviction takes from
+//! the tail. The list is 'intrusive': its nodes live in a single vector and link to one another by
+//! index, and the table maps each key straight to its node's index.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+
+use super::hashing::{SipHash, SlotHasher};
+use super::open_addressing::{HashTable, Probing};
+
+const INDEX_MAX_LOAD_FACTOR: f64 = 0.75;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// A least-recently-used cache
+///
+/// Holds up to `capacity` entries, evicting the least recently used entry to make room for a new
+/// one - and handing each evicted entry to an optional callback.
+///
+/// Should be expected to have performance characteristics of `O(1)` for every operation.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut dns_cache = LruCache::new(2);
+/// dns_cache.put("google.com", [33, 125, 45, 42]);
+/// dns_cache.put("facebook.com", [85, 28, 28, 4]);
+///
+/// dns_cache.get("google.com");
+/// dns_cache.put("microsoft.com", [12, 123, 12, 123]);
+///
+/// // "facebook.com" was the least recently used, so made way for "microsoft.com"
+/// assert!(dns_cache.peek("facebook.com").is_none());
+/// assert_eq!(dns_cache.hits(), 1);
+/// ```
+pub struct LruCache<K, V, S = SipHash> {
+    index: HashTable<K, usize, S>,
+    nodes: Vec<Node<K, V>>,
+    head: Option<usize>,
+    tail: Option<usize>,
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+    on_evict: Option<Box<dyn FnMut(K, V)>>,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V, SipHash> {
+    /// Creates an empty cache holding at most `capacity` entries
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, SipHash)
+    }
+}
+
+impl<K: Eq + Clone, V, S: SlotHasher<K>> LruCache<K, V, S> {
+    /// Creates an empty cache holding at most `capacity` entries, hashing its keys with `hasher`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        assert!(
+            capacity > 0,
+            "an LRU cache must have a capacity of at least 1"
+        );
+
+        LruCache {
+            index: HashTable::with_options_and_hasher(
+                capacity,
+                Probing::Linear,
+                INDEX_MAX_LOAD_FACTOR,
+                hasher,
+            ),
+            nodes: Vec::with_capacity(capacity),
+            head: None,
+            tail: None,
+            capacity,
+            hits: 0,
+            misses: 0,
+            on_evict: None,
+        }
+    }
+
+    /// Sets a callback to receive each entry evicted to make room for a new one
+    ///
+    /// Entries removed explicitly (through [remove](struct.LruCache.html#method.remove) or
+    /// [pop_lru](struct.LruCache.html#method.pop_lru)) are returned to the caller instead.
+    pub fn set_on_evict<F>(&mut self, on_evict: F)
+    where
+        F: FnMut(K, V) + 'static,
+    {
+        self.on_evict = Some(Box::new(on_evict));
+    }
+
+    /// Returns the number of entries in the cache
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns `true` if the cache holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Returns the most entries the cache will hold
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of lookups through [get](struct.LruCache.html#method.get) that found an entry
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of lookups through [get](struct.LruCache.html#method.get) that found nothing
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the fraction of lookups that found an entry, or `0` if there have been none
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+
+    /// Inserts or updates an entry, marking it most recently used - returns the previous value
+    /// if the key was already present
+    ///
+    /// If the cache is full, the least recently used entry is evicted first.
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(&node) = self.index.get(&key) {
+            self.touch(node);
+            return Some(mem::replace(&mut self.nodes[node].value, value));
+        }
+
+        if self.len() == self.capacity {
+            if let Some((k, v)) = self.pop_lru() {
+                if let Some(on_evict) = self.on_evict.as_mut() {
+                    on_evict(k, v);
+                }
+            }
+        }
+
+        self.nodes.push(Node {
+            key: key.clone(),
+            value,
+            prev: None,
+            next: None,
+        });
+        let node = self.nodes.len() - 1;
+
+        self.index.insert(key, node);
+        self.push_front(node);
+        None
+    }
+
+    /// Returns the value stored for `key`, marking it most recently used
+    ///
+    /// Counts towards the cache's hits or misses.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.get_mut(key).map(|v| &*v)
+    }
+
+    /// Returns a mutable reference to the value stored for `key`, marking it most recently used
+    ///
+    /// Counts towards the cache's hits or misses.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        match self.index.get(key) {
+            Some(&node) => {
+                self.hits += 1;
+                self.touch(node);
+                Some(&mut self.nodes[node].value)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Returns the value stored for `key`, without marking it used or counting the lookup
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.index.get(key).map(|&node| &self.nodes[node].value)
+    }
+
+    /// Returns `true` if the cache holds an entry for `key`, without marking it used
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.index.contains_key(key)
+    }
+
+    /// Removes the entry for `key` - returns the removed value
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let node = self.index.get(key).copied()?;
+        Some(self.take(node).1)
+    }
+
+    /// Removes the least recently used entry - returns it, if the cache was not empty
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let tail = self.tail?;
+        Some(self.take(tail))
+    }
+
+    /// Returns the least recently used entry, without marking it used
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.tail
+            .map(|tail| (&self.nodes[tail].key, &self.nodes[tail].value))
+    }
+
+    /// Returns an iterator over the entries, from most to least recently used
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut cursor = self.head;
+        std::iter::from_fn(move || {
+            let node = &self.nodes[cursor?];
+            cursor = node.next;
+            Some((&node.key, &node.value))
+        })
+    }
+
+    /// Removes every entry, keeping the hit and miss counts
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.nodes.clear();
+        self.head = None;
+        self.tail = None;
+    }
+
+    // Unlinks `node` from the list and removes its key from the index - returns its entry.
+    //
+    // The arena is kept dense by moving the last node into the vacated slot, so that node's
+    // neighbours and index entry are first pointed at its new position.
+    fn take(&mut self, node: usize) -> (K, V) {
+        self.unlink(node);
+
+        let last = self.nodes.len() - 1;
+        if node != last {
+            let (prev, next) = (self.nodes[last].prev, self.nodes[last].next);
+            match prev {
+                Some(prev) => self.nodes[prev].next = Some(node),
+                None => self.head = Some(node),
+            }
+            match next {
+                Some(next) => self.nodes[next].prev = Some(node),
+                None => self.tail = Some(node),
+            }
+            if let Some(index) = self.index.get_mut(&self.nodes[last].key) {
+                *index = node;
+            }
+        }
+
+        let Node { key, value, .. } = self.nodes.swap_remove(node);
+        self.index.remove(&key);
+        (key, value)
+    }
+
+    // Moves `node` to the head of the list, marking it most recently used
+    fn touch(&mut self, node: usize) {
+        if self.head != Some(node) {
+            self.unlink(node);
+            self.push_front(node);
+        }
+    }
+
+    fn push_front(&mut self, node: usize) {
+        self.nodes[node].prev = None;
+        self.nodes[node].next = self.head;
+        match self.head {
+            Some(head) => self.nodes[head].prev = Some(node),
+            None => self.tail = Some(node),
+        }
+        self.head = Some(node);
+    }
+
+    fn unlink(&mut self, node: usize) {
+        let (prev, next) = (self.nodes[node].prev, self.nodes[node].next);
+        match prev {
+            Some(prev) => self.nodes[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.nodes[next].prev = prev,
+            None => self.tail = prev,
+        }
+        self.nodes[node].prev = None;
+        self.nodes[node].next = None;
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for LruCache<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LruCache")
+            .field("len", &self.nodes.len())
+            .field("capacity", &self.capacity)
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn get_returns_value_put() {
+        let mut cache = LruCache::new(2);
+        cache.put("foo", 1);
+        assert_eq!(cache.get("foo"), Some(&1));
+        assert_eq!(cache.get("bar"), None)
+    }
+
+    #[test]
+    fn put_existing_key_replaces_value() {
+        let mut cache = LruCache::new(2);
+        assert_eq!(cache.put("foo", 1), None);
+        assert_eq!(cache.put("foo", 2), Some(1));
+        assert_eq!(cache.len(), 1)
+    }
+
+    #[test]
+    fn full_cache_evicts_least_recently_used() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        cache.get("a");
+        cache.put("d", 4);
+        assert_eq!(cache.peek("b"), None);
+        assert_eq!(cache.len(), 3);
+        let order: Vec<_> = cache.iter().map(|(k, _)| *k).collect();
+        assert_eq!(order, vec!["d", "a", "c"])
+    }
+
+    #[test]
+    fn peek_does_not_promote_or_count() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        assert_eq!(cache.peek("a"), Some(&1));
+        cache.put("c", 3);
+        assert!(!cache.contains_key("a"));
+        assert_eq!(cache.hits() + cache.misses(), 0)
+    }
+
+    #[test]
+    fn pop_lru_returns_entries_oldest_first() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        cache.get("a");
+        assert_eq!(cache.pop_lru(), Some(("b", 2)));
+        assert_eq!(cache.pop_lru(), Some(("c", 3)));
+        assert_eq!(cache.pop_lru(), Some(("a", 1)));
+        assert_eq!(cache.pop_lru(), None)
+    }
+
+    #[test]
+    fn hit_and_miss_counters_track_get() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", 1);
+        cache.get("a");
+        cache.get("a");
+        cache.get("b");
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 1);
+        assert!((cache.hit_rate() - 2.0 / 3.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn eviction_callback_receives_evicted_entries() {
+        let evicted = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&evicted);
+
+        let mut cache = LruCache::new(2);
+        cache.set_on_evict(move |k, v| sink.borrow_mut().push((k, v)));
+        for i in 0..5 {
+            cache.put(i, i * 10);
+        }
+        cache.pop_lru();
+
+        assert_eq!(evicted.take(), vec![(0, 0), (1, 10), (2, 20)])
+    }
+
+    #[test]
+    fn remove_from_middle_keeps_list_and_index_consistent() {
+        let mut cache = LruCache::new(100);
+        for i in 0..100 {
+            cache.put(i, i);
+        }
+        for i in (0..100).step_by(3) {
+            assert_eq!(cache.remove(&i), Some(i));
+        }
+        for i in 0..100 {
+            assert_eq!(cache.peek(&i), if i % 3 == 0 { None } else { Some(&i) });
+        }
+        let order: Vec<_> = cache.iter().map(|(k, _)| *k).collect();
+        let expected: Vec<_> = (0..100).rev().filter(|i| i % 3 != 0).collect();
+        assert_eq!(order, expected)
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_capacity_is_rejected() {
+        let _cache: LruCache<u64, u64> = LruCache::new(0);
+    }
+}
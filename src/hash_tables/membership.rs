@@ -0,0 +1,76 @@
+//! Approximate Membership
+//!
+//! `membership` provides [ApproxMembership](trait.ApproxMembership.html) - the interface shared by
+//! the filters which, unlike a plain [BloomFilter](struct.BloomFilter.html), can forget a key.
+//!
+//! That matters for the 'Uniqueness' flow from earlier in this module: once a person leaves the
+//! phone book, their name must be free to be inserted again. Being able to swap one filter for
+//! another behind a single interface also lets us measure their trade-offs side by side - how
+//! many bytes each needs, and how often each answers 'probably' for a key it never saw.
+
+use super::bloom::FilterError;
+use super::hashing::SlotHasher;
+
+/// A set which answers membership queries approximately, and supports removal
+///
+/// Like a Bloom filter, `contains` may return a false positive but never a false negative - so
+/// long as only keys that were inserted are ever removed. Removing a key that was never inserted
+/// may clear state belonging to a different key which happens to look the same.
+pub trait ApproxMembership {
+    /// The hash function keys are fed through
+    type Hasher;
+
+    /// Inserts a key, failing if the filter has no room for it
+    fn insert<K>(&mut self, key: &K) -> Result<(), FilterError>
+    where
+        K: ?Sized,
+        Self::Hasher: SlotHasher<K>;
+
+    /// Returns `false` if the key is definitely absent, and `true` if it is probably present
+    fn contains<K>(&self, key: &K) -> bool
+    where
+        K: ?Sized,
+        Self::Hasher: SlotHasher<K>;
+
+    /// Removes one insertion of a key - returns `false` if the key was definitely absent
+    fn remove<K>(&mut self, key: &K) -> bool
+    where
+        K: ?Sized,
+        Self::Hasher: SlotHasher<K>;
+
+    /// Returns the number of insertions not yet removed
+    fn len(&self) -> usize;
+
+    /// Returns `true` if nothing is currently inserted
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bytes the filter uses to record its keys
+    fn memory_usage(&self) -> usize;
+
+    /// Returns the fraction of `absent_keys` which the filter claims are present
+    ///
+    /// # Arguments
+    ///
+    /// * `absent_keys` - keys known never to have been inserted
+    fn measure_false_positive_rate<'a, K, I>(&self, absent_keys: I) -> f64
+    where
+        K: ?Sized + 'a,
+        I: IntoIterator<Item = &'a K>,
+        Self::Hasher: SlotHasher<K>,
+    {
+        let (mut checked, mut positives) = (0, 0);
+        for key in absent_keys {
+            checked += 1;
+            if self.contains(key) {
+                positives += 1;
+            }
+        }
+
+        match checked {
+            0 => 0.0,
+            _ => positives as f64 / checked as f64,
+        }
+    }
+}
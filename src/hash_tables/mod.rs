@@ -303,3 +303,201 @@
 //! A - 5.7
 //!
 //! B + C + D
+//!
+//! # Implementations
+//!
+//! While the standard library's `HashMap` is what we should reach for day to day, this module
+//! also provides its own tables so that the ideas above can be inspected and measured directly:
+//!
+//! * [HashTable](struct.HashTable.html) - an open-addressing table with linear or quadratic
+//!   probing, tombstones for removed entries, and resizing past a configurable load factor
+//! * [ChainedHashMap](struct.ChainedHashMap.html) - a separate-chaining table which reports
+//!   its chain lengths and load factor through [BucketStats](struct.BucketStats.html)
+//!
+//! Either can be switched to [Resizing::Incremental](enum.Resizing.html#variant.Incremental), so
+//! that a table holding millions of entries never stalls to rehash them all on a single insert.
+//!
+//! Alongside them sit a few more specialised designs:
+//!
+//! * [RobinHoodMap](struct.RobinHoodMap.html) - an open-addressing table using Robin Hood
+//!   hashing and backward-shift deletion, which keeps probe sequences short up to a load factor of `0.9`
+//! * [CuckooMap](struct.CuckooMap.html) - a two-table cuckoo hash map whose lookups examine at
+//!   most two slots (and a stash, for keys sharing a hash), making `O(1)` a worst-case bound
+//!   rather than an expected one
+//!
+//! And built on top of them, some of the applications listed above:
+//!
+//! * [LruCache](struct.LruCache.html) - a least-recently-used cache with hit and miss counters,
+//!   backed by a `HashTable` and an intrusive doubly-linked list
+//! * [TtlCache](struct.TtlCache.html) - a cache whose entries expire after a time-to-live, read
+//!   from an injectable [Clock](trait.Clock.html)
+//!
+//! When the 'Uniqueness' check only needs a 'have we seen this before?' answer, and storing every
+//! key is too costly, we can trade certainty for space:
+//!
+//! * [BloomFilter](struct.BloomFilter.html) - a bit array answering 'definitely not' or 'probably',
+//!   sized from an expected number of keys and a target false positive rate
+//!
+//! A Bloom filter cannot forget a key, though, so once people leave the phone book their names stay
+//! 'probably taken'. Two filters implement [ApproxMembership](trait.ApproxMembership.html), which
+//! adds removal, and can be swapped for one another to compare their size and accuracy:
+//!
+//! * [CountingBloomFilter](struct.CountingBloomFilter.html) - a Bloom filter with a counter in
+//!   place of each bit
+//! * [CuckooFilter](struct.CuckooFilter.html) - short fingerprints of each key, placed by cuckoo
+//!   hashing
+//!
+//! And when the question is not 'have we seen this key?' but 'how many distinct keys have we
+//! seen?', [HyperLogLog](struct.HyperLogLog.html) estimates the answer to within a percent or so
+//! from a few kilobytes.
+//!
+//! Likewise for 'how often have we seen each key?', when a counter per key would be too large:
+//!
+//! * [CountMinSketch](struct.CountMinSketch.html) - a grid of counters giving an upper bound on
+//!   any key's count, within an error chosen up front
+//! * [HeavyHitters](struct.HeavyHitters.html) - the `k` hottest keys, found by pairing that sketch
+//!   with a small table of candidates
+//!
+//! Hash tables are also the backbone of search engines: [InvertedIndex](struct.InvertedIndex.html)
+//! maps every word of a collection of documents to the documents containing it, answering 'all of
+//! these words', 'any of these words' and exact phrase searches ranked by TF-IDF.
+//!
+//! When every key is known up front, as in the battery sizes and book titles of the exercises,
+//! collisions can be ruled out entirely: [build_perfect_hash](fn.build_perfect_hash.html) searches
+//! for a minimal perfect hash function, and [FrozenMap](struct.FrozenMap.html) uses one to give a
+//! compact read-only map which can be saved as bytes and reloaded without searching again.
+//!
+//! None of the tables above can be shared between threads without an outside lock around the
+//! whole thing. [ShardedMap](struct.ShardedMap.html) splits its entries across many separately
+//! locked tables instead, so threads only wait on one another when their keys collide on a shard.
+//!
+//! And when the entries will not fit in memory at all - the database indexing use above -
+//! [LinearHashIndex](struct.LinearHashIndex.html) keeps its buckets in pages on disk, growing one
+//! bucket at a time by linear hashing, and logs each change ahead of writing it so that a crash
+//! loses nothing already committed.
+//!
+//! Finally, for when a single table is not enough - a cache or index sharded across processes -
+//! [HashRing](struct.HashRing.html), [RendezvousHash](struct.RendezvousHash.html) and
+//! [jump_consistent_hash](fn.jump_consistent_hash.html) assign keys to nodes so that few keys
+//! move when nodes come and go.
+//!
+//! Every table, filter and sketch here is generic over a [SlotHasher](trait.SlotHasher.html), and
+//! the four hash functions from the exercises above ship alongside the general purpose
+//! [Fnv1a](struct.Fnv1a.html) and [SipHash](struct.SipHash.html) - so the answers to 5.5 through
+//! 5.7 can be checked rather than taken on faith.
+//!
+//! To put a number on 'a good distribution', [analyze_distribution](fn.analyze_distribution.html)
+//! places any corpus of keys into a chosen number of slots and reports the histogram, collision
+//! count, fullest slot and a chi-squared uniformity score.
+
+mod bloom;
+mod chaining;
+mod codec;
+mod consistent;
+mod count_min;
+mod counting_bloom;
+mod cuckoo;
+mod cuckoo_filter;
+mod distribution;
+mod hashing;
+mod heavy_hitters;
+mod hyperloglog;
+mod inverted_index;
+mod linear_hashing;
+mod lru;
+mod membership;
+mod open_addressing;
+mod perfect;
+mod robin_hood;
+mod sharded;
+mod ttl;
+
+pub use bloom::{BloomFilter, FilterError};
+pub use chaining::{BucketStats, ChainedHashMap};
+pub use codec::ByteCodec;
+pub use consistent::{
+    jump_consistent_hash, measure_remapping, HashRing, Remapping, RendezvousHash,
+};
+pub use count_min::{CountMinSketch, CounterUpdate};
+pub use counting_bloom::CountingBloomFilter;
+pub use cuckoo::CuckooMap;
+pub use cuckoo_filter::CuckooFilter;
+pub use distribution::{analyze_distribution, DistributionReport};
+pub use hashing::{
+    ByteHasher, ConstantHasher, FirstCharHasher, Fnv1a, LengthHasher, PrimeSumHasher, SipHash,
+    SlotHasher, TaggedHasher,
+};
+pub use heavy_hitters::HeavyHitters;
+pub use hyperloglog::HyperLogLog;
+pub use inverted_index::{tokenize, InvertedIndex, Posting, SearchHit};
+pub use linear_hashing::LinearHashIndex;
+pub use lru::LruCache;
+pub use membership::ApproxMembership;
+pub use open_addressing::{HashTable, Probing};
+pub use perfect::{
+    build_perfect_hash, build_perfect_hash_with_hasher, FrozenMap, PerfectHash, PerfectHashError,
+};
+pub use robin_hood::RobinHoodMap;
+pub use sharded::{Entry, ShardedMap, ValueMut};
+pub use ttl::{Clock, ManualClock, SystemClock, TtlCache};
+
+/// How a table moves its entries into a larger slot array once it outgrows its load factor
+///
+/// As the 'Collisons' section above warns, resizing is expensive - every entry must be rehashed
+/// into the new array. Done all at once, that cost lands on whichever unlucky insert crossed the
+/// threshold. Done incrementally, the old and new arrays coexist for a while and the cost is
+/// spread across the operations that follow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resizing {
+    /// Moves every entry into the new array during the insert that crossed the threshold
+    #[default]
+    Immediate,
+    /// Keeps the old array alongside the new one, moving `step` of its slots across on each
+    /// insert, removal and mutable lookup - while lookups check both arrays
+    ///
+    /// A `step` too small to empty the old array before the next resize is due is raised to one
+    /// that will, so that no operation is left to finish the migration all at once. The step is
+    /// worked out from the inserts still to come alone, as lookups through a shared reference
+    /// can't move anything.
+    ///
+    /// Only [HashTable](struct.HashTable.html) and
+    /// [ChainedHashMap](struct.ChainedHashMap.html) resize incrementally.
+    /// [RobinHoodMap](struct.RobinHoodMap.html) and [CuckooMap](struct.CuckooMap.html) are out of
+    /// scope: their guarantees - entries ordered by distance from home, and at most two slots to
+    /// check - hold only within a single array, so they always resize immediately.
+    Incremental {
+        /// The number of old slots migrated per operation
+        step: usize,
+    },
+}
+
+// The fewest old slots an incremental resize must migrate per operation to empty `old_slots`
+// within `headroom` inserts - the most that can follow before the next resize is due
+pub(crate) fn minimum_step(old_slots: usize, headroom: f64) -> usize {
+    let headroom = headroom.floor().max(1.0) as usize;
+    old_slots.div_ceil(headroom)
+}
+
+/// A load factor calculation
+///
+/// Given a count of items and a count of slots - returns the load factor described
+/// in the 'Collisons' section above.
+///
+/// An empty table (no slots) is considered to have a load factor of zero.
+///
+/// # Arguments
+///
+/// * `items` - the number of items in the hash table
+/// * `slots` - the total number of slots
+///
+/// # Examples
+///
+/// ```rust
+/// load_factor(100, 50)
+/// ```
+pub fn load_factor(items: usize, slots: usize) -> f64 {
+    match slots {
+        0 => 0.0,
+        _ => items as f64 / slots as f64,
+    }
+}
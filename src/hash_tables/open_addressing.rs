@@ -0,0 +1,931 @@
+//! Open Addressing
+//!
+//! `open_addressing` provides [HashTable](struct.HashTable.html) - a hash table that stores
+//! every entry directly within its array of slots, rather than in a separate structure
+//! hanging off of each slot.
+//!
+//! When two keys hash to the same slot, the table probes forward through the array until
+//! it finds an empty slot to use. The order in which slots are visited is the probing
+//! strategy:
+//!
+//! ```text
+//! Linear:     h, h + 1, h + 2, h + 3, h + 4, ...
+//! Quadratic:  h, h + 1, h + 3, h + 6, h + 10, ...
+//! ```
+//!
+//! Removing an entry cannot simply empty its slot, as that would break the probe sequence
+//! of any key that was pushed past it. Instead, the slot is marked with a 'tombstone' -
+//! lookups skip over it, while inserts are free to reuse it.
+//!
+//! Once the load factor (counting tombstones, as they too lengthen probe sequences) crosses
+//! the configured maximum, the table is rebuilt into a larger array. With
+//! [Resizing::Incremental](enum.Resizing.html#variant.Incremental) the old array is kept
+//! alongside the new one and drained a few slots at a time, rather than all at once.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::mem;
+
+use super::hashing::{SipHash, SlotHasher};
+use super::{load_factor, minimum_step, Resizing};
+
+const DEFAULT_CAPACITY: usize = 8;
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.75;
+
+/// The strategy used to find the next slot when a collision occurs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probing {
+    /// Visits slots `h, h + 1, h + 2, ...`
+    Linear,
+    /// Visits slots `h, h + 1, h + 3, h + 6, ...` - the triangular numbers, which
+    /// reach every slot of a power-of-two sized array
+    Quadratic,
+}
+
+#[derive(Debug, Clone)]
+enum Slot<K, V> {
+    Empty,
+    Tombstone,
+    Occupied(K, V),
+}
+
+// The slot array being drained by an incremental resize, the next slot to move, and the
+// fewest slots to move per step to be done before the next resize
+#[derive(Debug, Clone)]
+struct Migration<K, V> {
+    slots: Vec<Slot<K, V>>,
+    next: usize,
+    min_step: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Table {
+    Current,
+    Old,
+}
+
+// The slot holding an entry - it stays put until the table is next changed
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Position {
+    table: Table,
+    index: usize,
+}
+
+/// An open-addressing hash table
+///
+/// Stores key-value pairs in a single array of slots, resolving collisions by probing
+/// and resizing once the load factor crosses a configurable threshold.
+///
+/// Should be expected to have average performance characteristics of `O(1)` for
+/// inserts, lookups and removals - degrading towards `O(n)` as the load factor
+/// approaches one.
+///
+/// Keys are hashed with [SipHash](struct.SipHash.html) unless another
+/// [SlotHasher](trait.SlotHasher.html) is supplied.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = HashTable::new();
+/// phone_book.insert("John", 4283058824);
+/// phone_book.insert("Ringo", 2123134152);
+///
+/// assert_eq!(phone_book.get("John"), Some(&4283058824));
+/// assert_eq!(phone_book.remove("Ringo"), Some(2123134152));
+/// assert!(!phone_book.contains_key("Ringo"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HashTable<K, V, S = SipHash> {
+    slots: Vec<Slot<K, V>>,
+    len: usize,
+    tombstones: usize,
+    probing: Probing,
+    max_load_factor: f64,
+    resizing: Resizing,
+    migration: Option<Migration<K, V>>,
+    hasher: S,
+}
+
+impl<K: Hash + Eq, V> HashTable<K, V, SipHash> {
+    /// Creates an empty table using linear probing and a maximum load factor of `0.75`
+    pub fn new() -> Self {
+        Self::with_options(DEFAULT_CAPACITY, Probing::Linear, DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Creates an empty table able to hold at least `capacity` entries without resizing
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_options(capacity, Probing::Linear, DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Creates an empty table with the given probing strategy and maximum load factor
+    ///
+    /// See [with_options_and_hasher](struct.HashTable.html#method.with_options_and_hasher).
+    pub fn with_options(capacity: usize, probing: Probing, max_load_factor: f64) -> Self {
+        Self::with_options_and_hasher(capacity, probing, max_load_factor, SipHash)
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> HashTable<K, V, S> {
+    /// Creates an empty table which hashes its keys with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_options_and_hasher(
+            DEFAULT_CAPACITY,
+            Probing::Linear,
+            DEFAULT_MAX_LOAD_FACTOR,
+            hasher,
+        )
+    }
+
+    /// Creates an empty table with the given probing strategy, maximum load factor and hasher
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the number of entries the table can hold without resizing
+    /// * `probing` - the strategy used to resolve collisions
+    /// * `max_load_factor` - the load factor at which the table grows, between `0` and `1` (exclusive)
+    /// * `hasher` - the hash function used to place keys
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_load_factor` is not strictly between `0` and `1` - an open-addressing
+    /// table must always keep at least one slot empty to terminate its probe sequences.
+    pub fn with_options_and_hasher(
+        capacity: usize,
+        probing: Probing,
+        max_load_factor: f64,
+        hasher: S,
+    ) -> Self {
+        assert!(
+            max_load_factor > 0.0 && max_load_factor < 1.0,
+            "max_load_factor must be between 0 and 1 (exclusive), got {}",
+            max_load_factor
+        );
+
+        HashTable {
+            slots: empty_slots(slots_for(capacity, max_load_factor)),
+            len: 0,
+            tombstones: 0,
+            probing,
+            max_load_factor,
+            resizing: Resizing::Immediate,
+            migration: None,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the table's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of entries in the table
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the table holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots in the underlying array
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of slots currently holding a tombstone
+    pub fn tombstones(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Returns the probing strategy used by the table
+    pub fn probing(&self) -> Probing {
+        self.probing
+    }
+
+    /// Returns the current load factor - the number of entries over the number of slots
+    pub fn load_factor(&self) -> f64 {
+        load_factor(self.len, self.slots.len())
+    }
+
+    /// Returns the maximum load factor the table will reach before resizing
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load_factor
+    }
+
+    /// Returns how the table moves its entries when it outgrows its slots
+    pub fn resizing(&self) -> Resizing {
+        self.resizing
+    }
+
+    /// Sets how the table moves its entries when it outgrows its slots
+    ///
+    /// Switching to [Resizing::Immediate](enum.Resizing.html#variant.Immediate) completes any
+    /// resize already in progress.
+    ///
+    /// # Panics
+    ///
+    /// Panics if given an incremental `step` of zero.
+    pub fn set_resizing(&mut self, resizing: Resizing) {
+        if let Resizing::Incremental { step } = resizing {
+            assert!(
+                step > 0,
+                "an incremental resize must migrate at least one slot per step"
+            );
+        }
+        self.resizing = resizing;
+        if resizing == Resizing::Immediate {
+            self.finish_resize();
+        }
+    }
+
+    /// Returns `true` if an incremental resize is in progress
+    pub fn is_resizing(&self) -> bool {
+        self.migration.is_some()
+    }
+
+    /// Moves every remaining entry out of the old slot array, completing any resize in progress
+    pub fn finish_resize(&mut self) {
+        self.migrate(usize::MAX);
+    }
+
+    /// Inserts a key-value pair - returns the previous value if the key was already present
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.migrate_step();
+
+        if let Some((table, index, _)) = self.locate(&key) {
+            if let Slot::Occupied(_, v) = self.slot_mut(table, index) {
+                return Some(mem::replace(v, value));
+            }
+        }
+
+        self.reserve_one();
+        self.place(key, value);
+        self.len += 1;
+        None
+    }
+
+    /// Returns a reference to the value stored for `key`, if present
+    ///
+    /// Lookups through a shared reference cannot advance an incremental resize - use
+    /// [get_mut](struct.HashTable.html#method.get_mut) on hot paths to keep one moving. Inserts
+    /// alone still move it fast enough to finish before the next resize.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let (table, index, _) = self.locate(key)?;
+        let slots = match table {
+            Table::Current => &self.slots,
+            Table::Old => &self.migration.as_ref()?.slots,
+        };
+        match &slots[index] {
+            Slot::Occupied(_, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value stored for `key`, if present
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.migrate_step();
+
+        let (table, index, _) = self.locate(key)?;
+        match self.slot_mut(table, index) {
+            Slot::Occupied(_, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the table holds an entry for `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.locate(key).is_some()
+    }
+
+    /// Removes the entry for `key`, leaving a tombstone in its slot - returns the removed value
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.migrate_step();
+
+        let (table, index, _) = self.locate(key)?;
+        match mem::replace(self.slot_mut(table, index), Slot::Tombstone) {
+            Slot::Occupied(_, v) => {
+                self.len -= 1;
+                // Tombstones left in the old array are dropped along with it
+                if table == Table::Current {
+                    self.tombstones += 1;
+                }
+                Some(v)
+            }
+            _ => unreachable!("locate only returns occupied slots"),
+        }
+    }
+
+    /// Returns the number of slots examined to locate `key`, if present
+    ///
+    /// A key stored in its home slot has a probe length of `1`.
+    pub fn probe_length<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.locate(key).map(|(_, _, probes)| probes)
+    }
+
+    /// Returns an iterator over the entries of the table, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let old = self.migration.iter().flat_map(|m| m.slots.iter());
+        self.slots.iter().chain(old).filter_map(|slot| match slot {
+            Slot::Occupied(k, v) => Some((k, v)),
+            _ => None,
+        })
+    }
+
+    /// Keeps only the entries for which `keep` returns `true`, leaving tombstones in place of
+    /// the rest
+    ///
+    /// Should `keep` panic, the entries already removed stay removed and the rest stay in place.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let current = self.slots.len();
+        let old = self.migration.iter_mut().flat_map(|m| m.slots.iter_mut());
+        for (index, slot) in self.slots.iter_mut().chain(old).enumerate() {
+            if let Slot::Occupied(k, v) = slot {
+                if !keep(k, v) {
+                    // Counted as each entry goes, so a later panic leaves the counts right
+                    *slot = Slot::Tombstone;
+                    self.len -= 1;
+                    if index < current {
+                        self.tombstones += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes every entry (and tombstone), keeping the allocated slots
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot = Slot::Empty;
+        }
+        self.migration = None;
+        self.len = 0;
+        self.tombstones = 0;
+    }
+
+    // Returns the position of the entry for `key`, first inserting one with the value
+    // `default()` if there is none
+    pub(crate) fn position_or_insert_with<F>(&mut self, key: K, default: F) -> Position
+    where
+        F: FnOnce() -> V,
+    {
+        self.migrate_step();
+
+        if let Some((table, index, _)) = self.locate(&key) {
+            return Position { table, index };
+        }
+
+        let value = default();
+        self.reserve_one();
+        let index = self.place(key, value);
+        self.len += 1;
+        Position {
+            table: Table::Current,
+            index,
+        }
+    }
+
+    pub(crate) fn value_at(&self, position: Position) -> &V {
+        let slots = match (position.table, &self.migration) {
+            (Table::Old, Some(migration)) => &migration.slots,
+            _ => &self.slots,
+        };
+        match &slots[position.index] {
+            Slot::Occupied(_, v) => v,
+            _ => unreachable!("positions only point at occupied slots"),
+        }
+    }
+
+    pub(crate) fn value_at_mut(&mut self, position: Position) -> &mut V {
+        match self.slot_mut(position.table, position.index) {
+            Slot::Occupied(_, v) => v,
+            _ => unreachable!("positions only point at occupied slots"),
+        }
+    }
+
+    // Locates the slot holding `key`, checking the old array of any resize in progress -
+    // returns the array, the index and the number of slots examined
+    fn locate<Q>(&self, key: &Q) -> Option<(Table, usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let hash = self.hasher.hash(key);
+        if let Some((index, probes)) = find_in(&self.slots, self.probing, hash, key) {
+            return Some((Table::Current, index, probes));
+        }
+
+        let migration = self.migration.as_ref()?;
+        find_in(&migration.slots, self.probing, hash, key)
+            .map(|(index, probes)| (Table::Old, index, probes))
+    }
+
+    fn slot_mut(&mut self, table: Table, index: usize) -> &mut Slot<K, V> {
+        match (table, &mut self.migration) {
+            (Table::Old, Some(migration)) => &mut migration.slots[index],
+            _ => &mut self.slots[index],
+        }
+    }
+
+    // Stores an entry known not to be present in the current array, reusing the first
+    // tombstone or empty slot along its probe sequence - returns the index of that slot
+    fn place(&mut self, key: K, value: V) -> usize {
+        let hash = self.hasher.hash(&key);
+        self.place_hashed(hash, key, value)
+    }
+
+    // Stores an entry already hashed - calling no code of the key's, so it cannot panic part way
+    fn place_hashed(&mut self, hash: u64, key: K, value: V) -> usize {
+        for i in 0..self.slots.len() {
+            let index = probe(self.probing, hash, i, self.slots.len());
+            match self.slots[index] {
+                Slot::Occupied(..) => {}
+                Slot::Tombstone => {
+                    self.tombstones -= 1;
+                    self.slots[index] = Slot::Occupied(key, value);
+                    return index;
+                }
+                Slot::Empty => {
+                    self.slots[index] = Slot::Occupied(key, value);
+                    return index;
+                }
+            }
+        }
+
+        // `reserve_one` guarantees at least one empty slot, so a slot is always found
+        unreachable!("open-addressing table has no free slot");
+    }
+
+    // Ensures there is room for one more entry while staying under the maximum load factor.
+    //
+    // Tombstones count towards the load, as they lengthen probe sequences just like entries -
+    // if the table is mostly tombstones we rebuild at the same size to sweep them away,
+    // otherwise we double.
+    fn reserve_one(&mut self) {
+        let limit = self.slots.len() as f64 * self.max_load_factor;
+        if ((self.len + self.tombstones + 1) as f64) <= limit {
+            return;
+        }
+
+        let capacity = if ((self.len + 1) as f64) <= limit / 2.0 {
+            self.slots.len()
+        } else {
+            self.slots.len() * 2
+        };
+        self.resize(capacity);
+    }
+
+    // Swaps in a fresh slot array, then moves the entries of the old one across - all at
+    // once, or a step at a time as the table is used
+    fn resize(&mut self, capacity: usize) {
+        self.finish_resize();
+
+        let old = mem::replace(&mut self.slots, empty_slots(capacity));
+        self.tombstones = 0;
+
+        // The next resize comes once the entries reach the limit, and only inserts add entries
+        let headroom = capacity as f64 * self.max_load_factor - (self.len + 1) as f64;
+        self.migration = Some(Migration {
+            min_step: minimum_step(old.len(), headroom),
+            slots: old,
+            next: 0,
+        });
+
+        if self.resizing == Resizing::Immediate {
+            self.finish_resize();
+        }
+    }
+
+    fn migrate_step(&mut self) {
+        if let Resizing::Incremental { step } = self.resizing {
+            let min_step = self.migration.as_ref().map_or(0, |m| m.min_step);
+            self.migrate(step.max(min_step));
+        }
+    }
+
+    // Moves the entries of the next `step` old slots into the current array. Each emptied
+    // slot becomes a tombstone, so that keys further along still probe past it.
+    //
+    // Each entry is hashed while still in its old slot, and only then moved - so should the
+    // hasher panic, every entry is still in one array or the other.
+    fn migrate(&mut self, step: usize) {
+        let (next, end) = match &self.migration {
+            Some(migration) => (
+                migration.next,
+                migration
+                    .next
+                    .saturating_add(step)
+                    .min(migration.slots.len()),
+            ),
+            None => return,
+        };
+
+        for index in next..end {
+            let migration = self.migration.as_mut().expect("a resize is in progress");
+            let hash = match &migration.slots[index] {
+                Slot::Occupied(k, _) => Some(self.hasher.hash(k)),
+                _ => None,
+            };
+            let slot = mem::replace(&mut migration.slots[index], Slot::Tombstone);
+            migration.next = index + 1;
+
+            if let (Some(hash), Slot::Occupied(k, v)) = (hash, slot) {
+                self.place_hashed(hash, k, v);
+            }
+        }
+
+        if let Some(migration) = &self.migration {
+            if migration.next == migration.slots.len() {
+                self.migration = None;
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Default for HashTable<K, V, SipHash> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Locates the slot holding `key` within `slots` - returns its index and the number of slots examined
+fn find_in<K, V, Q>(
+    slots: &[Slot<K, V>],
+    probing: Probing,
+    hash: u64,
+    key: &Q,
+) -> Option<(usize, usize)>
+where
+    K: Borrow<Q>,
+    Q: Eq + ?Sized,
+{
+    for i in 0..slots.len() {
+        let index = probe(probing, hash, i, slots.len());
+        match &slots[index] {
+            Slot::Empty => return None,
+            Slot::Occupied(k, _) if k.borrow() == key => return Some((index, i + 1)),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// The `i`th slot of the probe sequence for `hash`, in an array of `len` (a power of two) slots
+fn probe(probing: Probing, hash: u64, i: usize, len: usize) -> usize {
+    let offset = match probing {
+        Probing::Linear => i,
+        Probing::Quadratic => i * (i + 1) / 2,
+    };
+    (hash as usize).wrapping_add(offset) & (len - 1)
+}
+
+// The smallest power-of-two slot count that holds `capacity` entries under `max_load_factor`
+fn slots_for(capacity: usize, max_load_factor: f64) -> usize {
+    let needed = (capacity as f64 / max_load_factor).ceil() as usize;
+    (needed + 1).next_power_of_two()
+}
+
+fn empty_slots<K, V>(capacity: usize) -> Vec<Slot<K, V>> {
+    (0..capacity).map(|_| Slot::Empty).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::hashing::Fnv1a;
+    use super::*;
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    #[test]
+    fn new_table_is_empty() {
+        let table: HashTable<&str, i32> = HashTable::new();
+        assert!(table.is_empty());
+        assert_eq!(table.get("foo"), None)
+    }
+
+    #[test]
+    fn insert_then_get_returns_value() {
+        let mut table = HashTable::new();
+        table.insert("foo", 1);
+        table.insert("bar", 2);
+        assert_eq!(table.get("foo"), Some(&1));
+        assert_eq!(table.get("bar"), Some(&2));
+        assert_eq!(table.len(), 2)
+    }
+
+    #[test]
+    fn insert_existing_key_replaces_value() {
+        let mut table = HashTable::new();
+        assert_eq!(table.insert("foo", 1), None);
+        assert_eq!(table.insert("foo", 2), Some(1));
+        assert_eq!(table.get("foo"), Some(&2));
+        assert_eq!(table.len(), 1)
+    }
+
+    #[test]
+    fn remove_leaves_tombstone_and_keeps_other_keys_reachable() {
+        let mut table = HashTable::with_options(64, Probing::Linear, 0.5);
+        for i in 0..32 {
+            table.insert(i, i * 10);
+        }
+        assert_eq!(table.remove(&7), Some(70));
+        assert_eq!(table.remove(&7), None);
+        assert_eq!(table.tombstones(), 1);
+        for i in (0..32).filter(|i| *i != 7) {
+            assert_eq!(table.get(&i), Some(&(i * 10)));
+        }
+    }
+
+    #[test]
+    fn retain_tombstones_rejected_entries() {
+        let mut table = HashTable::with_options(64, Probing::Linear, 0.5);
+        for i in 0..32 {
+            table.insert(i, i * 10);
+        }
+        table.retain(|k, v| {
+            *v += 1;
+            k % 2 == 0
+        });
+        assert_eq!(table.len(), 16);
+        assert_eq!(table.tombstones(), 16);
+        assert!((0..32).all(|i| table.get(&i).copied() == (i % 2 == 0).then_some(i * 10 + 1)))
+    }
+
+    #[test]
+    fn insert_reuses_tombstone_slot() {
+        let mut table = HashTable::new();
+        table.insert("foo", 1);
+        table.remove("foo");
+        table.insert("foo", 2);
+        assert_eq!(table.tombstones(), 0);
+        assert_eq!(table.get("foo"), Some(&2))
+    }
+
+    #[test]
+    fn table_grows_once_load_factor_is_crossed() {
+        let mut table = HashTable::with_options(0, Probing::Linear, 0.5);
+        for i in 0..1000 {
+            table.insert(i, i);
+            assert!(table.load_factor() <= 0.5);
+        }
+        assert_eq!(table.len(), 1000);
+        assert!((0..1000).all(|i| table.get(&i) == Some(&i)))
+    }
+
+    #[test]
+    fn quadratic_probing_matches_linear_probing_contents() {
+        let mut linear = HashTable::with_options(0, Probing::Linear, 0.9);
+        let mut quadratic = HashTable::with_options(0, Probing::Quadratic, 0.9);
+        for i in 0..500 {
+            linear.insert(i, i);
+            quadratic.insert(i, i);
+        }
+        for i in (0..500).step_by(3) {
+            assert_eq!(linear.remove(&i), quadratic.remove(&i));
+        }
+        for i in 0..500 {
+            assert_eq!(linear.get(&i), quadratic.get(&i));
+        }
+    }
+
+    #[test]
+    fn churn_of_inserts_and_removes_sweeps_tombstones() {
+        let mut table = HashTable::with_capacity(16);
+        for i in 0..10_000 {
+            table.insert(i, i);
+            table.remove(&i);
+        }
+        assert!(table.is_empty());
+        assert!(table.capacity() <= 32)
+    }
+
+    #[test]
+    fn probe_length_is_one_for_lone_key() {
+        let mut table = HashTable::new();
+        table.insert(String::from("foo"), 1);
+        assert_eq!(table.probe_length("foo"), Some(1));
+        assert_eq!(table.probe_length("bar"), None)
+    }
+
+    #[test]
+    fn table_works_over_any_slot_hasher() {
+        let mut table = HashTable::with_options_and_hasher(0, Probing::Quadratic, 0.5, Fnv1a);
+        for title in &["Maus", "Fun Home", "Watchmen"] {
+            table.insert(String::from(*title), title.len());
+        }
+        assert_eq!(table.get("Fun Home"), Some(&8));
+        assert_eq!(table.remove("Maus"), Some(4));
+        assert_eq!(table.len(), 2)
+    }
+
+    #[test]
+    #[should_panic]
+    fn load_factor_of_one_is_rejected() {
+        let _table: HashTable<i32, i32> = HashTable::with_options(8, Probing::Linear, 1.0);
+    }
+
+    #[test]
+    fn incremental_resize_keeps_every_entry_reachable() {
+        let mut map = HashTable::with_options(8, Probing::Linear, 0.75);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        for i in 0..10_000 {
+            map.insert(i, i);
+            assert_eq!(map.get(&(i / 2)), Some(&(i / 2)));
+        }
+        assert_eq!(map.len(), 10_000);
+        assert_eq!(map.iter().count(), 10_000);
+        assert!((0..10_000).all(|i| map.get(&i) == Some(&i)))
+    }
+
+    #[test]
+    fn incremental_resize_migrates_one_step_per_operation() {
+        let mut map = HashTable::with_options(8, Probing::Linear, 0.75);
+        map.set_resizing(Resizing::Incremental { step: 2 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        assert_eq!(map.migration.as_ref().unwrap().next, 0);
+        map.insert(i, i);
+        assert_eq!(map.migration.as_ref().unwrap().next, 2);
+        map.get_mut(&0);
+        assert_eq!(map.migration.as_ref().unwrap().next, 4)
+    }
+
+    #[test]
+    fn incremental_resize_finishes_before_the_next_one_starts() {
+        let mut map = HashTable::with_options(8, Probing::Linear, 0.75);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        for i in 0..10_000 {
+            let remaining = map.migration.as_ref().map(|m| {
+                let step = m.min_step.max(1);
+                (m.slots.len() - m.next, step)
+            });
+            let capacity = map.capacity();
+            map.insert(i, i);
+            if map.capacity() != capacity {
+                // Whatever was left of the last resize fit in the step this insert took
+                if let Some((remaining, step)) = remaining {
+                    assert!(
+                        remaining <= step,
+                        "{} slots left for a step of {}",
+                        remaining,
+                        step
+                    );
+                }
+            }
+        }
+    }
+
+    // Hashes with SipHash - or, once `fail` is set, panics
+    #[derive(Debug, Clone, Default)]
+    struct FragileHasher {
+        fail: Rc<Cell<bool>>,
+    }
+
+    impl SlotHasher<u32> for FragileHasher {
+        fn hash(&self, key: &u32) -> u64 {
+            assert!(!self.fail.get(), "hasher failed");
+            SipHash.hash(key)
+        }
+    }
+
+    #[test]
+    fn a_panicking_retain_leaves_the_counts_consistent() {
+        let mut map = HashTable::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            map.retain(|k, _| {
+                if *k == 50 {
+                    panic!("keep failed")
+                } else {
+                    k % 2 == 0
+                }
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.len(), map.iter().count());
+        assert!(map.len() < 100)
+    }
+
+    #[test]
+    fn a_panicking_hasher_mid_migration_loses_no_entries() {
+        let hasher = FragileHasher::default();
+        let mut map = HashTable::with_options_and_hasher(8, Probing::Linear, 0.75, hasher.clone());
+        map.set_resizing(Resizing::Incremental { step: 4 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+
+        hasher.fail.set(true);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| map.finish_resize()));
+        assert!(result.is_err());
+        hasher.fail.set(false);
+
+        assert!(map.is_resizing());
+        assert_eq!(map.len(), i as usize);
+        map.finish_resize();
+        assert!((0..i).all(|j| map.get(&j) == Some(&j)))
+    }
+
+    #[test]
+    fn positions_reach_values_without_advancing_a_resize() {
+        let mut map = HashTable::with_options(8, Probing::Linear, 0.75);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        let new = map.position_or_insert_with(-1, || 100);
+        // The key furthest along the old array, so the step taken below does not move it
+        let unmoved = (0..i)
+            .filter_map(|j| match map.locate(&j) {
+                Some((Table::Old, index, _)) => Some((index, j)),
+                _ => None,
+            })
+            .max()
+            .unwrap()
+            .1;
+        let old = map.position_or_insert_with(unmoved, || unreachable!());
+        assert_eq!(old.table, Table::Old);
+        let next = map.migration.as_ref().unwrap().next;
+
+        for _ in 0..10 {
+            *map.value_at_mut(old) += 1;
+            *map.value_at_mut(new) += 1;
+        }
+        assert_eq!(map.migration.as_ref().unwrap().next, next);
+        assert_eq!(
+            (*map.value_at(old), *map.value_at(new)),
+            (unmoved + 10, 110)
+        );
+        assert_eq!(map.get(&-1), Some(&110))
+    }
+
+    #[test]
+    fn remove_during_incremental_resize_reaches_old_slots() {
+        let mut map = HashTable::with_options(8, Probing::Linear, 0.75);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        for j in 0..=i {
+            assert_eq!(map.remove(&j), if j < i { Some(j) } else { None });
+        }
+        assert!(map.is_empty());
+        assert_eq!(map.iter().count(), 0)
+    }
+
+    #[test]
+    fn switching_to_immediate_resizing_finishes_migration() {
+        let mut map = HashTable::with_options(8, Probing::Linear, 0.75);
+        map.set_resizing(Resizing::Incremental { step: 1 });
+        let mut i = 0;
+        while !map.is_resizing() {
+            map.insert(i, i);
+            i += 1;
+        }
+        map.set_resizing(Resizing::Immediate);
+        assert!(!map.is_resizing());
+        assert!((0..i).all(|j| map.get(&j) == Some(&j)))
+    }
+}
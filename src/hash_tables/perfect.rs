@@ -0,0 +1,561 @@
+//! Perfect Hashing
+//!
+//! `perfect` provides [build_perfect_hash](fn.build_perfect_hash.html) and
+//! [FrozenMap](struct.FrozenMap.html) - hashing without collisions, for key sets known in advance.
+//!
+//! Every table elsewhere in this module has to cope with collisions, because it cannot know which
+//! keys are coming. But a read-only table - the battery sizes or book titles from the exercises
+//! above - knows all of its keys before the first lookup. With that knowledge we can search for a
+//! hash function that sends `n` keys to `n` slots with no two sharing: a *minimal perfect* hash.
+//!
+//! The search used here is 'hash and displace':
+//!
+//! 1. Hash each key into one of roughly `n / 5` buckets
+//! 2. Taking the biggest buckets first, look for a displacement pair `(d₁, d₂)` which sends every
+//!    key in the bucket to a distinct, still empty slot - `(f₂ + d₁ · f₁ + d₂) mod n`, where `f₁`
+//!    and `f₂` are two more hashes of the key
+//! 3. Record each bucket's displacement
+//!
+//! ```text
+//!  bucket 0: "AAA", "A"  ==> (d₁, d₂) = (0, 3)  ==> slots 3, 0
+//!  bucket 1: "AAAA"      ==> (d₁, d₂) = (1, 0)  ==> slot 1
+//!  bucket 2: "AA"        ==> (d₁, d₂) = (0, 1)  ==> slot 2
+//! ```
+//!
+//! A lookup then costs one hash, one displacement read and one comparison - and the function itself
+//! is just one pair of small numbers per bucket, cheap to store and reload without searching again.
+
+use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use super::codec::ByteCodec;
+use super::hashing::{mix, Fnv1a, SlotHasher};
+
+const KEYS_PER_BUCKET: usize = 5;
+const MAX_SEEDS: u64 = 64;
+
+/// The ways in which building or decoding a perfect hash can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfectHashError {
+    /// Two keys share a full 64-bit hash - most likely, the same key was given twice
+    HashCollision,
+    /// Every seed tried left some bucket without a displacement - vanishingly unlikely for keys
+    /// with distinct hashes, but possible with a poor hasher
+    NoDisplacement,
+    /// The bytes do not describe a perfect hash built with this hasher
+    Malformed,
+}
+
+impl fmt::Display for PerfectHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PerfectHashError::HashCollision => write!(f, "two keys share the same hash"),
+            PerfectHashError::NoDisplacement => write!(f, "no displacement separates the keys"),
+            PerfectHashError::Malformed => write!(f, "bytes do not describe a perfect hash"),
+        }
+    }
+}
+
+impl Error for PerfectHashError {}
+
+/// A minimal perfect hash function
+///
+/// Maps each of the `n` keys it was built from to a distinct index in `0..n`. Keys outside of that
+/// set map to an arbitrary index, so callers must check the key stored there.
+///
+/// Should be expected to have performance characteristics of `O(1)` for lookups, and `O(n)`
+/// on average to build.
+///
+/// Keys are hashed with [Fnv1a](struct.Fnv1a.html) unless another
+/// [SlotHasher](trait.SlotHasher.html) is supplied - its hashes don't change between Rust
+/// releases, so a function encoded with [to_bytes](struct.PerfectHash.html#method.to_bytes) can be
+/// stored and decoded again by a later build. It does see keys through their `Hash`
+/// implementations, though, which write integers - and the lengths of slices and `Vec`s - in the
+/// byte order and width of the target. Such keys hash differently on a target of another byte
+/// order or pointer width, so a function built from them should be decoded on the same kind of
+/// target. String keys are hashed from their bytes alone, and travel anywhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerfectHash<S = Fnv1a> {
+    seed: u64,
+    len: usize,
+    displacements: Vec<(u32, u32)>,
+    hasher: S,
+}
+
+/// Builds a minimal perfect hash function for a set of distinct keys
+///
+/// See [build_perfect_hash_with_hasher](fn.build_perfect_hash_with_hasher.html).
+///
+/// # Examples
+///
+/// ```rust
+/// let sizes = ["A", "AA", "AAA", "AAAA"];
+/// let hash = build_perfect_hash(&sizes).unwrap();
+///
+/// let mut indexes: Vec<usize> = sizes.iter().map(|size| hash.index(size)).collect();
+/// indexes.sort();
+/// assert_eq!(indexes, vec![0, 1, 2, 3]);
+/// ```
+pub fn build_perfect_hash<K: Hash>(keys: &[K]) -> Result<PerfectHash, PerfectHashError> {
+    build_perfect_hash_with_hasher(keys, Fnv1a)
+}
+
+/// Builds a minimal perfect hash function for a set of distinct keys, hashing with `hasher`
+///
+/// # Arguments
+///
+/// * `keys` - the keys to hash, which must not repeat
+/// * `hasher` - the hash function the perfect hash is derived from
+///
+/// # Errors
+///
+/// Returns [PerfectHashError::HashCollision](enum.PerfectHashError.html#variant.HashCollision)
+/// if two keys share a 64-bit hash, which no displacement can separate, and
+/// [PerfectHashError::NoDisplacement](enum.PerfectHashError.html#variant.NoDisplacement) if the
+/// search for displacements gives up.
+pub fn build_perfect_hash_with_hasher<K, S>(
+    keys: &[K],
+    hasher: S,
+) -> Result<PerfectHash<S>, PerfectHashError>
+where
+    S: SlotHasher<K>,
+{
+    let mut hashes: Vec<u64> = keys.iter().map(|key| hasher.hash(key)).collect();
+    hashes.sort_unstable();
+    if hashes.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(PerfectHashError::HashCollision);
+    }
+
+    let (seed, displacements) = search(&hashes, MAX_SEEDS)?;
+    Ok(PerfectHash {
+        seed,
+        len: keys.len(),
+        displacements,
+        hasher,
+    })
+}
+
+/// Tries up to `max_seeds` seeds - each gives fresh bucket assignments, so a rare unlucky one just
+/// moves on to the next - returning the first to displace every bucket
+fn search(hashes: &[u64], max_seeds: u64) -> Result<(u64, Vec<(u32, u32)>), PerfectHashError> {
+    (0..max_seeds)
+        .find_map(|seed| displace(hashes, seed).map(|displacements| (seed, displacements)))
+        .ok_or(PerfectHashError::NoDisplacement)
+}
+
+/// Searches for a displacement per bucket, giving up if some bucket has none
+fn displace(hashes: &[u64], seed: u64) -> Option<Vec<(u32, u32)>> {
+    let len = hashes.len();
+    let num_buckets = len.div_ceil(KEYS_PER_BUCKET).max(1);
+
+    let mut buckets: Vec<Vec<Derived>> = vec![Vec::new(); num_buckets];
+    for hash in hashes {
+        let derived = Derived::new(*hash, seed, len);
+        buckets[derived.bucket % num_buckets].push(derived);
+    }
+    let mut order: Vec<usize> = (0..num_buckets).collect();
+    order.sort_by_key(|bucket| std::cmp::Reverse(buckets[*bucket].len()));
+
+    let mut taken = vec![false; len];
+    let mut displacements = vec![(0, 0); num_buckets];
+    let mut slots = Vec::new();
+    for bucket in order {
+        let keys = &buckets[bucket];
+        if keys.is_empty() {
+            break;
+        }
+
+        let found = (0..len as u32)
+            .flat_map(|d1| (0..len as u32).map(move |d2| (d1, d2)))
+            .find(|(d1, d2)| {
+                slots.clear();
+                for key in keys {
+                    let slot = key.slot(*d1, *d2, len);
+                    if taken[slot] || slots.contains(&slot) {
+                        return false;
+                    }
+                    slots.push(slot);
+                }
+                true
+            })?;
+
+        for slot in &slots {
+            taken[*slot] = true;
+        }
+        displacements[bucket] = found;
+    }
+    Some(displacements)
+}
+
+/// The three hashes of a key used while displacing
+#[derive(Debug, Clone, Copy)]
+struct Derived {
+    bucket: usize,
+    f1: u64,
+    f2: u64,
+}
+
+impl Derived {
+    fn new(hash: u64, seed: u64, len: usize) -> Self {
+        let len = len.max(1) as u64;
+        Derived {
+            bucket: mix(hash, 3 * seed) as usize,
+            f1: mix(hash, 3 * seed + 1) % len,
+            f2: mix(hash, 3 * seed + 2) % len,
+        }
+    }
+
+    fn slot(&self, d1: u32, d2: u32, len: usize) -> usize {
+        ((self.f2 + u64::from(d1) * self.f1 + u64::from(d2)) % len as u64) as usize
+    }
+}
+
+impl<S> PerfectHash<S> {
+    /// Returns a reference to the function's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of keys the function was built from
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the function was built from no keys
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the index of a key, in `0..len()`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the function was built from no keys, as there is no index to return.
+    pub fn index<K>(&self, key: &K) -> usize
+    where
+        K: ?Sized,
+        S: SlotHasher<K>,
+    {
+        assert!(!self.is_empty(), "an empty perfect hash has no indexes");
+
+        let derived = Derived::new(self.hasher.hash(key), self.seed, self.len);
+        let (d1, d2) = self.displacements[derived.bucket % self.displacements.len()];
+        derived.slot(d1, d2, self.len)
+    }
+
+    /// Encodes the function as bytes
+    ///
+    /// The layout is the seed, key count and bucket count (each a `u64`), then each bucket's
+    /// displacement pair as two `u32`s - all little-endian. The hasher is not encoded, so a function
+    /// must be decoded with the same hasher it was built with - and one whose hashes stay the same
+    /// from build to build, unlike the standard library's SipHash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(24 + self.displacements.len() * 8);
+        self.encode(&mut bytes);
+        bytes
+    }
+
+    /// Decodes a function encoded by [to_bytes](struct.PerfectHash.html#method.to_bytes)
+    pub fn from_bytes(bytes: &[u8], hasher: S) -> Result<Self, PerfectHashError> {
+        let mut bytes = bytes;
+        let hash = Self::decode(&mut bytes, hasher)?;
+        if !bytes.is_empty() {
+            return Err(PerfectHashError::Malformed);
+        }
+        Ok(hash)
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.seed.encode(out);
+        (self.len as u64).encode(out);
+        (self.displacements.len() as u64).encode(out);
+        for (d1, d2) in &self.displacements {
+            d1.encode(out);
+            d2.encode(out);
+        }
+    }
+
+    fn decode(bytes: &mut &[u8], hasher: S) -> Result<Self, PerfectHashError> {
+        let malformed = PerfectHashError::Malformed;
+        let seed = u64::decode(bytes).ok_or(malformed)?;
+        let len = u64::decode(bytes).ok_or(malformed)? as usize;
+        let num_buckets = u64::decode(bytes).ok_or(malformed)? as usize;
+        if num_buckets != len.div_ceil(KEYS_PER_BUCKET).max(1) {
+            return Err(malformed);
+        }
+
+        let displacements = (0..num_buckets)
+            .map(|_| Some((u32::decode(bytes)?, u32::decode(bytes)?)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(malformed)?;
+        Ok(PerfectHash {
+            seed,
+            len,
+            displacements,
+            hasher,
+        })
+    }
+}
+
+/// A read-only map built on a minimal perfect hash
+///
+/// Stores its entries in a single array, positioned by a [PerfectHash](struct.PerfectHash.html),
+/// so that every lookup examines exactly one entry.
+///
+/// Should be expected to have worst-case performance characteristics of `O(1)` for lookups.
+///
+/// Keys are hashed with [Fnv1a](struct.Fnv1a.html) unless another
+/// [SlotHasher](trait.SlotHasher.html) is supplied, so that maps written out with
+/// [to_bytes](struct.FrozenMap.html#method.to_bytes) - and embedded in a program, say - still
+/// decode after a toolchain upgrade. As with [PerfectHash](struct.PerfectHash.html), maps with
+/// integer keys should be decoded on a target of the same byte order and pointer width they were
+/// built on - elsewhere their keys hash differently, and the bytes are rejected as malformed.
+///
+/// # Examples
+///
+/// ```rust
+/// let authors = FrozenMap::new(vec![
+///     ("Maus".to_string(), "Art Spiegelman".to_string()),
+///     ("Fun Home".to_string(), "Alison Bechdel".to_string()),
+///     ("Watchmen".to_string(), "Alan Moore".to_string()),
+/// ])
+/// .unwrap();
+///
+/// assert_eq!(authors.get("Maus"), Some(&"Art Spiegelman".to_string()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrozenMap<K, V, S = Fnv1a> {
+    hash: PerfectHash<S>,
+    entries: Vec<(K, V)>,
+}
+
+impl<K: Hash + Eq, V> FrozenMap<K, V, Fnv1a> {
+    /// Builds a map from its entries
+    ///
+    /// See [with_hasher](struct.FrozenMap.html#method.with_hasher).
+    pub fn new(entries: Vec<(K, V)>) -> Result<Self, PerfectHashError> {
+        Self::with_hasher(entries, Fnv1a)
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> FrozenMap<K, V, S> {
+    /// Builds a map from its entries, hashing keys with `hasher`
+    ///
+    /// # Errors
+    ///
+    /// Returns [PerfectHashError::HashCollision](enum.PerfectHashError.html#variant.HashCollision)
+    /// if a key repeats, or two keys share a 64-bit hash, and
+    /// [PerfectHashError::NoDisplacement](enum.PerfectHashError.html#variant.NoDisplacement) if the
+    /// search for displacements gives up.
+    pub fn with_hasher(entries: Vec<(K, V)>, hasher: S) -> Result<Self, PerfectHashError> {
+        let keys: Vec<&K> = entries.iter().map(|(key, _)| key).collect();
+        let hash = build_perfect_hash_with_hasher(&keys, ByReference(hasher))?;
+        let hash = PerfectHash {
+            seed: hash.seed,
+            len: hash.len,
+            displacements: hash.displacements,
+            hasher: hash.hasher.0,
+        };
+
+        let mut slots: Vec<Option<(K, V)>> = entries.iter().map(|_| None).collect();
+        for (key, value) in entries {
+            let index = hash.index(&key);
+            slots[index] = Some((key, value));
+        }
+        Ok(FrozenMap {
+            hash,
+            entries: slots.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Returns a reference to the map's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hash.hasher
+    }
+
+    /// Returns the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the map has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns a reference to the value for a key
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        if self.is_empty() {
+            return None;
+        }
+
+        let (candidate, value) = &self.entries[self.hash.index(key)];
+        if candidate.borrow() == key {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the map has an entry for the key
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns an iterator over the entries, in index order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K: Eq + ByteCodec, V: ByteCodec, S: SlotHasher<K>> FrozenMap<K, V, S> {
+    /// Encodes the map as bytes - its perfect hash, as in
+    /// [PerfectHash::to_bytes](struct.PerfectHash.html#method.to_bytes), followed by each entry's
+    /// key and value
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.hash.encode(&mut bytes);
+        for (key, value) in &self.entries {
+            key.encode(&mut bytes);
+            value.encode(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Decodes a map encoded by [to_bytes](struct.FrozenMap.html#method.to_bytes), without
+    /// searching for a perfect hash again
+    ///
+    /// Every key is checked to sit at its own index, so bytes decoded with the wrong hasher are
+    /// rejected as malformed rather than giving wrong answers.
+    pub fn from_bytes(bytes: &[u8], hasher: S) -> Result<Self, PerfectHashError> {
+        let mut bytes = bytes;
+        let hash = PerfectHash::decode(&mut bytes, hasher)?;
+
+        let mut entries = Vec::with_capacity(hash.len);
+        for index in 0..hash.len {
+            let key = K::decode(&mut bytes).ok_or(PerfectHashError::Malformed)?;
+            let value = V::decode(&mut bytes).ok_or(PerfectHashError::Malformed)?;
+            if hash.index(&key) != index {
+                return Err(PerfectHashError::Malformed);
+            }
+            entries.push((key, value));
+        }
+
+        if !bytes.is_empty() {
+            return Err(PerfectHashError::Malformed);
+        }
+        Ok(FrozenMap { hash, entries })
+    }
+}
+
+/// Lets a hasher of `K` hash `&K`, so keys can be hashed without being moved or cloned
+struct ByReference<S>(S);
+
+impl<'a, K, S: SlotHasher<K>> SlotHasher<&'a K> for ByReference<S> {
+    fn hash(&self, key: &&'a K) -> u64 {
+        self.0.hash(*key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::hashing::{LengthHasher, SipHash};
+    use super::*;
+
+    fn titles() -> Vec<(String, String)> {
+        vec![
+            ("Maus".to_string(), "Art Spiegelman".to_string()),
+            ("Fun Home".to_string(), "Alison Bechdel".to_string()),
+            ("Watchmen".to_string(), "Alan Moore".to_string()),
+        ]
+    }
+
+    #[test]
+    fn indexes_are_a_permutation() {
+        for &n in &[1, 2, 10, 1000, 20_000] {
+            let keys: Vec<u64> = (0..n).map(|i| i * 7919).collect();
+            let hash = build_perfect_hash(&keys).unwrap();
+
+            let mut seen = vec![false; n as usize];
+            for key in &keys {
+                let index = hash.index(key);
+                assert!(!seen[index]);
+                seen[index] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_keys_are_rejected() {
+        assert_eq!(
+            build_perfect_hash(&["AA", "A", "AA"]),
+            Err(PerfectHashError::HashCollision)
+        );
+        // Every battery size has a distinct length, but "A" and "B" would not
+        assert!(build_perfect_hash_with_hasher(&["A", "AA", "AAA", "AAAA"], LengthHasher).is_ok());
+        assert!(build_perfect_hash_with_hasher(&["A", "B"], LengthHasher).is_err())
+    }
+
+    #[test]
+    fn running_out_of_seeds_is_not_a_collision() {
+        assert_eq!(search(&[1, 2, 3], 0), Err(PerfectHashError::NoDisplacement));
+        assert_eq!(search(&[1, 2, 3], MAX_SEEDS).map(|(seed, _)| seed), Ok(0))
+    }
+
+    #[test]
+    fn frozen_map_finds_only_its_keys() {
+        let authors = FrozenMap::new(titles()).unwrap();
+        assert_eq!(authors.len(), 3);
+        assert_eq!(authors.get("Watchmen"), Some(&"Alan Moore".to_string()));
+        assert_eq!(authors.get("Maus"), Some(&"Art Spiegelman".to_string()));
+        assert_eq!(authors.get("Persepolis"), None)
+    }
+
+    #[test]
+    fn empty_frozen_map_finds_nothing() {
+        let empty: FrozenMap<String, u32> = FrozenMap::new(Vec::new()).unwrap();
+        assert_eq!(empty.get("Maus"), None)
+    }
+
+    #[test]
+    fn frozen_map_round_trips_through_bytes() {
+        let authors = FrozenMap::with_hasher(titles(), Fnv1a).unwrap();
+        let decoded = FrozenMap::from_bytes(&authors.to_bytes(), Fnv1a).unwrap();
+        assert_eq!(decoded, authors);
+        assert_eq!(decoded.get("Fun Home"), Some(&"Alison Bechdel".to_string()))
+    }
+
+    #[test]
+    fn bytes_for_another_hasher_are_malformed() {
+        let powers: Vec<(u32, u64)> = (0..100).map(|i| (i, u64::from(i) * 1000)).collect();
+        let bytes = FrozenMap::with_hasher(powers, Fnv1a).unwrap().to_bytes();
+        assert_eq!(
+            FrozenMap::<u32, u64, _>::from_bytes(&bytes, SipHash),
+            Err(PerfectHashError::Malformed)
+        );
+        assert_eq!(
+            FrozenMap::<u32, u64, _>::from_bytes(&bytes[..bytes.len() - 1], Fnv1a),
+            Err(PerfectHashError::Malformed)
+        )
+    }
+
+    #[test]
+    fn perfect_hash_round_trips_through_bytes() {
+        let keys: Vec<u32> = (0..500).collect();
+        let hash = build_perfect_hash(&keys).unwrap();
+        let decoded = PerfectHash::from_bytes(&hash.to_bytes(), Fnv1a).unwrap();
+        assert!(keys.iter().all(|key| decoded.index(key) == hash.index(key)))
+    }
+}
@@ -0,0 +1,441 @@
+//! Robin Hood Hashing
+//!
+//! `robin_hood` provides [RobinHoodMap](struct.RobinHoodMap.html) - an open-addressing table which
+//! keeps probe sequences short and even, so that it remains quick to search at load factors where
+//! plain linear probing would have long since bogged down.
+//!
+//! Every entry remembers its probe-sequence length (PSL) - how many slots past its home slot it
+//! had to travel. When an insert meets an entry that is 'richer' than it (one with a shorter PSL),
+//! it takes that entry's slot and carries on inserting the displaced entry instead:
+//!
+//! ```text
+//!  inserting `e` (home 1)         `e` now has PSL 2, `c` only 1 - so `e` steals the slot
+//!
+//!  [a:0][b:0][c:1][d:1][ ]   ==>   [a:0][b:0][e:2][c:2][d:2]
+//!    0    1    2    3   4            0    1    2    3    4
+//! ```
+//!
+//! Taking from the rich to give to the poor bounds how far any one entry sits from home, and it
+//! gives lookups an early exit: once we pass an entry with a shorter PSL than our own search
+//! distance, the key we want cannot be further along.
+//!
+//! Removal uses backward-shift deletion rather than tombstones - each following entry is moved
+//! one slot back towards its home until we meet an empty slot or an entry already at home.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::mem;
+
+use super::hashing::{SipHash, SlotHasher};
+use super::load_factor;
+
+const DEFAULT_CAPACITY: usize = 8;
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.9;
+
+#[derive(Debug, Clone)]
+struct Bucket<K, V> {
+    key: K,
+    value: V,
+    hash: u64,
+    psl: usize,
+}
+
+/// A Robin Hood hash map
+///
+/// Stores key-value pairs in a single array of slots, displacing entries that sit close to home
+/// in favour of those that have probed further.
+///
+/// Should be expected to have average performance characteristics of `O(1)` for inserts, lookups
+/// and removals - with an expected longest probe sequence of `O(log n)`, even at high load factors.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut dns_store = RobinHoodMap::new();
+/// dns_store.insert("google.com", [33, 125, 45, 42]);
+/// dns_store.insert("microsoft.com", [12, 123, 12, 123]);
+///
+/// assert_eq!(dns_store.get("google.com"), Some(&[33, 125, 45, 42]));
+/// assert_eq!(dns_store.probe_length("google.com"), Some(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RobinHoodMap<K, V, S = SipHash> {
+    slots: Vec<Option<Bucket<K, V>>>,
+    len: usize,
+    max_load_factor: f64,
+    hasher: S,
+}
+
+impl<K: Hash + Eq, V> RobinHoodMap<K, V, SipHash> {
+    /// Creates an empty map with a maximum load factor of `0.9`
+    pub fn new() -> Self {
+        Self::with_options(DEFAULT_CAPACITY, DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Creates an empty map able to hold at least `capacity` entries without resizing
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_options(capacity, DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Creates an empty map with the given capacity and maximum load factor
+    ///
+    /// See [with_options_and_hasher](struct.RobinHoodMap.html#method.with_options_and_hasher).
+    pub fn with_options(capacity: usize, max_load_factor: f64) -> Self {
+        Self::with_options_and_hasher(capacity, max_load_factor, SipHash)
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> RobinHoodMap<K, V, S> {
+    /// Creates an empty map which hashes its keys with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_options_and_hasher(DEFAULT_CAPACITY, DEFAULT_MAX_LOAD_FACTOR, hasher)
+    }
+
+    /// Creates an empty map with the given capacity, maximum load factor and hasher
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the number of entries the map can hold without resizing
+    /// * `max_load_factor` - the load factor at which the map grows, between `0` and `1` (exclusive)
+    /// * `hasher` - the hash function used to place keys
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_load_factor` is not strictly between `0` and `1`.
+    pub fn with_options_and_hasher(capacity: usize, max_load_factor: f64, hasher: S) -> Self {
+        assert!(
+            max_load_factor > 0.0 && max_load_factor < 1.0,
+            "max_load_factor must be between 0 and 1 (exclusive), got {}",
+            max_load_factor
+        );
+
+        let needed = (capacity as f64 / max_load_factor).ceil() as usize;
+        RobinHoodMap {
+            slots: empty_slots((needed + 1).next_power_of_two()),
+            len: 0,
+            max_load_factor,
+            hasher,
+        }
+    }
+
+    /// Returns a reference to the map's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of entries in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of slots in the underlying array
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the current load factor - the number of entries over the number of slots
+    pub fn load_factor(&self) -> f64 {
+        load_factor(self.len, self.slots.len())
+    }
+
+    /// Inserts a key-value pair - returns the previous value if the key was already present
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hasher.hash(&key);
+        if let Some(index) = self.find_index(hash, &key) {
+            let bucket = self.slots[index].as_mut().expect("found slot is occupied");
+            return Some(mem::replace(&mut bucket.value, value));
+        }
+
+        // Only a new entry adds to the load - replacing a value never needs the room
+        if load_factor(self.len + 1, self.slots.len()) > self.max_load_factor {
+            self.rebuild(self.slots.len() * 2);
+        }
+        self.place(Bucket {
+            key,
+            value,
+            hash,
+            psl: 0,
+        });
+        self.len += 1;
+        None
+    }
+
+    /// Returns a reference to the value stored for `key`, if present
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let index = self.find_index(self.hasher.hash(key), key)?;
+        self.slots[index].as_ref().map(|bucket| &bucket.value)
+    }
+
+    /// Returns a mutable reference to the value stored for `key`, if present
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let index = self.find_index(self.hasher.hash(key), key)?;
+        self.slots[index].as_mut().map(|bucket| &mut bucket.value)
+    }
+
+    /// Returns `true` if the map holds an entry for `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.find_index(self.hasher.hash(key), key).is_some()
+    }
+
+    /// Removes the entry for `key`, shifting the entries behind it back a slot - returns the removed value
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let mut index = self.find_index(self.hasher.hash(key), key)?;
+        let removed = self.slots[index].take().expect("found slot is occupied");
+        self.len -= 1;
+
+        // Backward-shift: pull each following entry one slot closer to home until we reach
+        // an empty slot or an entry that already sits in its home slot
+        let mask = self.slots.len() - 1;
+        loop {
+            let next = (index + 1) & mask;
+            match self.slots[next].take() {
+                Some(mut bucket) if bucket.psl > 0 => {
+                    bucket.psl -= 1;
+                    self.slots[index] = Some(bucket);
+                    index = next;
+                }
+                other => {
+                    self.slots[next] = other;
+                    break;
+                }
+            }
+        }
+
+        Some(removed.value)
+    }
+
+    /// Returns the number of slots examined to locate `key`, if present
+    ///
+    /// A key stored in its home slot has a probe length of `1`.
+    pub fn probe_length<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let index = self.find_index(self.hasher.hash(key), key)?;
+        self.slots[index].as_ref().map(|bucket| bucket.psl + 1)
+    }
+
+    /// Returns the longest probe sequence of any entry, in slots examined
+    pub fn max_probe_length(&self) -> usize {
+        self.buckets()
+            .map(|bucket| bucket.psl + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the mean probe sequence over all entries, in slots examined
+    pub fn mean_probe_length(&self) -> f64 {
+        match self.len {
+            0 => 0.0,
+            len => self.buckets().map(|bucket| bucket.psl + 1).sum::<usize>() as f64 / len as f64,
+        }
+    }
+
+    /// Returns an iterator over the entries of the map, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets().map(|bucket| (&bucket.key, &bucket.value))
+    }
+
+    /// Removes every entry, keeping the allocated slots
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot = None;
+        }
+        self.len = 0;
+    }
+
+    fn buckets(&self) -> impl Iterator<Item = &Bucket<K, V>> {
+        self.slots.iter().flatten()
+    }
+
+    // Walks the probe sequence from the home slot, stopping early once the resident entry is
+    // closer to its home than we are to ours - our key would have displaced it if present
+    fn find_index<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let mask = self.slots.len() - 1;
+        let mut index = hash as usize & mask;
+
+        for distance in 0..self.slots.len() {
+            match &self.slots[index] {
+                None => return None,
+                Some(bucket) if bucket.psl < distance => return None,
+                Some(bucket) if bucket.hash == hash && bucket.key.borrow() == key => {
+                    return Some(index)
+                }
+                Some(_) => index = (index + 1) & mask,
+            }
+        }
+
+        None
+    }
+
+    // Places a bucket known not to be present, stealing from richer entries along the way
+    fn place(&mut self, mut incoming: Bucket<K, V>) {
+        let mask = self.slots.len() - 1;
+        let mut index = incoming.hash as usize & mask;
+
+        loop {
+            match &mut self.slots[index] {
+                None => {
+                    self.slots[index] = Some(incoming);
+                    return;
+                }
+                Some(resident) => {
+                    if resident.psl < incoming.psl {
+                        mem::swap(resident, &mut incoming);
+                    }
+                }
+            }
+            incoming.psl += 1;
+            index = (index + 1) & mask;
+        }
+    }
+
+    fn rebuild(&mut self, capacity: usize) {
+        let old = mem::replace(&mut self.slots, empty_slots(capacity));
+        for mut bucket in old.into_iter().flatten() {
+            bucket.psl = 0;
+            self.place(bucket);
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Default for RobinHoodMap<K, V, SipHash> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn empty_slots<K, V>(capacity: usize) -> Vec<Option<Bucket<K, V>>> {
+    (0..capacity).map(|_| None).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::open_addressing::{HashTable, Probing};
+    use super::*;
+
+    #[test]
+    fn insert_then_get_returns_value() {
+        let mut map = RobinHoodMap::new();
+        map.insert("foo", 1);
+        map.insert("bar", 2);
+        assert_eq!(map.get("foo"), Some(&1));
+        assert_eq!(map.get("bar"), Some(&2));
+        assert_eq!(map.get("baz"), None)
+    }
+
+    #[test]
+    fn insert_existing_key_replaces_value() {
+        let mut map = RobinHoodMap::new();
+        assert_eq!(map.insert("foo", 1), None);
+        assert_eq!(map.insert("foo", 2), Some(1));
+        assert_eq!(map.len(), 1)
+    }
+
+    #[test]
+    fn replacing_a_value_at_the_threshold_does_not_grow_the_map() {
+        let mut map = RobinHoodMap::with_options(16, 0.5);
+        let capacity = map.capacity();
+        for i in 0..capacity / 2 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.insert(0, 1), Some(0));
+        assert_eq!(map.capacity(), capacity)
+    }
+
+    #[test]
+    fn remove_shifts_following_entries_back() {
+        let mut map = RobinHoodMap::with_options(1000, 0.9);
+        for i in 0..900 {
+            map.insert(i, i);
+        }
+        for i in (0..900).step_by(2) {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+        for i in 0..900 {
+            assert_eq!(map.get(&i), if i % 2 == 0 { None } else { Some(&i) });
+        }
+        assert_eq!(map.len(), 450)
+    }
+
+    #[test]
+    fn entries_are_never_further_from_home_than_recorded() {
+        let mut map = RobinHoodMap::with_options(0, 0.95);
+        for i in 0..5000 {
+            map.insert(i, ());
+            if i % 3 == 0 {
+                map.remove(&(i / 2));
+            }
+        }
+        let mask = map.capacity() - 1;
+        for (index, slot) in map.slots.iter().enumerate() {
+            if let Some(bucket) = slot {
+                assert_eq!((bucket.hash as usize + bucket.psl) & mask, index);
+            }
+        }
+    }
+
+    #[test]
+    fn map_grows_once_load_factor_is_crossed() {
+        let mut map = RobinHoodMap::with_options(0, 0.9);
+        for i in 0..1000 {
+            map.insert(i, i);
+            assert!(map.load_factor() <= 0.9);
+        }
+        assert!((0..1000).all(|i| map.get(&i) == Some(&i)))
+    }
+
+    #[test]
+    fn probe_tail_is_shorter_than_linear_probing_at_high_load() {
+        let mut robin_hood = RobinHoodMap::with_options(0, 0.9);
+        let mut linear = HashTable::with_options(0, Probing::Linear, 0.9);
+        for i in 0..50_000 {
+            robin_hood.insert(i, ());
+            linear.insert(i, ());
+        }
+        let linear_max = (0..50_000)
+            .filter_map(|i| linear.probe_length(&i))
+            .max()
+            .unwrap();
+        assert!(robin_hood.max_probe_length() < linear_max);
+        assert!(robin_hood.mean_probe_length() < 10.0)
+    }
+
+    #[test]
+    fn empty_map_reports_no_probes() {
+        let map: RobinHoodMap<u64, u64> = RobinHoodMap::new();
+        assert_eq!(map.max_probe_length(), 0);
+        assert_eq!(map.mean_probe_length(), 0.0)
+    }
+}
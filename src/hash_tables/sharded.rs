@@ -0,0 +1,566 @@
+//! Sharded Hash Map
+//!
+//! `sharded` provides [ShardedMap](struct.ShardedMap.html) - a hash table that many threads can
+//! read and write at once.
+//!
+//! The simplest thread-safe table is an ordinary one behind a single lock, but then every thread
+//! waits on every other, however unrelated their keys. Sharding splits the table into `N`
+//! independent tables - shards - each behind its own lock, and uses the key's hash to pick a shard:
+//!
+//! ```text
+//!  hash("John")  ==> shard 2         [ shard 0 ] [ shard 1 ] [ shard 2 ] [ shard 3 ]
+//!  hash("Ringo") ==> shard 0               ^                       ^
+//!                                    thread A                 thread B     - no waiting
+//! ```
+//!
+//! Threads only contend when their keys land in the same shard, so with enough shards most
+//! operations proceed in parallel. Each shard uses a read-write lock, so any number of readers can
+//! share one shard at a time.
+//!
+//! The price is paid by operations spanning the whole map. `len` and `retain` visit the shards one
+//! at a time, so writers may slip in between. Where a single point-in-time view matters,
+//! [snapshot](struct.ShardedMap.html#method.snapshot) holds every shard's lock at once - always
+//! taken in shard order, so two snapshots can never deadlock each other.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::hashing::{mix, SipHash, SlotHasher};
+use super::open_addressing::{HashTable, Position};
+
+const DEFAULT_SHARDS: usize = 16;
+
+/// A concurrent hash map
+///
+/// Partitions its entries across a fixed number of [HashTable](struct.HashTable.html)s, each
+/// behind its own read-write lock, so that threads working on different shards never block one
+/// another. All methods take `&self`, so the map can be shared between threads in an `Arc`.
+///
+/// Should be expected to have average performance characteristics of `O(1)` for inserts, lookups
+/// and removals, plus any time spent waiting for the key's shard.
+///
+/// A thread which panics while holding a shard's lock does not poison the map. A `HashTable` keeps
+/// its entries and count consistent even when a hasher, key or `retain` closure panics part way
+/// through an operation, so other threads carry on using the shard.
+///
+/// # Examples
+///
+/// ```rust
+/// let phone_book = Arc::new(ShardedMap::new());
+///
+/// let writer = Arc::clone(&phone_book);
+/// thread::spawn(move || writer.insert("John", 4283058824)).join().unwrap();
+///
+/// assert_eq!(phone_book.get("John"), Some(4283058824));
+/// ```
+#[derive(Debug)]
+pub struct ShardedMap<K, V, S = SipHash> {
+    shards: Vec<RwLock<HashTable<K, V, S>>>,
+    hasher: S,
+}
+
+impl<K: Hash + Eq, V> ShardedMap<K, V, SipHash> {
+    /// Creates an empty map with `16` shards
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Creates an empty map with the given number of shards
+    ///
+    /// See [with_shards_and_hasher](struct.ShardedMap.html#method.with_shards_and_hasher).
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, SipHash)
+    }
+}
+
+impl<K: Hash + Eq, V> Default for ShardedMap<K, V, SipHash> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K> + Clone> ShardedMap<K, V, S> {
+    /// Creates an empty map with `16` shards, hashing keys with `hasher`
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_shards_and_hasher(DEFAULT_SHARDS, hasher)
+    }
+
+    /// Creates an empty map with the given number of shards, hashing keys with `hasher`
+    ///
+    /// A few times the number of threads expected to use the map at once keeps contention low.
+    ///
+    /// # Arguments
+    ///
+    /// * `shards` - the number of independently locked tables
+    /// * `hasher` - the hash function used both to choose a shard and within each shard
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        assert!(shards > 0, "a sharded map needs at least one shard");
+
+        ShardedMap {
+            shards: (0..shards)
+                .map(|_| RwLock::new(HashTable::with_hasher(hasher.clone())))
+                .collect(),
+            hasher,
+        }
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> ShardedMap<K, V, S> {
+    /// Returns a reference to the map's hasher
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns the number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of entries in each shard, in shard order
+    pub fn shard_lens(&self) -> Vec<usize> {
+        (0..self.shards.len())
+            .map(|shard| self.read(shard).len())
+            .collect()
+    }
+
+    /// Returns the number of entries
+    ///
+    /// Shards are counted one at a time, so the total may be out of date by the time it returns
+    /// if other threads are writing.
+    pub fn len(&self) -> usize {
+        self.shard_lens().iter().sum()
+    }
+
+    /// Returns `true` if the map holds no entries - subject to the same caveat as
+    /// [len](struct.ShardedMap.html#method.len)
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts a key-value pair - returns the previous value if the key was already present
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let shard = self.shard_for(&key);
+        self.write(shard).insert(key, value)
+    }
+
+    /// Returns a copy of the value stored for `key`, if present
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+        V: Clone,
+    {
+        self.read_with(key, V::clone)
+    }
+
+    /// Calls `f` on the value stored for `key`, if present, while holding the shard's read lock
+    ///
+    /// Useful for reading part of a value without cloning all of it.
+    pub fn read_with<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+        F: FnOnce(&V) -> R,
+    {
+        self.read(self.shard_for(key)).get(key).map(f)
+    }
+
+    /// Returns `true` if the map holds an entry for `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.read(self.shard_for(key)).contains_key(key)
+    }
+
+    /// Removes the entry for `key` - returns the removed value
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.write(self.shard_for(key)).remove(key)
+    }
+
+    /// Locks the shard for `key` and returns its entry, for reading and updating in place
+    ///
+    /// The shard stays locked until the entry (or the value reference it hands out) is dropped,
+    /// so the check and the update happen atomically:
+    ///
+    /// ```rust
+    /// let word_counts = ShardedMap::new();
+    /// *word_counts.entry("the").or_insert(0) += 1;
+    /// ```
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, S> {
+        let shard = self.write(self.shard_for(&key));
+        Entry { shard, key }
+    }
+
+    /// Keeps only the entries for which `keep` returns `true`
+    ///
+    /// Shards are visited one at a time, each under its write lock - so entries inserted by other
+    /// threads during the call may or may not be visited.
+    pub fn retain<F>(&self, mut keep: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for shard in 0..self.shards.len() {
+            self.write(shard).retain(&mut keep);
+        }
+    }
+
+    /// Removes every entry, one shard at a time
+    pub fn clear(&self) {
+        for shard in 0..self.shards.len() {
+            self.write(shard).clear();
+        }
+    }
+
+    /// Returns a copy of every entry as of a single moment
+    ///
+    /// Every shard's read lock is taken, in shard order, before any entry is copied - so writers
+    /// are held off until the copy is complete, and no write is ever half seen.
+    pub fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let guards: Vec<_> = (0..self.shards.len())
+            .map(|shard| self.read(shard))
+            .collect();
+        guards
+            .iter()
+            .flat_map(|table| table.iter())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    fn shard_for<Q>(&self, key: &Q) -> usize
+    where
+        Q: ?Sized,
+        S: SlotHasher<Q>,
+    {
+        // Mixing keeps the choice of shard independent of the slot chosen within it
+        (mix(self.hasher.hash(key), 0) % self.shards.len() as u64) as usize
+    }
+
+    fn read(&self, shard: usize) -> RwLockReadGuard<'_, HashTable<K, V, S>> {
+        self.shards[shard]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, shard: usize) -> RwLockWriteGuard<'_, HashTable<K, V, S>> {
+        self.shards[shard]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A key's entry in a [ShardedMap](struct.ShardedMap.html), holding its shard's write lock
+pub struct Entry<'a, K, V, S = SipHash> {
+    shard: RwLockWriteGuard<'a, HashTable<K, V, S>>,
+    key: K,
+}
+
+impl<'a, K: Eq, V, S: SlotHasher<K>> Entry<'a, K, V, S> {
+    /// Returns the entry's key
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns a reference to the entry's value, if it has one
+    pub fn get(&self) -> Option<&V> {
+        self.shard.get(&self.key)
+    }
+
+    /// Returns a mutable reference to the entry's value, if it has one
+    pub fn get_mut(&mut self) -> Option<&mut V> {
+        self.shard.get_mut(&self.key)
+    }
+
+    /// Returns `true` if the entry has a value
+    pub fn is_occupied(&self) -> bool {
+        self.shard.contains_key(&self.key)
+    }
+
+    /// Calls `f` on the entry's value, if it has one
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Some(value) = self.get_mut() {
+            f(value);
+        }
+        self
+    }
+
+    /// Sets the entry's value - returns the previous value, if it had one
+    pub fn insert(mut self, value: V) -> Option<V> {
+        self.shard.insert(self.key, value)
+    }
+
+    /// Removes the entry's value - returns it, if it had one
+    pub fn remove(mut self) -> Option<V> {
+        self.shard.remove(&self.key)
+    }
+
+    /// Gives the entry `default` as its value if it has none, returning a reference to the value
+    pub fn or_insert(self, default: V) -> ValueMut<'a, K, V, S> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Gives the entry the result of `default` as its value if it has none, returning a reference
+    /// to the value - `default` is only called if it is needed
+    pub fn or_insert_with<F: FnOnce() -> V>(mut self, default: F) -> ValueMut<'a, K, V, S> {
+        let position = self.shard.position_or_insert_with(self.key, default);
+        ValueMut {
+            shard: self.shard,
+            position,
+        }
+    }
+
+    /// Gives the entry `V::default()` as its value if it has none, returning a reference to the
+    /// value
+    pub fn or_default(self) -> ValueMut<'a, K, V, S>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// A mutable reference to a value in a [ShardedMap](struct.ShardedMap.html), holding its
+/// shard's write lock
+///
+/// The value's slot is found once, when the reference is made - the lock keeps it from moving.
+pub struct ValueMut<'a, K, V, S = SipHash> {
+    shard: RwLockWriteGuard<'a, HashTable<K, V, S>>,
+    position: Position,
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> Deref for ValueMut<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.shard.value_at(self.position)
+    }
+}
+
+impl<K: Eq, V, S: SlotHasher<K>> DerefMut for ValueMut<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.shard.value_at_mut(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    const THREADS: usize = 8;
+
+    #[test]
+    fn behaves_like_a_map_on_one_thread() {
+        let map = ShardedMap::with_shards(4);
+        assert_eq!(map.insert("John", 1), None);
+        assert_eq!(map.insert("John", 2), Some(1));
+        assert_eq!(map.get("John"), Some(2));
+        assert!(map.contains_key("John"));
+        assert_eq!(map.remove("John"), Some(2));
+        assert!(map.is_empty())
+    }
+
+    #[test]
+    fn entry_api_updates_in_place() {
+        let map = ShardedMap::new();
+        *map.entry("John").or_insert(0) += 5;
+        *map.entry("John").or_default() += 1;
+        map.entry("Ringo").and_modify(|v| *v += 100).or_insert(7);
+        assert_eq!(map.get("John"), Some(6));
+        assert_eq!(map.get("Ringo"), Some(7));
+
+        assert_eq!(map.entry("John").insert(0), Some(6));
+        assert_eq!(map.entry("Ringo").remove(), Some(7));
+        assert!(!map.entry("Ringo").is_occupied())
+    }
+
+    #[test]
+    fn entry_values_need_no_clone_of_the_key() {
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Name(&'static str);
+
+        let map = ShardedMap::new();
+        *map.entry(Name("John")).or_insert(0) += 5;
+        *map.entry(Name("John")).or_insert_with(|| unreachable!()) += 1;
+        *map.entry(Name("Ringo")).or_default() += 7;
+
+        assert_eq!(map.get(&Name("John")), Some(6));
+        assert_eq!(map.get(&Name("Ringo")), Some(7))
+    }
+
+    #[test]
+    fn keys_spread_across_shards() {
+        let map = ShardedMap::with_shards(8);
+        for i in 0..8000 {
+            map.insert(i, ());
+        }
+        assert!(map.shard_lens().iter().all(|len| *len > 800 && *len < 1200))
+    }
+
+    #[test]
+    fn concurrent_inserts_of_disjoint_keys_are_all_kept() {
+        let map = Arc::new(ShardedMap::new());
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for i in 0..5000 {
+                        map.insert(t * 5000 + i, t);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(map.len(), THREADS * 5000);
+        assert!((0..THREADS * 5000).all(|key| map.get(&key) == Some(key / 5000)))
+    }
+
+    #[test]
+    fn concurrent_entry_increments_are_never_lost() {
+        let map = Arc::new(ShardedMap::with_shards(4));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for i in 0..10_000 {
+                        *map.entry(i % 50).or_insert(0u64) += 1;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let expected = (THREADS * 10_000 / 50) as u64;
+        assert!((0..50).all(|key| map.get(&key) == Some(expected)))
+    }
+
+    #[test]
+    fn retain_runs_alongside_writers() {
+        let map = Arc::new(ShardedMap::new());
+        let writers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for i in 0..2000 {
+                        map.insert(t * 2000 + i, i);
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..20 {
+            map.retain(|_, v| *v % 2 == 0);
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        map.retain(|_, v| *v % 2 == 0);
+        assert_eq!(map.len(), THREADS * 1000);
+        assert!(map.snapshot().iter().all(|(_, v)| v % 2 == 0))
+    }
+
+    #[test]
+    fn snapshots_see_a_single_moment() {
+        // One writer inserts keys in order, so any consistent view is exactly `0..n` for some `n`
+        let map = Arc::new(ShardedMap::new());
+        let done = Arc::new(AtomicBool::new(false));
+
+        let writer = {
+            let (map, done) = (Arc::clone(&map), Arc::clone(&done));
+            thread::spawn(move || {
+                for i in 0..20_000u32 {
+                    map.insert(i, ());
+                }
+                done.store(true, Ordering::SeqCst);
+            })
+        };
+        let readers: Vec<_> = (0..THREADS - 1)
+            .map(|_| {
+                let (map, done) = (Arc::clone(&map), Arc::clone(&done));
+                thread::spawn(move || {
+                    while !done.load(Ordering::SeqCst) {
+                        let mut keys: Vec<u32> =
+                            map.snapshot().into_iter().map(|(k, _)| k).collect();
+                        keys.sort_unstable();
+                        assert!(keys.iter().enumerate().all(|(i, k)| i as u32 == *k));
+                    }
+                })
+            })
+            .collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(map.len(), 20_000)
+    }
+
+    #[test]
+    fn a_panicking_thread_does_not_poison_the_map() {
+        let map = Arc::new(ShardedMap::with_shards(1));
+        map.insert("John", 1);
+
+        let panicker = Arc::clone(&map);
+        let result = thread::spawn(move || {
+            panicker
+                .entry("John")
+                .and_modify(|_| panic!("worker failed"));
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert_eq!(map.get("John"), Some(1))
+    }
+
+    #[test]
+    fn a_panicking_retain_leaves_the_shard_consistent() {
+        let map = Arc::new(ShardedMap::with_shards(1));
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+
+        let panicker = Arc::clone(&map);
+        let result = thread::spawn(move || {
+            panicker.retain(|k, _| {
+                if *k == 500 {
+                    panic!("worker failed")
+                } else {
+                    false
+                }
+            });
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert_eq!(map.len(), map.snapshot().len());
+        assert!(map.contains_key(&500));
+        map.insert(1000, 1000);
+        assert_eq!(map.get(&1000), Some(1000))
+    }
+}
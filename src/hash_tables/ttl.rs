@@ -0,0 +1,468 @@
+//! TTL Cache
+//!
+//! `ttl` provides [TtlCache](struct.TtlCache.html) - a cache whose entries expire once their
+//! time-to-live (TTL) has passed.
+//!
+//! The DNS resolution example from earlier in this module is the classic case: a DNS record comes
+//! with a TTL, after which the answer may have changed and must be fetched again. So each entry
+//! is stored with a deadline, and there are two ways for an expired entry to leave the cache:
+//!
+//! * Lazily - any lookup that finds an expired entry drops it and reports a miss
+//! * Actively - [purge_expired](struct.TtlCache.html#method.purge_expired) sweeps out every entry
+//!   whose deadline has passed, so that entries which are never looked up again don't linger
+//!
+//! To make the active sweep cheap, deadlines are also kept in a min-heap - the sweep pops entries
+//! from the front of the heap until it reaches one that is still live. Overwriting or removing a
+//! key leaves its old deadline in the heap, so once the heap holds twice as many deadlines as the
+//! cache holds entries, it is rebuilt from the entries alone.
+//!
+//! The cache reads the current time through a [Clock](trait.Clock.html), so that tests may
+//! substitute a [ManualClock](struct.ManualClock.html) and move time along deterministically.
+
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::hashing::{SipHash, SlotHasher};
+use super::open_addressing::HashTable;
+
+/// A source of the current time
+pub trait Clock {
+    /// Returns the current instant
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock which only moves when told to
+///
+/// Clones share the same time, so a test can keep one and hand another to a cache.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Rc<Cell<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at the current instant
+    pub fn new() -> Self {
+        ManualClock {
+            now: Rc::new(Cell::new(Instant::now())),
+        }
+    }
+
+    /// Moves the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+// An entry's value, and the instant it expires - None if it never does, its time-to-live
+// reaching past the furthest instant the clock can represent
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    value: V,
+    deadline: Option<Instant>,
+}
+
+impl<V> Entry<V> {
+    fn is_live(&self, now: Instant) -> bool {
+        self.deadline.is_none_or(|deadline| deadline > now)
+    }
+}
+
+// A heap entry ordered by deadline alone (soonest first), so keys need not be comparable.
+// Overwriting or removing a key leaves its old heap entry behind - the sweep recognises and
+// skips these by comparing against the deadline actually stored in the table, and they are
+// cleared out whenever the heap is rebuilt.
+#[derive(Debug, Clone)]
+struct Expiry<K> {
+    deadline: Instant,
+    key: K,
+}
+
+impl<K> PartialEq for Expiry<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl<K> Eq for Expiry<K> {}
+
+impl<K> PartialOrd for Expiry<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for Expiry<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+/// A cache of entries which expire after a time-to-live
+///
+/// Expired entries are dropped lazily when looked up, and actively by
+/// [purge_expired](struct.TtlCache.html#method.purge_expired).
+///
+/// Should be expected to have average performance characteristics of `O(log n)` for inserts
+/// (which also record a deadline in a heap), `O(1)` for lookups, and `O(k log n)` for a sweep
+/// expiring `k` entries.
+///
+/// # Examples
+///
+/// ```rust
+/// let clock = ManualClock::new();
+/// let mut dns_cache = TtlCache::with_clock(Duration::from_secs(300), clock.clone());
+/// dns_cache.insert("google.com", [33, 125, 45, 42]);
+///
+/// clock.advance(Duration::from_secs(301));
+/// assert_eq!(dns_cache.get("google.com"), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TtlCache<K, V, C = SystemClock, S = SipHash> {
+    entries: HashTable<K, Entry<V>, S>,
+    deadlines: BinaryHeap<Expiry<K>>,
+    default_ttl: Duration,
+    clock: C,
+}
+
+impl<K: Hash + Eq + Clone, V> TtlCache<K, V, SystemClock, SipHash> {
+    /// Creates an empty cache whose entries live for `default_ttl` unless told otherwise
+    pub fn new(default_ttl: Duration) -> Self {
+        Self::with_clock_and_hasher(default_ttl, SystemClock, SipHash)
+    }
+}
+
+impl<K: Hash + Eq + Clone, V, C: Clock> TtlCache<K, V, C, SipHash> {
+    /// Creates an empty cache which reads the time from `clock`
+    pub fn with_clock(default_ttl: Duration, clock: C) -> Self {
+        Self::with_clock_and_hasher(default_ttl, clock, SipHash)
+    }
+}
+
+impl<K: Eq + Clone, V, C: Clock, S: SlotHasher<K>> TtlCache<K, V, C, S> {
+    /// Creates an empty cache which reads the time from `clock` and hashes its keys with `hasher`
+    pub fn with_clock_and_hasher(default_ttl: Duration, clock: C, hasher: S) -> Self {
+        TtlCache {
+            entries: HashTable::with_hasher(hasher),
+            deadlines: BinaryHeap::new(),
+            default_ttl,
+            clock,
+        }
+    }
+
+    /// Returns the number of entries held, including any that have expired but not yet been dropped
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the cache holds no entries, expired or otherwise
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the time-to-live given to entries inserted without one
+    pub fn default_ttl(&self) -> Duration {
+        self.default_ttl
+    }
+
+    /// Inserts an entry which expires after the default time-to-live - returns the previous
+    /// value if the key was present and had not expired
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_with_ttl(key, value, self.default_ttl)
+    }
+
+    /// Inserts an entry which expires after `ttl` - returns the previous value if the key was
+    /// present and had not expired
+    ///
+    /// An entry whose `ttl` reaches past the furthest instant the clock can represent (such as
+    /// `Duration::MAX`) never expires.
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let now = self.clock.now();
+        let deadline = now.checked_add(ttl);
+
+        if let Some(deadline) = deadline {
+            self.deadlines.push(Expiry {
+                deadline,
+                key: key.clone(),
+            });
+        }
+        let previous = self
+            .entries
+            .insert(key, Entry { value, deadline })
+            .filter(|previous| previous.is_live(now))
+            .map(|previous| previous.value);
+
+        self.compact_deadlines();
+        previous
+    }
+
+    /// Returns the value stored for `key`, dropping the entry instead if it has expired
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let now = self.clock.now();
+        let expired = !self.entries.get(key)?.is_live(now);
+        if expired {
+            self.entries.remove(key);
+            self.compact_deadlines();
+            return None;
+        }
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Returns `true` if the cache holds a live entry for `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        self.time_to_live(key).is_some()
+    }
+
+    /// Returns how long the entry for `key` has left to live, if it is present and live -
+    /// `Duration::MAX` for an entry which never expires
+    pub fn time_to_live<Q>(&self, key: &Q) -> Option<Duration>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let now = self.clock.now();
+        self.entries
+            .get(key)
+            .filter(|entry| entry.is_live(now))
+            .map(|entry| {
+                entry
+                    .deadline
+                    .map_or(Duration::MAX, |deadline| deadline - now)
+            })
+    }
+
+    /// Removes the entry for `key` - returns its value if it had not expired
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        S: SlotHasher<Q>,
+    {
+        let now = self.clock.now();
+        let removed = self
+            .entries
+            .remove(key)
+            .filter(|entry| entry.is_live(now))
+            .map(|entry| entry.value);
+
+        self.compact_deadlines();
+        removed
+    }
+
+    /// Drops every entry whose deadline is at or before `now` - returns the number dropped
+    pub fn purge_expired(&mut self, now: Instant) -> usize {
+        let mut purged = 0;
+
+        while let Some(next) = self.deadlines.peek() {
+            if next.deadline > now {
+                break;
+            }
+            let Expiry { deadline, key } = self.deadlines.pop().expect("peeked entry exists");
+
+            // Skip heap entries left behind by an overwrite or removal of the key
+            let current = self.entries.get(&key).and_then(|entry| entry.deadline);
+            if current == Some(deadline) {
+                self.entries.remove(&key);
+                purged += 1;
+            }
+        }
+
+        purged
+    }
+
+    /// Drops every entry whose deadline has passed by the cache's clock - returns the number dropped
+    pub fn purge(&mut self) -> usize {
+        self.purge_expired(self.clock.now())
+    }
+
+    /// Returns an iterator over the live entries of the cache
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let now = self.clock.now();
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| entry.is_live(now))
+            .map(|(k, entry)| (k, &entry.value))
+    }
+
+    /// Removes every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.deadlines.clear();
+    }
+
+    // Rebuilds the heap from the entries' own deadlines once stale ones - left by overwrites and
+    // removals - outnumber the live ones, so that the heap can't grow without bound between
+    // purges. Rebuilding costs `O(n)`, and takes at least `n` inserts to come round again.
+    fn compact_deadlines(&mut self) {
+        if self.deadlines.len() <= 2 * self.entries.len() {
+            return;
+        }
+        self.deadlines = self
+            .entries
+            .iter()
+            .filter_map(|(key, entry)| {
+                entry.deadline.map(|deadline| Expiry {
+                    deadline,
+                    key: key.clone(),
+                })
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn live_entry_is_returned() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        cache.insert("google.com", 1);
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(cache.get("google.com"), Some(&1));
+        assert_eq!(
+            cache.time_to_live("google.com"),
+            Some(Duration::from_secs(1))
+        )
+    }
+
+    #[test]
+    fn expired_entry_is_dropped_lazily_on_get() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        cache.insert("google.com", 1);
+        clock.advance(MINUTE);
+        assert_eq!(cache.len(), 1);
+        assert!(!cache.contains_key("google.com"));
+        assert_eq!(cache.get("google.com"), None);
+        assert_eq!(cache.len(), 0)
+    }
+
+    #[test]
+    fn purge_expired_drops_only_entries_past_their_deadline() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        for i in 0..10 {
+            cache.insert_with_ttl(i, i, Duration::from_secs(i + 1));
+        }
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.purge(), 5);
+        assert_eq!(cache.len(), 5);
+        assert!((5..10).all(|i| cache.contains_key(&i)))
+    }
+
+    #[test]
+    fn purge_skips_deadlines_replaced_by_reinsertion() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        cache.insert("a", 1);
+        clock.advance(Duration::from_secs(30));
+        cache.insert("a", 2);
+        clock.advance(Duration::from_secs(45));
+        assert_eq!(cache.purge(), 0);
+        assert_eq!(cache.get("a"), Some(&2))
+    }
+
+    #[test]
+    fn purge_expired_accepts_an_explicit_instant() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        cache.insert("a", 1);
+        cache.remove("a");
+        cache.insert("b", 2);
+        assert_eq!(cache.purge_expired(clock.now() + MINUTE), 1);
+        assert!(cache.is_empty())
+    }
+
+    #[test]
+    fn insert_over_expired_entry_returns_nothing() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        assert_eq!(cache.insert("a", 1), None);
+        assert_eq!(cache.insert("a", 2), Some(1));
+        clock.advance(MINUTE);
+        assert_eq!(cache.insert("a", 3), None)
+    }
+
+    #[test]
+    fn stale_deadlines_are_compacted_away() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        for i in 0..10_000 {
+            cache.insert(i % 10, i);
+            clock.advance(Duration::from_millis(1));
+            if i % 3 == 0 {
+                cache.remove(&(i % 10));
+            }
+        }
+        assert!(cache.deadlines.len() <= 2 * cache.len() + 1);
+
+        clock.advance(MINUTE);
+        let live = cache.len();
+        assert_eq!(cache.purge(), live);
+        assert!(cache.is_empty())
+    }
+
+    #[test]
+    fn a_ttl_past_the_end_of_time_never_expires() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        cache.insert_with_ttl("forever", 1, Duration::MAX);
+        clock.advance(Duration::from_secs(1_000_000_000));
+        assert_eq!(cache.purge(), 0);
+        assert_eq!(cache.time_to_live("forever"), Some(Duration::MAX));
+        assert_eq!(cache.insert("forever", 2), Some(1));
+        assert_eq!(cache.get("forever"), Some(&2))
+    }
+
+    #[test]
+    fn iter_skips_expired_entries() {
+        let clock = ManualClock::new();
+        let mut cache = TtlCache::with_clock(MINUTE, clock.clone());
+        cache.insert_with_ttl("short", 1, Duration::from_secs(1));
+        cache.insert("long", 2);
+        clock.advance(Duration::from_secs(1));
+        let live: Vec<_> = cache.iter().collect();
+        assert_eq!(live, vec![(&"long", &2)])
+    }
+}
@@ -0,0 +1,327 @@
+//! Bounds and Insertion Points
+//!
+//! `bounds` generalizes binary search from 'is the item here?' to 'where does the item belong?'.
+//!
+//! Every search here is built on a single question - given a sorted list split in two by a
+//! predicate, true for every element of the first part and false for every element of the second,
+//! where is the split?
+//!
+//! ```text
+//!  list:       [ 1, 3, 3, 3, 5, 8 ]
+//!  x < 3:        T  F  F  F  F  F     partition_point = 1 = lower_bound(3)
+//!  x <= 3:       T  T  T  T  F  F     partition_point = 4 = upper_bound(3)
+//!                   |-----|
+//!                   equal_range(3) = 1..4
+//! ```
+//!
+//! Halving the range each step finds the split in `O(log n)` comparisons, and the split is always
+//! a valid index to insert at without breaking the order - so a search that misses still says
+//! where the item would go. Like `std`, the `binary_search_*` functions return `Ok(index)` for a
+//! match and `Err(index)` for that insertion point.
+
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use super::monotonic::midpoint;
+
+/// A partition point search
+///
+/// Returns the index of the first element for which `pred` is false, or the list's length if
+/// there is none.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A vector of elements, partitioned so that `pred` holds for a prefix and no further
+/// * `pred` - The predicate splitting the vector
+///
+/// # Examples
+///
+/// ```rust
+/// let list = vec![1, 2, 3, 10, 20];
+/// assert_eq!(partition_point(&list, |x| *x < 5), 3);
+/// ```
+pub fn partition_point<T, P>(list: &[T], mut pred: P) -> usize
+where
+    P: FnMut(&T) -> bool,
+{
+    // The split always lies within low..=high
+    let mut low = 0;
+    let mut high = list.len();
+
+    while low < high {
+        let mid = midpoint(low, high);
+        if pred(&list[mid]) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// A lower bound search with a comparator
+///
+/// Returns the index of the first element not ordered `Less` by `compare` - the first position
+/// the searched-for item could be inserted at.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A vector of elements, sorted consistently with `compare`
+/// * `compare` - Orders an element of the vector against the searched-for item
+///
+/// # Examples
+///
+/// ```rust
+/// let list = vec![1, 3, 3, 5];
+/// assert_eq!(lower_bound_by(&list, |x| x.cmp(&3)), 1);
+/// ```
+pub fn lower_bound_by<T, F>(list: &[T], mut compare: F) -> usize
+where
+    F: FnMut(&T) -> Ordering,
+{
+    partition_point(list, |x| compare(x) == Ordering::Less)
+}
+
+/// An upper bound search with a comparator
+///
+/// Returns the index of the first element ordered `Greater` by `compare` - the last position the
+/// searched-for item could be inserted at.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A vector of elements, sorted consistently with `compare`
+/// * `compare` - Orders an element of the vector against the searched-for item
+///
+/// # Examples
+///
+/// ```rust
+/// let list = vec![1, 3, 3, 5];
+/// assert_eq!(upper_bound_by(&list, |x| x.cmp(&3)), 3);
+/// ```
+pub fn upper_bound_by<T, F>(list: &[T], mut compare: F) -> usize
+where
+    F: FnMut(&T) -> Ordering,
+{
+    partition_point(list, |x| compare(x) != Ordering::Greater)
+}
+
+/// A lower bound search
+///
+/// Returns the index of the first element not less than `item`.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A sorted vector of elements
+/// * `item` - An element to find the bound of
+///
+/// # Examples
+///
+/// ```rust
+/// let list = vec![1, 3, 3, 5];
+/// assert_eq!(lower_bound(&list, &3), 1);
+/// assert_eq!(lower_bound(&list, &4), 3);
+/// ```
+pub fn lower_bound<T: Ord>(list: &[T], item: &T) -> usize {
+    lower_bound_by(list, |x| x.cmp(item))
+}
+
+/// An upper bound search
+///
+/// Returns the index of the first element greater than `item`.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A sorted vector of elements
+/// * `item` - An element to find the bound of
+///
+/// # Examples
+///
+/// ```rust
+/// let list = vec![1, 3, 3, 5];
+/// assert_eq!(upper_bound(&list, &3), 3);
+/// ```
+pub fn upper_bound<T: Ord>(list: &[T], item: &T) -> usize {
+    upper_bound_by(list, |x| x.cmp(item))
+}
+
+/// An equal range search
+///
+/// Returns the range of indexes holding elements equal to `item` - empty, and positioned where
+/// `item` would be inserted, if there are none.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A sorted vector of elements
+/// * `item` - An element to find the range of
+///
+/// # Examples
+///
+/// ```rust
+/// let list = vec![1, 3, 3, 5];
+/// assert_eq!(equal_range(&list, &3), 1..3);
+/// assert_eq!(equal_range(&list, &4), 3..3);
+/// ```
+pub fn equal_range<T: Ord>(list: &[T], item: &T) -> Range<usize> {
+    equal_range_by(list, |x| x.cmp(item))
+}
+
+/// An equal range search with a comparator
+///
+/// Returns the range of indexes of elements ordered `Equal` by `compare`.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A vector of elements, sorted consistently with `compare`
+/// * `compare` - Orders an element of the vector against the searched-for item
+///
+/// # Examples
+///
+/// ```rust
+/// let words = vec!["apple", "banana", "blueberry", "cherry"];
+/// assert_eq!(equal_range_by(&words, |w| w[..1].cmp("b")), 1..3);
+/// ```
+pub fn equal_range_by<T, F>(list: &[T], mut compare: F) -> Range<usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let start = lower_bound_by(list, &mut compare);
+    let end = start + upper_bound_by(&list[start..], &mut compare);
+    start..end
+}
+
+/// A binary search with a comparator
+///
+/// Returns `Ok` with the index of the first element ordered `Equal` by `compare`, or `Err` with
+/// the index at which such an element could be inserted, keeping the vector sorted.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A vector of elements, sorted consistently with `compare`
+/// * `compare` - Orders an element of the vector against the searched-for item
+///
+/// # Examples
+///
+/// ```rust
+/// let list = vec![1, 3, 3, 5];
+/// assert_eq!(binary_search_by(&list, |x| x.cmp(&3)), Ok(1));
+/// assert_eq!(binary_search_by(&list, |x| x.cmp(&4)), Err(3));
+/// ```
+pub fn binary_search_by<T, F>(list: &[T], mut compare: F) -> Result<usize, usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let index = lower_bound_by(list, &mut compare);
+    match list.get(index).map(compare) {
+        Some(Ordering::Equal) => Ok(index),
+        _ => Err(index),
+    }
+}
+
+/// A binary search by key
+///
+/// Searches a vector sorted by the key `key_of` extracts - returning `Ok` with the index of the
+/// first element whose key equals `key`, or `Err` with the index at which one could be inserted.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A vector of elements, sorted by their keys
+/// * `key` - The key to search for
+/// * `key_of` - Extracts the key of an element
+///
+/// # Examples
+///
+/// ```rust
+/// let phone_book = vec![("Adit", 5551234), ("Maggie", 5559876), ("Rama", 5550000)];
+/// assert_eq!(binary_search_by_key(&phone_book, &"Maggie", |entry| entry.0), Ok(1));
+/// assert_eq!(binary_search_by_key(&phone_book, &"Bob", |entry| entry.0), Err(1));
+/// ```
+pub fn binary_search_by_key<T, B, F>(list: &[T], key: &B, mut key_of: F) -> Result<usize, usize>
+where
+    B: Ord,
+    F: FnMut(&T) -> B,
+{
+    binary_search_by(list, |x| key_of(x).cmp(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::small_sorted_lists;
+    use super::*;
+
+    #[test]
+    fn partition_point_of_an_empty_vector_is_zero() {
+        let v: Vec<usize> = Vec::new();
+        assert_eq!(partition_point(&v, |_| true), 0)
+    }
+
+    #[test]
+    fn partition_point_matches_a_linear_scan() {
+        for list in small_sorted_lists() {
+            for split in 0..=5 {
+                let expected = list.iter().take_while(|x| **x < split).count();
+                assert_eq!(partition_point(&list, |x| *x < split), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_bracket_every_equal_element() {
+        for list in small_sorted_lists() {
+            for item in 0..=5 {
+                let range = equal_range(&list, &item);
+                assert_eq!(range.start, lower_bound(&list, &item));
+                assert_eq!(range.end, upper_bound(&list, &item));
+                assert!(list[..range.start].iter().all(|x| *x < item));
+                assert!(list[range.clone()].iter().all(|x| *x == item));
+                assert!(list[range.end..].iter().all(|x| *x > item));
+            }
+        }
+    }
+
+    #[test]
+    fn binary_search_by_agrees_with_std() {
+        for list in small_sorted_lists() {
+            for item in 0..=5 {
+                let found = binary_search_by(&list, |x| x.cmp(&item));
+                match list.binary_search(&item) {
+                    Ok(_) => assert_eq!(found, Ok(list.iter().position(|x| *x == item).unwrap())),
+                    Err(index) => assert_eq!(found, Err(index)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn binary_search_by_key_finds_insertion_points() {
+        let books = vec![(1949, "1984"), (1953, "Fahrenheit 451"), (1985, "Watchmen")];
+        assert_eq!(binary_search_by_key(&books, &1953, |book| book.0), Ok(1));
+        assert_eq!(binary_search_by_key(&books, &1900, |book| book.0), Err(0));
+        assert_eq!(binary_search_by_key(&books, &2000, |book| book.0), Err(3))
+    }
+
+    #[test]
+    fn comparators_can_reverse_the_order() {
+        let descending = vec![9, 7, 7, 2];
+        assert_eq!(lower_bound_by(&descending, |x| 7.cmp(x)), 1);
+        assert_eq!(upper_bound_by(&descending, |x| 7.cmp(x)), 3);
+        assert_eq!(binary_search_by(&descending, |x| 5.cmp(x)), Err(3))
+    }
+}
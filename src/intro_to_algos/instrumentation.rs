@@ -0,0 +1,419 @@
+//! Counting the Work
+//!
+//! `instrumentation` turns the Big O claims above into something we can watch happen.
+//!
+//! Rather than timing the searches - timings wobble with the machine, the cache and everything else
+//! running - we count the work they do: every comparison made, and every *probe*, each time a
+//! search moves on to look at a different element of the list. Any search here can be counted
+//! unchanged, by running it over [Tracked](struct.Tracked.html) elements, which report each
+//! comparison made on them to a shared [SearchStats](struct.SearchStats.html):
+//!
+//! ```text
+//!  stats.track(&list)  -->  [ 0 | 1 | 2 | 3 | ... ]   each element knows its index and the stats
+//!  stats.item(3)       -->  item to search for
+//!
+//!  linear_search: 0 == 3? 1 == 3? 2 == 3? 3 == 3!    4 comparisons, 4 probes
+//! ```
+//!
+//! [measure_growth](fn.measure_growth.html) repeats a search over growing lists and fits the counts
+//! to the common run times - `O(1)`, `O(log n)`, `O(n)`, `O(n log n)` and `O(n^2)` - and
+//! [search_report](fn.search_report.html) does so for every search in this module at once.
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::searches::Numeric;
+use super::{
+    exponential_search, fibonacci_search, interpolation_search, iterative_binary_search,
+    linear_search, recursive_binary_search,
+};
+
+/// Counts of the work done by a search
+#[derive(Debug, Default)]
+pub struct SearchStats {
+    comparisons: Cell<u64>,
+    probes: Cell<u64>,
+    last_probe: Cell<Option<usize>>,
+}
+
+impl SearchStats {
+    /// Creates a counter with nothing yet counted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of comparisons made
+    pub fn comparisons(&self) -> u64 {
+        self.comparisons.get()
+    }
+
+    /// Returns the number of probes - the times the search moved on to a different element
+    pub fn probes(&self) -> u64 {
+        self.probes.get()
+    }
+
+    /// Sets both counts back to zero
+    pub fn reset(&self) {
+        self.comparisons.set(0);
+        self.probes.set(0);
+        self.last_probe.set(None);
+    }
+
+    /// Copies a list into elements which count their comparisons here
+    ///
+    /// Should be expected to have performance characteristics of `O(n)`.
+    pub fn track<'s, T: Clone>(&'s self, list: &[T]) -> Vec<Tracked<'s, T>> {
+        list.iter()
+            .enumerate()
+            .map(|(index, value)| Tracked {
+                value: value.clone(),
+                index: Some(index),
+                stats: self,
+            })
+            .collect()
+    }
+
+    /// Wraps an item to search for, counting its comparisons here
+    pub fn item<T>(&self, value: T) -> Tracked<'_, T> {
+        Tracked {
+            value,
+            index: None,
+            stats: self,
+        }
+    }
+
+    fn record(&self, index: Option<usize>) {
+        self.comparisons.set(self.comparisons.get() + 1);
+        if index.is_some() && index != self.last_probe.get() {
+            self.probes.set(self.probes.get() + 1);
+            self.last_probe.set(index);
+        }
+    }
+}
+
+/// A value which counts every comparison made on it
+///
+/// Created by [SearchStats::track](struct.SearchStats.html#method.track) for the elements of a list,
+/// or [SearchStats::item](struct.SearchStats.html#method.item) for the item searched for.
+#[derive(Debug, Clone, Copy)]
+pub struct Tracked<'s, T> {
+    value: T,
+    index: Option<usize>,
+    stats: &'s SearchStats,
+}
+
+impl<'s, T> Tracked<'s, T> {
+    /// Returns the wrapped value
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    fn record(&self, other: &Self) {
+        self.stats.record(self.index.or(other.index));
+    }
+}
+
+impl<'s, T: PartialEq> PartialEq for Tracked<'s, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.record(other);
+        self.value == other.value
+    }
+}
+
+impl<'s, T: Eq> Eq for Tracked<'s, T> {}
+
+impl<'s, T: Ord> PartialOrd for Tracked<'s, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'s, T: Ord> Ord for Tracked<'s, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.record(other);
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<'s, T: Numeric> Numeric for Tracked<'s, T> {
+    fn to_f64(self) -> f64 {
+        self.value.to_f64()
+    }
+}
+
+/// A common run time, to fit observed counts against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Growth {
+    /// `O(1)`
+    Constant,
+    /// `O(log n)`
+    Logarithmic,
+    /// `O(n)`
+    Linear,
+    /// `O(n log n)`
+    Linearithmic,
+    /// `O(n^2)`
+    Quadratic,
+}
+
+impl Growth {
+    const ALL: [Growth; 5] = [
+        Growth::Constant,
+        Growth::Logarithmic,
+        Growth::Linear,
+        Growth::Linearithmic,
+        Growth::Quadratic,
+    ];
+
+    /// Returns the shape of the run time at input size `n`, up to a constant factor
+    pub fn shape(self, n: f64) -> f64 {
+        let n = n.max(2.0);
+        match self {
+            Growth::Constant => 1.0,
+            Growth::Logarithmic => n.log2(),
+            Growth::Linear => n,
+            Growth::Linearithmic => n * n.log2(),
+            Growth::Quadratic => n * n,
+        }
+    }
+}
+
+impl fmt::Display for Growth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let notation = match self {
+            Growth::Constant => "O(1)",
+            Growth::Logarithmic => "O(log n)",
+            Growth::Linear => "O(n)",
+            Growth::Linearithmic => "O(n log n)",
+            Growth::Quadratic => "O(n^2)",
+        };
+        f.write_str(notation)
+    }
+}
+
+/// Fits observed counts to the run time they grow most like
+///
+/// Each [Growth](enum.Growth.html) is scaled by the constant which best matches the counts, and
+/// the one left with the smallest relative error wins.
+///
+/// # Arguments
+///
+/// * `samples` - Pairs of input size and the count observed at that size
+///
+/// # Examples
+///
+/// ```rust
+/// let samples = vec![(10, 21.0), (100, 199.0), (1000, 2003.0)];
+/// assert_eq!(fit_growth(&samples), Growth::Linear);
+/// ```
+pub fn fit_growth(samples: &[(usize, f64)]) -> Growth {
+    let error = |growth: Growth| {
+        // The least-squares scale for count ~ c * shape(n), relative to each count
+        let (mut numerator, mut denominator) = (0.0, 0.0);
+        for &(n, count) in samples {
+            let ratio = growth.shape(n as f64) / count.max(1.0);
+            numerator += ratio;
+            denominator += ratio * ratio;
+        }
+        let scale = numerator / denominator;
+        samples
+            .iter()
+            .map(|&(n, count)| {
+                let relative = scale * growth.shape(n as f64) / count.max(1.0) - 1.0;
+                relative * relative
+            })
+            .sum::<f64>()
+    };
+
+    let mut best = Growth::Constant;
+    let mut best_error = f64::INFINITY;
+    for &growth in &Growth::ALL {
+        let error = error(growth);
+        if error < best_error {
+            best = growth;
+            best_error = error;
+        }
+    }
+    best
+}
+
+/// The counts observed for one search over growing input sizes
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrowthReport {
+    /// The name of the search
+    pub name: String,
+    /// Input sizes, with the average comparisons and probes per search at each
+    pub samples: Vec<(usize, f64, f64)>,
+    /// The run time the comparisons grew most like
+    pub growth: Growth,
+}
+
+impl fmt::Display for GrowthReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} - grows like {}", self.name, self.growth)?;
+        writeln!(f, "{:>10} {:>14} {:>10}", "n", "comparisons", "probes")?;
+        for (n, comparisons, probes) in &self.samples {
+            writeln!(f, "{:>10} {:>14.1} {:>10.1}", n, comparisons, probes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Measures how the work done by a search grows with the size of its input
+///
+/// Runs `search` once for each size, then fits the average comparisons per search to a run time.
+///
+/// # Arguments
+///
+/// * `name` - The name of the search, for the report
+/// * `sizes` - The input sizes to measure at
+/// * `search` - Searches an input of the given size, counting into the given stats, and returns
+///   the number of searches it made
+///
+/// # Examples
+///
+/// ```rust
+/// let report = measure_growth("linear", &[10, 100, 1000], |n, stats| {
+///     let list: Vec<usize> = (0..n).collect();
+///     linear_search(&stats.track(&list), stats.item(n - 1));
+///     1
+/// });
+/// assert_eq!(report.growth, Growth::Linear);
+/// ```
+pub fn measure_growth<F>(name: &str, sizes: &[usize], mut search: F) -> GrowthReport
+where
+    F: FnMut(usize, &SearchStats) -> usize,
+{
+    let samples: Vec<(usize, f64, f64)> = sizes
+        .iter()
+        .map(|&n| {
+            let stats = SearchStats::new();
+            let searches = search(n, &stats).max(1) as f64;
+            (
+                n,
+                stats.comparisons() as f64 / searches,
+                stats.probes() as f64 / searches,
+            )
+        })
+        .collect();
+
+    let counts: Vec<(usize, f64)> = samples.iter().map(|&(n, c, _)| (n, c)).collect();
+    GrowthReport {
+        name: name.to_string(),
+        growth: fit_growth(&counts),
+        samples,
+    }
+}
+
+/// Measures every search in this module over sorted lists of the given sizes
+///
+/// Each list holds `0, 2, 4, ...`, and each search looks up a spread of items across it, present
+/// and absent alike.
+///
+/// # Examples
+///
+/// ```rust
+/// for report in search_report(&[16, 256, 4096, 65536]) {
+///     println!("{}", report);
+/// }
+/// ```
+pub fn search_report(sizes: &[usize]) -> Vec<GrowthReport> {
+    type Search = fn(&[Tracked<u64>], Tracked<u64>) -> bool;
+    let searches: [(&str, Search); 6] = [
+        ("linear_search", |list, item| {
+            linear_search(list, item).is_some()
+        }),
+        ("iterative_binary_search", |list, item| {
+            iterative_binary_search(list, item).is_some()
+        }),
+        ("recursive_binary_search", |list, item| {
+            recursive_binary_search(list, item, 0, list.len()).is_some()
+        }),
+        ("interpolation_search", |list, item| {
+            interpolation_search(list, item).is_ok()
+        }),
+        ("exponential_search", |list, item| {
+            exponential_search(list, &item).is_ok()
+        }),
+        ("fibonacci_search", |list, item| {
+            fibonacci_search(list, &item).is_ok()
+        }),
+    ];
+
+    searches
+        .iter()
+        .map(|&(name, search)| {
+            measure_growth(name, sizes, |n, stats| {
+                let list: Vec<u64> = (0..n as u64).map(|i| i * 2).collect();
+                let tracked = stats.track(&list);
+                let items: Vec<u64> = (0..=32)
+                    .map(|i| (i * 2 * n as u64 / 32) | (i & 1))
+                    .collect();
+                for &item in &items {
+                    search(&tracked, stats.item(item));
+                }
+                items.len()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_search_compares_every_element_before_a_match() {
+        let stats = SearchStats::new();
+        let list = stats.track(&(0..10).collect::<Vec<usize>>());
+        assert_eq!(linear_search(&list, stats.item(6)), Some(6));
+        assert_eq!(stats.comparisons(), 7);
+        assert_eq!(stats.probes(), 7);
+
+        stats.reset();
+        assert_eq!(linear_search(&list, stats.item(99)), None);
+        assert_eq!(stats.comparisons(), 10)
+    }
+
+    #[test]
+    fn binary_search_compares_at_most_log_n_times() {
+        let stats = SearchStats::new();
+        let list = stats.track(&(0..1024).collect::<Vec<usize>>());
+        for item in 0..=1024 {
+            stats.reset();
+            iterative_binary_search(&list, stats.item(item));
+            assert!(stats.comparisons() <= 11, "{}", item);
+            assert_eq!(stats.probes(), stats.comparisons());
+        }
+    }
+
+    #[test]
+    fn fit_growth_recognizes_each_run_time() {
+        let sizes = [16, 64, 256, 1024, 4096];
+        for &growth in &Growth::ALL {
+            let samples: Vec<(usize, f64)> = sizes
+                .iter()
+                .map(|&n| (n, 3.0 * growth.shape(n as f64) + 1.0))
+                .collect();
+            assert_eq!(fit_growth(&samples), growth);
+        }
+    }
+
+    #[test]
+    fn search_report_fits_the_expected_run_times() {
+        let reports = search_report(&[16, 128, 1024, 8192]);
+        let growth = |name: &str| {
+            reports
+                .iter()
+                .find(|report| report.name == name)
+                .unwrap()
+                .growth
+        };
+        assert_eq!(growth("linear_search"), Growth::Linear);
+        assert_eq!(growth("iterative_binary_search"), Growth::Logarithmic);
+        assert_eq!(growth("recursive_binary_search"), Growth::Logarithmic);
+        assert_eq!(growth("fibonacci_search"), Growth::Logarithmic);
+        assert_eq!(growth("interpolation_search"), Growth::Constant)
+    }
+}
@@ -128,16 +128,93 @@
 //! Given a sorted list and an item,
 //! if the item is in the list - return the item.
 //!
-//! Initially, we define a low value of 0 and a high value equal to the list's length minus 1.
+//! Initially, we define a low value of 0 and a high value equal to the list's length - one past
+//! the last element, so that the item, if present, always lies at some index `low <= i < high`.
 //!
-//! Then, `while` we haven't found the element `(low < high)`,
-//! we check the middle element "our guess" `((low + high) / 2)` against the following:
+//! Then, `while` there are elements left to check `(low < high)`,
+//! we check the middle element "our guess" `(low + (high - low) / 2)` against the following:
 //!
 //! - If the guess is equal to the item, return the item.
 //! - If the guess is not equal to the item:
-//!       If the guess is greater than the item, reset the high so that `high = mid - 1`.
+//!       If the guess is greater than the item, reset the high so that `high = mid`.
 //!       If the guess is less than the item, reset the low so that `low = mid + 1`.
 //! ```
+//!
+//! Two details here are easy to get wrong. Because `high` is one past the end of the range, the
+//! guess is excluded by `high = mid`, not `high = mid - 1` - which would skip the element below
+//! the guess, and underflow when the guess is at index 0. And the middle is found with
+//! [midpoint](fn.midpoint.html) - the same as `low + (high - low) / 2` - rather than
+//! `(low + high) / 2`, as the sum can overflow for large indexes even when neither index does.
+//!
+//! # Beyond 'Is It There?'
+//!
+//! Binary search can answer more than whether an item is present. The same halving finds where an
+//! item belongs - [lower_bound](fn.lower_bound.html), [upper_bound](fn.upper_bound.html) and
+//! [equal_range](fn.equal_range.html) - and works for any order given by a comparator closure,
+//! through [partition_point](fn.partition_point.html),
+//! [binary_search_by](fn.binary_search_by.html) and
+//! [binary_search_by_key](fn.binary_search_by_key.html). These return insertion points the way
+//! `std` does, ready for keeping a list sorted as items are added.
+//!
+//! # Other Ways to Search a Sorted List
+//!
+//! Binary search is the safe default, but it ignores everything about the data except its order.
+//! [interpolation_search](fn.interpolation_search.html) uses the values themselves to guess where
+//! to look, [exponential_search](fn.exponential_search.html) favours items near the front and
+//! works without knowing the length, and [fibonacci_search](fn.fibonacci_search.html) narrows the
+//! range without division. Each returns the same [SearchResult](type.SearchResult.html).
+//!
+//! # Seeing Big O
+//!
+//! To watch the difference between `O(n)` and `O(log n)` rather than take it on trust, any of
+//! these searches can be run over elements that count their comparisons into a
+//! [SearchStats](struct.SearchStats.html). [search_report](fn.search_report.html) runs every search
+//! over growing lists and reports which run time each one's counts grew like.
+//!
+//! # Searching for an Answer
+//!
+//! A binary search needs no list at all - only a question whose answer, once yes, stays yes.
+//! [search_first_true](fn.search_first_true.html) finds the smallest integer for which such a
+//! predicate holds, and [bisect](fn.bisect.html) narrows in on the same point among the real
+//! numbers, to within a tolerance.
+//!
+//! The list need not even be in memory. [SortedFile](struct.SortedFile.html) binary searches a
+//! file of sorted lines by seeking to byte offsets, reading only the few lines it lands on.
+//!
+//! # Staying Sorted
+//!
+//! Every search above trusts that its list is sorted, and gives wrong answers - not errors - when it
+//! isn't. [SortedVec](struct.SortedVec.html) and [SortedSet](struct.SortedSet.html) take that
+//! burden off the caller: they insert by binary search, so they are sorted by construction, and
+//! build rank and select, range queries, merging and set operations on top.
+
+use std::cmp::Ordering;
+
+mod bounds;
+mod instrumentation;
+mod monotonic;
+mod searches;
+mod sorted_file;
+mod sorted_set;
+mod sorted_vec;
+#[cfg(test)]
+mod test_support;
+
+pub use bounds::{
+    binary_search_by, binary_search_by_key, equal_range, equal_range_by, lower_bound,
+    lower_bound_by, partition_point, upper_bound, upper_bound_by,
+};
+pub use instrumentation::{
+    fit_growth, measure_growth, search_report, Growth, GrowthReport, SearchStats, Tracked,
+};
+pub use monotonic::{bisect, midpoint, search_first_true, Bisection, Integer};
+pub use searches::{
+    exponential_search, exponential_search_by, fibonacci_search, interpolation_search, Numeric,
+    SearchResult,
+};
+pub use sorted_file::{Line, SortedFile};
+pub use sorted_set::SortedSet;
+pub use sorted_vec::SortedVec;
 
 /// A linear search
 ///
@@ -185,30 +262,29 @@ pub fn linear_search<T: Ord + std::fmt::Debug>(list: &[T], item: T) -> Option<us
 /// iterative_binary_search(list, 5)
 /// ```
 pub fn iterative_binary_search<T: Ord + std::fmt::Debug>(list: &[T], item: T) -> Option<usize> {
-    // Setting the upper and lower bounds
+    // Setting the upper and lower bounds - `high` is exclusive, one past the last candidate
     let mut low = 0;
     let mut high = list.len();
 
-    // So long as our low is less than our high, we proceed
+    // So long as there are candidates left between low and high, we proceed
     while low < high {
-        // Calculate a mid point or pivot point within the collection
-        let mid = (low + high) / 2;
+        // Calculate a mid point or pivot point within the collection, without the risk of
+        // `low + high` overflowing
+        let mid = midpoint(low, high);
 
         // Ask if we have found a match for the item within the list, and return if found.
         //
         // If we have found no match, but our item value is less than the mid point,
-        // reset high to one less than our current mid point and repeat operations
-        // from the beginning of the `while` loop.
+        // reset high to our current mid point - excluding it, as high is exclusive - and repeat
+        // operations from the beginning of the `while` loop.
         //
         // If we have found no match, but our item value is greater than the mid point,
         // increase our low to one greater than our current mid point and repeat operations
         // from the beginning of the `while` loop.
-        if item == list[mid] {
-            return Some(mid);
-        } else if item < list[mid] {
-            high = mid - 1;
-        } else {
-            low = mid + 1;
+        match item.cmp(&list[mid]) {
+            Ordering::Equal => return Some(mid),
+            Ordering::Less => high = mid,
+            Ordering::Greater => low = mid + 1,
         }
     }
 
@@ -217,7 +293,8 @@ pub fn iterative_binary_search<T: Ord + std::fmt::Debug>(list: &[T], item: T) ->
 
 /// A recursive binary search
 ///
-/// Searches for the presence of an item within a list - returning a matching index or None.
+/// Searches for the presence of an item within `list[low..high]` - returning a matching index
+/// (into the whole list) or None.
 ///
 /// Should be expected to have performance characteristics of `O(log n)`.
 ///
@@ -225,12 +302,15 @@ pub fn iterative_binary_search<T: Ord + std::fmt::Debug>(list: &[T], item: T) ->
 ///
 /// * `list` - A sorted vector of elements
 /// * `item` - An element to search for within the vector
+/// * `low` - The first index to search
+/// * `high` - One past the last index to search - clamped to the length of the vector, so passing
+///   `list.len()` or more searches to the end
 ///
 /// # Examples
 ///
 /// ```rust
 /// let list: Vec<usize> = (0..10).collect();
-/// recursive_binary_search(list, 5)
+/// recursive_binary_search(list, 5, 0, list.len())
 /// ```
 pub fn recursive_binary_search<T: Ord + std::fmt::Debug>(
     list: &[T],
@@ -238,25 +318,24 @@ pub fn recursive_binary_search<T: Ord + std::fmt::Debug>(
     low: usize,
     high: usize,
 ) -> Option<usize> {
-    let mid = (low + high) / 2;
-
-    if list.is_empty() || mid >= list.len() {
+    // Bounds beyond the end of the list are pulled back to it, and an empty range has nothing
+    // left to search
+    let high = high.min(list.len());
+    if low >= high {
         return None;
-    } else {
-        if item == list[mid] {
-            return Some(mid);
-        }
+    }
 
-        if item < list[mid] {
-            recursive_binary_search(list, item, low, mid - 1)
-        } else {
-            recursive_binary_search(list, item, mid + 1, high)
-        }
+    let mid = midpoint(low, high);
+    match item.cmp(&list[mid]) {
+        Ordering::Equal => Some(mid),
+        Ordering::Less => recursive_binary_search(list, item, low, mid),
+        Ordering::Greater => recursive_binary_search(list, item, mid + 1, high),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::test_support::{small_sorted_lists, XorShift};
     use super::*;
 
     #[test]
@@ -330,4 +409,104 @@ mod tests {
         let v: Vec<usize> = (0..4).collect();
         assert_eq!(recursive_binary_search(&v, 3, 0, v.len()), Some(3));
     }
+
+    #[test]
+    fn iterative_binary_search_does_not_skip_the_element_below_a_guess() {
+        let v: Vec<usize> = (0..5).collect();
+        assert_eq!(iterative_binary_search(&v, 1), Some(1))
+    }
+
+    #[test]
+    fn iterative_binary_search_does_not_underflow_below_the_first_element() {
+        let v = vec![5];
+        assert_eq!(iterative_binary_search(&v, 1), None)
+    }
+
+    #[test]
+    fn recursive_binary_search_does_not_underflow_below_the_first_element() {
+        let v = vec![5, 6];
+        assert_eq!(recursive_binary_search(&v, 1, 0, v.len()), None)
+    }
+
+    #[test]
+    fn recursive_binary_search_clamps_out_of_range_bounds() {
+        let v: Vec<usize> = (0..4).collect();
+        assert_eq!(recursive_binary_search(&v, 3, 0, usize::MAX), Some(3));
+        assert_eq!(recursive_binary_search(&v, 3, 9, 12), None);
+        assert_eq!(recursive_binary_search(&v, 1, 3, 1), None)
+    }
+
+    #[test]
+    fn recursive_binary_search_only_searches_within_its_bounds() {
+        let v: Vec<usize> = (0..8).collect();
+        assert_eq!(recursive_binary_search(&v, 2, 3, 8), None);
+        assert_eq!(recursive_binary_search(&v, 5, 3, 6), Some(5))
+    }
+
+    // Agrees with `linear_search` on whether the item is present - when duplicated, any of its
+    // indexes will do
+    fn assert_agrees_with_linear_search(list: &[u32], item: u32, found: Option<usize>) {
+        match linear_search(list, item) {
+            Some(_) => assert_eq!(found.map(|i| list[i]), Some(item), "{:?} {}", list, item),
+            None => assert_eq!(found, None, "{:?} {}", list, item),
+        }
+    }
+
+    #[test]
+    fn iterative_binary_search_agrees_with_linear_search_on_all_small_inputs() {
+        for list in small_sorted_lists() {
+            for item in 0..=5 {
+                let found = iterative_binary_search(&list, item);
+                assert_agrees_with_linear_search(&list, item, found);
+            }
+        }
+    }
+
+    #[test]
+    fn recursive_binary_search_agrees_with_linear_search_on_all_small_inputs_and_bounds() {
+        for list in small_sorted_lists() {
+            for item in 0..=5 {
+                for low in 0..=list.len() + 1 {
+                    for high in 0..=list.len() + 1 {
+                        let found = recursive_binary_search(&list, item, low, high);
+                        let start = low.min(list.len());
+                        let range = &list[start..high.min(list.len()).max(start)];
+                        let found_in_range = found.map(|i| {
+                            assert!(
+                                i >= start && i < high,
+                                "{:?} {} {}..{}",
+                                list,
+                                item,
+                                low,
+                                high
+                            );
+                            i - start
+                        });
+                        assert_agrees_with_linear_search(range, item, found_in_range);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn binary_searches_agree_with_linear_search_on_random_inputs() {
+        let mut rng = XorShift::new(0x2545_f491_4f6c_dd1d);
+        let mut next = move |bound: u64| rng.below(bound) as u32;
+
+        for _ in 0..300 {
+            let len = next(1000) as usize;
+            let spread = u64::from(next(2000)) + 1;
+            let mut list: Vec<u32> = (0..len).map(|_| next(spread)).collect();
+            list.sort_unstable();
+
+            for _ in 0..20 {
+                let item = next(spread + 2);
+                let found = iterative_binary_search(&list, item);
+                assert_agrees_with_linear_search(&list, item, found);
+                let found = recursive_binary_search(&list, item, 0, list.len());
+                assert_agrees_with_linear_search(&list, item, found);
+            }
+        }
+    }
 }
@@ -0,0 +1,286 @@
+//! Searching Monotonic Predicates
+//!
+//! `monotonic` binary searches a question rather than a list.
+//!
+//! Plenty of problems ask for the smallest value that works - the smallest truck that fits every
+//! parcel, the fewest servers that handle the load. If 'works' never stops being true once it
+//! starts, the question is *monotonic*, and its answers line up like a sorted list of booleans:
+//!
+//! ```text
+//!  capacity:    1  2  3  4  5  6  7  8  9  10
+//!  fits?        F  F  F  F  F  T  T  T  T  T
+//!                              ^ the answer
+//! ```
+//!
+//! So binary search applies unchanged: check the middle value, and keep the half where the answer
+//! must lie. [search_first_true](fn.search_first_true.html) does this over any range of integers,
+//! and [bisect](fn.bisect.html) over real numbers, narrowing an interval until it is within a
+//! tolerance.
+//!
+//! Finding the middle is where binary searches most often go wrong. `(low + high) / 2` overflows
+//! once the sum passes the largest integer - even though the middle itself never could - so every
+//! search in this module finds it with [midpoint](fn.midpoint.html) instead.
+
+/// An integer type which can be binary searched
+pub trait Integer: Copy + Ord {
+    /// Returns the middle of `low` and `high`, rounded down, without overflowing
+    fn midpoint(low: Self, high: Self) -> Self;
+
+    /// Returns the next integer up
+    fn successor(self) -> Self;
+}
+
+macro_rules! integer {
+    ($($int:ty),*) => {
+        $(
+            impl Integer for $int {
+                fn midpoint(low: Self, high: Self) -> Self {
+                    // The bits both share, plus half the bits only one has - never more than
+                    // either, so never overflowing
+                    (low & high) + ((low ^ high) >> 1)
+                }
+
+                fn successor(self) -> Self {
+                    self + 1
+                }
+            }
+        )*
+    };
+}
+
+integer!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// An overflow-safe midpoint
+///
+/// Returns the middle of `low` and `high`, rounded down - for any two integers, however large.
+///
+/// # Arguments
+///
+/// * `low` - The lower end of the range
+/// * `high` - The upper end of the range
+///
+/// # Examples
+///
+/// ```rust
+/// assert_eq!(midpoint(3, 8), 5);
+/// assert_eq!(midpoint(usize::MAX - 2, usize::MAX), usize::MAX - 1);
+/// assert_eq!(midpoint(i64::MIN, i64::MAX), -1);
+/// ```
+pub fn midpoint<T: Integer>(low: T, high: T) -> T {
+    T::midpoint(low, high)
+}
+
+/// A binary search over a range of integers
+///
+/// Returns the smallest integer in `low..=high` for which `pred` is true, or `None` if there is
+/// none.
+///
+/// Should be expected to have performance characteristics of `O(log n)` calls to `pred`, where `n`
+/// is the size of the range.
+///
+/// # Arguments
+///
+/// * `low` - The smallest integer to consider
+/// * `high` - The largest integer to consider
+/// * `pred` - A predicate which, once true, stays true for every larger integer
+///
+/// # Examples
+///
+/// ```rust
+/// // The smallest truck capacity carrying these parcels in at most 3 trips
+/// let parcels = [3, 2, 2, 4, 1, 4];
+/// let trips = |capacity: u32| {
+///     let (mut trips, mut load) = (1, 0);
+///     for &parcel in &parcels {
+///         if load + parcel > capacity {
+///             trips += 1;
+///             load = 0;
+///         }
+///         load += parcel;
+///     }
+///     trips
+/// };
+/// assert_eq!(search_first_true(4, 16, |capacity| trips(capacity) <= 3), Some(6));
+/// ```
+pub fn search_first_true<T, P>(mut low: T, mut high: T, mut pred: P) -> Option<T>
+where
+    T: Integer,
+    P: FnMut(T) -> bool,
+{
+    if low > high {
+        return None;
+    }
+
+    // The answer, if there is one, always lies within low..=high
+    while low < high {
+        let mid = midpoint(low, high);
+        if pred(mid) {
+            high = mid;
+        } else {
+            // `mid` is below `high`, so this cannot overflow
+            low = mid.successor();
+        }
+    }
+
+    if pred(low) {
+        Some(low)
+    } else {
+        None
+    }
+}
+
+/// The result of a [bisect](fn.bisect.html) - an interval around where a predicate turns true
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bisection {
+    /// The lower end of the final interval, where the predicate was last seen false
+    pub low: f64,
+    /// The upper end of the final interval, where the predicate was last seen true
+    pub high: f64,
+    /// The number of times the predicate was called
+    pub iterations: u32,
+    /// `true` if the interval narrowed to within the tolerance, `false` if the iteration cap was
+    /// reached first
+    pub converged: bool,
+}
+
+impl Bisection {
+    /// Returns the middle of the final interval - the best estimate of the answer
+    pub fn estimate(&self) -> f64 {
+        self.low / 2.0 + self.high / 2.0
+    }
+}
+
+/// A bisection search over the real numbers
+///
+/// Narrows `low..=high` around the point where `pred` turns from false to true, halving the
+/// interval until it is no wider than `tolerance`, or `max_iterations` calls to `pred` have been
+/// made, or it can be halved no further in floating point.
+///
+/// Should be expected to have performance characteristics of `O(log((high - low) / tolerance))`
+/// calls to `pred`.
+///
+/// # Arguments
+///
+/// * `low` - The lower end of the interval
+/// * `high` - The upper end of the interval
+/// * `tolerance` - The width at which to stop
+/// * `max_iterations` - The most calls to `pred` to make
+/// * `pred` - A predicate which, once true, stays true for every larger number
+///
+/// # Examples
+///
+/// ```rust
+/// let root = bisect(0.0, 2.0, 1e-9, 100, |x| x * x >= 2.0);
+/// assert!(root.converged);
+/// assert!((root.estimate() - 2f64.sqrt()).abs() < 1e-9);
+/// ```
+///
+/// # Panics
+///
+/// Panics if either end is not finite, `low` is greater than `high`, or `tolerance` is negative
+/// or NaN.
+pub fn bisect<P>(
+    mut low: f64,
+    mut high: f64,
+    tolerance: f64,
+    max_iterations: u32,
+    mut pred: P,
+) -> Bisection
+where
+    P: FnMut(f64) -> bool,
+{
+    assert!(
+        low.is_finite() && high.is_finite() && low <= high,
+        "low and high must be finite, with low <= high"
+    );
+    assert!(tolerance >= 0.0, "tolerance must not be negative");
+
+    let mut iterations = 0;
+    loop {
+        // Halving each end before adding stays finite, even where `low + high` or `high - low`
+        // would not
+        let mid = low / 2.0 + high / 2.0;
+        let converged = high - low <= tolerance || mid <= low || mid >= high;
+        if converged || iterations == max_iterations {
+            return Bisection {
+                low,
+                high,
+                iterations,
+                converged,
+            };
+        }
+
+        iterations += 1;
+        if pred(mid) {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn midpoint_does_not_overflow_at_the_extremes() {
+        assert_eq!(midpoint(u8::MAX, u8::MAX), u8::MAX);
+        assert_eq!(midpoint(u64::MAX - 1, u64::MAX), u64::MAX - 1);
+        assert_eq!(midpoint(i32::MIN, i32::MIN + 1), i32::MIN);
+        assert_eq!(midpoint(-7, -2), -5);
+        for low in -20i32..20 {
+            for high in low..20 {
+                assert_eq!(midpoint(low, high), (low + high).div_euclid(2));
+            }
+        }
+    }
+
+    #[test]
+    fn search_first_true_matches_a_linear_scan() {
+        for low in 0u32..12 {
+            for high in 0u32..12 {
+                for threshold in 0u32..14 {
+                    let expected = (low..=high).find(|x| *x >= threshold);
+                    assert_eq!(search_first_true(low, high, |x| x >= threshold), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn search_first_true_covers_whole_integer_types() {
+        assert_eq!(search_first_true(i64::MIN, i64::MAX, |x| x >= 0), Some(0));
+        assert_eq!(
+            search_first_true(0, u64::MAX, |x| x == u64::MAX),
+            Some(u64::MAX)
+        );
+        assert_eq!(search_first_true(i8::MIN, i8::MAX, |_| false), None)
+    }
+
+    #[test]
+    fn search_first_true_calls_pred_logarithmically() {
+        let mut calls = 0;
+        search_first_true(0u64, u64::MAX, |x| {
+            calls += 1;
+            x >= 12345
+        });
+        assert!(calls <= 65, "{}", calls)
+    }
+
+    #[test]
+    fn bisect_stops_at_the_iteration_cap() {
+        let capped = bisect(0.0, 1024.0, 1e-12, 3, |x| x >= 100.0);
+        assert!(!capped.converged);
+        assert_eq!(capped.iterations, 3);
+        assert_eq!((capped.low, capped.high), (0.0, 128.0))
+    }
+
+    #[test]
+    fn bisect_stops_when_floats_can_halve_no_further() {
+        let exact = bisect(f64::MIN, f64::MAX, 0.0, 10_000, |x| x >= 1.5);
+        assert!(exact.converged);
+        assert!(exact.low < 1.5 && exact.high >= 1.5);
+        assert!(exact.iterations < 10_000)
+    }
+}
@@ -0,0 +1,434 @@
+//! More Searches
+//!
+//! `searches` adds three searches which, like binary search, need a sorted list - but choose
+//! where to look next differently, to do better than binary search on particular shapes of data.
+//!
+//! **Interpolation search** guesses where the item should be from its value, the way we open a
+//! phone book near the back for 'Young'. On evenly spread numeric keys the first guess lands close,
+//! and each guess shrinks the range to roughly its square root - `O(log log n)` probes. On skewed
+//! keys the guesses can creep forward one element at a time, falling back to `O(n)`.
+//!
+//! ```text
+//!  item 70,  list [ 10, 20, 30, 40, 50, 60, 70, 80, 90, 100 ]
+//!  guess = 0 + (70 - 10) / (100 - 10) * 9 = 6    --> found on the first probe
+//! ```
+//!
+//! **Exponential search** (or galloping) checks indexes `1, 2, 4, 8, ...` until it passes the
+//! item, then binary searches the last gap. It takes `O(log i)` probes for an item at index `i`, so
+//! it wins when items are near the front, and it never needs the length - it can search a sequence
+//! which is unbounded, or too large to measure.
+//!
+//! ```text
+//!  probes:  1   2     4           8                     16
+//!          [.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.|.]
+//!                      |-----------|  item lies here - binary search 5..=8
+//! ```
+//!
+//! **Fibonacci search** splits the range at Fibonacci numbers rather than halves, so each step
+//! needs only addition and subtraction to find the next split - a saving on machines where division
+//! was slow - and its probes cluster closer together than binary search's, which suits storage
+//! where nearby reads are cheaper.
+//!
+//! All three return a [SearchResult](type.SearchResult.html), just like
+//! [binary_search_by](fn.binary_search_by.html).
+
+use std::cmp::Ordering;
+
+use super::monotonic::midpoint;
+
+/// The outcome of a search - `Ok` with the index of a matching element, or `Err` with the index at
+/// which the item could be inserted, keeping the list sorted
+pub type SearchResult = Result<usize, usize>;
+
+/// A numeric key whose position between two others can be estimated
+pub trait Numeric: Ord + Copy {
+    /// Returns the key as a float, for interpolating between keys
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! numeric {
+    ($($int:ty),*) => {
+        $(
+            impl Numeric for $int {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+numeric!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// An interpolation search
+///
+/// Searches for the presence of an item within a list, guessing its position from its value.
+///
+/// Should be expected to have performance characteristics of `O(log log n)` for evenly spread
+/// keys, and `O(n)` in the worst case.
+///
+/// # Arguments
+///
+/// * `list` - A sorted vector of numeric elements
+/// * `item` - An element to search for within the vector
+///
+/// # Examples
+///
+/// ```rust
+/// let list: Vec<u32> = (0..100).map(|i| i * 10).collect();
+/// assert_eq!(interpolation_search(&list, 420), Ok(42));
+/// assert_eq!(interpolation_search(&list, 425), Err(43));
+/// ```
+pub fn interpolation_search<T: Numeric>(list: &[T], item: T) -> SearchResult {
+    // As in the binary searches, `high` is exclusive
+    let mut low = 0;
+    let mut high = list.len();
+
+    while low < high {
+        let (first, last) = (list[low], list[high - 1]);
+        if item < first {
+            return Err(low);
+        }
+        if item > last {
+            return Err(high);
+        }
+
+        // The item lies between `first` and `last`, so guess it lies as far between their indexes
+        // - clamping, as rounding can carry the guess past the end
+        let guess = if first == last {
+            low
+        } else {
+            let fraction = (item.to_f64() - first.to_f64()) / (last.to_f64() - first.to_f64());
+            (low + (fraction * (high - 1 - low) as f64) as usize).min(high - 1)
+        };
+
+        match item.cmp(&list[guess]) {
+            Ordering::Equal => return Ok(guess),
+            Ordering::Less => high = guess,
+            Ordering::Greater => low = guess + 1,
+        }
+    }
+
+    Err(low)
+}
+
+/// An exponential search
+///
+/// Searches for the presence of an item within a list by galloping forward from the start, then
+/// binary searching the final gap.
+///
+/// Should be expected to have performance characteristics of `O(log i)`, where `i` is the index the
+/// item is found at or belongs at.
+///
+/// # Arguments
+///
+/// * `list` - A sorted vector of elements
+/// * `item` - An element to search for within the vector
+///
+/// # Examples
+///
+/// ```rust
+/// let list: Vec<usize> = (0..1000).collect();
+/// assert_eq!(exponential_search(&list, &3), Ok(3));
+/// ```
+pub fn exponential_search<T: Ord>(list: &[T], item: &T) -> SearchResult {
+    exponential_search_by(|i| list.get(i).map(|x| x.cmp(item)))
+}
+
+/// An exponential search of a sequence of unknown length
+///
+/// Searches a sorted sequence through `compare`, which orders the element at an index against the
+/// searched-for item - or returns `None` if the index is past the end of the sequence. The sequence
+/// may be unbounded, so long as the item would be found (or passed) at some index.
+///
+/// Should be expected to have performance characteristics of `O(log i)`, where `i` is the index the
+/// item is found at or belongs at.
+///
+/// # Arguments
+///
+/// * `compare` - Orders the element at an index against the searched-for item, or returns `None`
+///   past the end of the sequence
+///
+/// # Examples
+///
+/// ```rust
+/// // The first square of at least 1,000,000, without a list of squares
+/// let result = exponential_search_by(|i| Some((i * i).cmp(&1_000_000)));
+/// assert_eq!(result, Ok(1000));
+/// ```
+pub fn exponential_search_by<F>(mut compare: F) -> SearchResult
+where
+    F: FnMut(usize) -> Option<Ordering>,
+{
+    let mut is_less = |i| compare(i) == Some(Ordering::Less);
+
+    // Gallop until an index is not less than the item - every index up to `low` is less
+    let (mut low, mut high) = (0, 0);
+    if is_less(0) {
+        high = 1;
+        while is_less(high) {
+            low = high;
+            high = match high.checked_mul(2) {
+                Some(doubled) => doubled,
+                None => usize::MAX,
+            };
+            if low == high {
+                return Err(usize::MAX);
+            }
+        }
+        low += 1;
+    }
+
+    // Then binary search for the first index which is not less, within low..=high
+    while low < high {
+        let mid = midpoint(low, high);
+        if is_less(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    match compare(low) {
+        Some(Ordering::Equal) => Ok(low),
+        _ => Err(low),
+    }
+}
+
+/// A Fibonacci search
+///
+/// Searches for the presence of an item within a list, splitting the range at Fibonacci numbers.
+///
+/// Should be expected to have performance characteristics of `O(log n)`.
+///
+/// # Arguments
+///
+/// * `list` - A sorted vector of elements
+/// * `item` - An element to search for within the vector
+///
+/// # Examples
+///
+/// ```rust
+/// let list: Vec<usize> = (0..100).collect();
+/// assert_eq!(fibonacci_search(&list, &61), Ok(61));
+/// ```
+pub fn fibonacci_search<T: Ord>(list: &[T], item: &T) -> SearchResult {
+    // The smallest Fibonacci number `fib` no less than the length, and the two before it
+    let (mut smaller, mut small, mut fib) = (0, 1, 1);
+    while fib < list.len() {
+        smaller = small;
+        small = fib;
+        fib = small + smaller;
+    }
+
+    // Every element before `offset` is less than the item
+    let mut offset = 0;
+    while fib > 1 {
+        let i = (offset + smaller).min(list.len()) - 1;
+        match list[i].cmp(item) {
+            Ordering::Equal => return Ok(i),
+            // Drop everything up to the probe - the range shrinks by one Fibonacci number
+            Ordering::Less => {
+                fib = small;
+                small = smaller;
+                smaller = fib - small;
+                offset = i + 1;
+            }
+            // Drop everything after the probe - the range shrinks by two
+            Ordering::Greater => {
+                fib = smaller;
+                small -= smaller;
+                smaller = fib - small;
+            }
+        }
+    }
+
+    // At most one candidate is left, at `offset`
+    match list.get(offset).map(|x| x.cmp(item)) {
+        Some(Ordering::Equal) => Ok(offset),
+        Some(Ordering::Less) => Err(offset + 1),
+        _ => Err(offset),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::small_sorted_lists;
+    use super::*;
+
+    // Matches an element equal to the item, or the insertion point `std` would give
+    fn assert_valid(list: &[u32], item: u32, result: SearchResult) {
+        match list.binary_search(&item) {
+            Ok(_) => assert_eq!(result.map(|i| list[i]), Ok(item), "{:?} {}", list, item),
+            Err(index) => assert_eq!(result, Err(index), "{:?} {}", list, item),
+        }
+    }
+
+    #[test]
+    fn searches_agree_with_std_on_all_small_inputs() {
+        for list in small_sorted_lists() {
+            for item in 0..=5 {
+                assert_valid(&list, item, interpolation_search(&list, item));
+                assert_valid(&list, item, exponential_search(&list, &item));
+                assert_valid(&list, item, fibonacci_search(&list, &item));
+            }
+        }
+    }
+
+    #[test]
+    fn searches_agree_with_std_on_every_length_up_to_200() {
+        for len in 0..200u32 {
+            let list: Vec<u32> = (0..len).map(|i| i * 2 + 1).collect();
+            for item in 0..=len * 2 + 1 {
+                assert_valid(&list, item, interpolation_search(&list, item));
+                assert_valid(&list, item, exponential_search(&list, &item));
+                assert_valid(&list, item, fibonacci_search(&list, &item));
+            }
+        }
+    }
+
+    #[test]
+    fn searches_agree_with_std_on_skewed_keys() {
+        let list: Vec<u32> = (0..2000u32).map(|i| i * i / 7).collect();
+        for item in 0..=list[list.len() - 1] + 1 {
+            if item % 97 == 0 || item < 100 {
+                assert_valid(&list, item, interpolation_search(&list, item));
+                assert_valid(&list, item, exponential_search(&list, &item));
+                assert_valid(&list, item, fibonacci_search(&list, &item));
+            }
+        }
+    }
+
+    #[test]
+    fn interpolation_search_handles_extreme_keys() {
+        let list = vec![i64::MIN, -1, 0, i64::MAX];
+        assert_eq!(interpolation_search(&list, i64::MAX), Ok(3));
+        assert_eq!(interpolation_search(&list, i64::MIN), Ok(0));
+        assert_eq!(interpolation_search(&list, 5), Err(3))
+    }
+
+    #[test]
+    fn exponential_search_by_searches_unbounded_sequences() {
+        assert_eq!(exponential_search_by(|i| Some(i.cmp(&12345))), Ok(12345));
+        assert_eq!(
+            exponential_search_by(|i| Some((i * 2).cmp(&12345))),
+            Err(6173)
+        );
+        assert_eq!(
+            exponential_search_by(|_| Some(Ordering::Less)),
+            Err(usize::MAX)
+        )
+    }
+}
+
+/// Timings of the searches against `iterative_binary_search`, run with
+/// `cargo test --release -- --ignored --nocapture`
+#[cfg(test)]
+mod benches {
+    use super::super::iterative_binary_search;
+    use super::super::test_support::XorShift;
+    use super::*;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    const SIZES: [usize; 6] = [16, 256, 4096, 65_536, 1 << 20, 1 << 22];
+    const LOOKUPS: usize = 100_000;
+
+    // Nanoseconds per lookup of each item in turn, repeated up to `LOOKUPS` lookups
+    fn time<F: FnMut(u64) -> bool>(items: &[u64], mut search: F) -> f64 {
+        let start = Instant::now();
+        for i in 0..LOOKUPS {
+            black_box(search(items[i % items.len()]));
+        }
+        start.elapsed().as_nanos() as f64 / LOOKUPS as f64
+    }
+
+    // A deterministic spread of lookups over a list's values, hits and misses alike
+    fn lookups(list: &[u64], near_front: bool) -> Vec<u64> {
+        let mut rng = XorShift::new(0x9e37_79b9_7f4a_7c15);
+        (0..1024)
+            .map(|_| {
+                let state = rng.next_u64();
+                let window = if near_front {
+                    list.len().min(64)
+                } else {
+                    list.len()
+                };
+                list[(state % window as u64) as usize] + state % 2
+            })
+            .collect()
+    }
+
+    // Prints one row per size, and the size from which `challenger` beat binary search every time
+    fn compare<F>(shape: &str, make_list: fn(usize) -> Vec<u64>, near_front: bool, challenger: F)
+    where
+        F: Fn(&[u64], u64) -> bool,
+    {
+        println!("\n{}", shape);
+        println!("{:>10} {:>12} {:>12}", "n", "binary ns", "other ns");
+        let mut crossover = None;
+        for &n in &SIZES {
+            let list = make_list(n);
+            let items = lookups(&list, near_front);
+            let binary = time(&items, |item| {
+                iterative_binary_search(&list, item).is_some()
+            });
+            let other = time(&items, |item| challenger(&list, item));
+            println!("{:>10} {:>12.1} {:>12.1}", n, binary, other);
+            crossover = if other < binary {
+                crossover.or(Some(n))
+            } else {
+                None
+            };
+        }
+        match crossover {
+            Some(n) => println!("faster than binary search from n = {}", n),
+            None => println!("never faster than binary search"),
+        }
+    }
+
+    fn uniform(n: usize) -> Vec<u64> {
+        (0..n as u64).map(|i| i * 3).collect()
+    }
+
+    // Squares rather than cubes, which would overflow a `u64` past n = 2^21
+    fn skewed(n: usize) -> Vec<u64> {
+        (0..n as u64).map(|i| i * i).collect()
+    }
+
+    #[test]
+    #[ignore]
+    fn interpolation_search_on_uniform_and_skewed_keys() {
+        compare(
+            "interpolation, uniform keys",
+            uniform,
+            false,
+            |list, item| interpolation_search(list, item).is_ok(),
+        );
+        compare(
+            "interpolation, quadratic keys",
+            skewed,
+            false,
+            |list, item| interpolation_search(list, item).is_ok(),
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn exponential_search_near_the_front_and_throughout() {
+        compare("exponential, first 64", uniform, true, |list, item| {
+            exponential_search(list, &item).is_ok()
+        });
+        compare("exponential, anywhere", uniform, false, |list, item| {
+            exponential_search(list, &item).is_ok()
+        });
+    }
+
+    #[test]
+    #[ignore]
+    fn fibonacci_search_throughout() {
+        compare("fibonacci, anywhere", uniform, false, |list, item| {
+            fibonacci_search(list, &item).is_ok()
+        });
+    }
+}
@@ -0,0 +1,318 @@
+//! Searching Sorted Files
+//!
+//! `sorted_file` binary searches a file of sorted lines without reading it all into memory.
+//!
+//! A `Vec` lets us jump straight to its middle element, but a file of lines has no index - only
+//! bytes. We can still jump to the middle *byte*, though, and from there skip ahead to the start of
+//! the next line:
+//!
+//! ```text
+//!  offset  line
+//!       0  Adit,5551234
+//!      13  John,5550000
+//!      26  Maggie,5559876   <-- byte 27, the middle of the file, lands part way through a line
+//!      41  Rama,5557777     <-- so skip past the next newline, and read this line instead
+//! ```
+//!
+//! Whichever line we land on, its key tells us which half of the file to keep, just as the middle
+//! element of a list does - so a file of `n` bytes takes `O(log n)` seeks, and only the lines
+//! landed on are ever read. Lines are found by the byte they start at, so the searches return that
+//! offset alongside the line.
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::monotonic::search_first_true;
+
+/// A line of a sorted file, and the byte offset it starts at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// The offset of the line's first byte
+    pub offset: u64,
+    /// The line, without its line ending
+    pub text: String,
+}
+
+/// A binary searcher over a file of sorted lines
+///
+/// Lines end with `\n` or `\r\n`, and must be sorted by whatever key they are searched by.
+///
+/// Should be expected to have performance characteristics of `O(log n)` seeks and line reads,
+/// where `n` is the size of the file in bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut phone_book = SortedFile::open("phone_book.csv")?;
+/// let maggie = phone_book.search_by(|line| line.split(',').next().unwrap().cmp("Maggie"))?;
+/// assert_eq!(maggie.unwrap().text, "Maggie,5559876");
+/// ```
+#[derive(Debug)]
+pub struct SortedFile<R> {
+    reader: BufReader<R>,
+    len: u64,
+}
+
+impl SortedFile<File> {
+    /// Opens a file of sorted lines
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> SortedFile<R> {
+    /// Searches sorted lines read from any seekable source
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        Ok(SortedFile {
+            reader: BufReader::new(reader),
+            len,
+        })
+    }
+
+    /// Returns the size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the file is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Searches for a line equal to `item` - returning the first matching line, or None
+    pub fn search(&mut self, item: &str) -> io::Result<Option<Line>> {
+        self.search_by(|line| line.cmp(item))
+    }
+
+    /// Searches for a line whose key equals `key` - returning the first matching line, or None
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to search for
+    /// * `key_of` - Extracts the key of a line, by which the file is sorted
+    pub fn search_by_key<K, F>(&mut self, key: &K, mut key_of: F) -> io::Result<Option<Line>>
+    where
+        K: Ord,
+        F: FnMut(&str) -> K,
+    {
+        self.search_by(|line| key_of(line).cmp(key))
+    }
+
+    /// Searches for a line ordered `Equal` by `compare` - returning the first matching line, or
+    /// None
+    ///
+    /// # Arguments
+    ///
+    /// * `compare` - Orders a line against the searched-for item, consistently with the file's
+    ///   order
+    pub fn search_by<F>(&mut self, mut compare: F) -> io::Result<Option<Line>>
+    where
+        F: FnMut(&str) -> Ordering,
+    {
+        match self.lower_bound_line(&mut compare)? {
+            Some(line) if compare(&line.text) == Ordering::Equal => Ok(Some(line)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the offset of the first line not ordered `Less` by `compare` - or the length of the
+    /// file if there is none
+    ///
+    /// Like [lower_bound_by](fn.lower_bound_by.html), this is where a line would be inserted to
+    /// keep the file sorted.
+    pub fn lower_bound_by<F>(&mut self, compare: F) -> io::Result<u64>
+    where
+        F: FnMut(&str) -> Ordering,
+    {
+        let line = self.lower_bound_line(compare)?;
+        Ok(line.map_or(self.len, |line| line.offset))
+    }
+
+    /// Reads the first line not ordered `Less` by `compare` - or None if there is none
+    fn lower_bound_line<F>(&mut self, mut compare: F) -> io::Result<Option<Line>>
+    where
+        F: FnMut(&str) -> Ordering,
+    {
+        // Whether the first line starting at or after an offset is less than the item only ever
+        // goes from true to false as the offset grows - so the offsets can be binary searched
+        // like any other monotonic predicate. The line read at the smallest offset found true so
+        // far is kept, so the answer's line never needs reading twice
+        let mut error = None;
+        let mut first: Option<(u64, Option<Line>)> = None;
+        search_first_true(0, self.len, |offset| {
+            if error.is_some() {
+                return true;
+            }
+            let line = match self.next_line(offset) {
+                Ok(line) => line,
+                Err(e) => {
+                    error = Some(e);
+                    return true;
+                }
+            };
+            let found = line
+                .as_ref()
+                .is_none_or(|line| compare(&line.text) != Ordering::Less);
+            if found
+                && first
+                    .as_ref()
+                    .is_none_or(|(smallest, _)| offset < *smallest)
+            {
+                first = Some((offset, line));
+            }
+            found
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        // The offset at the end of the file is always found true, so `first` is always set
+        Ok(first.and_then(|(_, line)| line))
+    }
+
+    /// Reads the line starting at `offset` - or None at the end of the file
+    ///
+    /// The offset should be the start of a line, as returned in a [Line](struct.Line.html) or by
+    /// [lower_bound_by](struct.SortedFile.html#method.lower_bound_by).
+    pub fn line_at(&mut self, offset: u64) -> io::Result<Option<Line>> {
+        if offset >= self.len {
+            return Ok(None);
+        }
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut bytes = Vec::new();
+        self.reader.read_until(b'\n', &mut bytes)?;
+
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+        }
+        let text =
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some(Line { offset, text }))
+    }
+
+    /// Reads the first line starting at or after `offset`, resynchronizing to a line boundary by
+    /// skipping past the next newline
+    fn next_line(&mut self, offset: u64) -> io::Result<Option<Line>> {
+        if offset == 0 {
+            return self.line_at(0);
+        }
+
+        // The byte before the offset is the end of the previous line if it is a newline - so
+        // skipping from there never misses a line starting exactly at the offset
+        self.reader.seek(SeekFrom::Start(offset - 1))?;
+        let skipped = self.reader.skip_until(b'\n')? as u64;
+        self.line_at(offset - 1 + skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::iterative_binary_search;
+    use super::*;
+    use std::io::Cursor;
+
+    fn sorted(lines: &[&str]) -> SortedFile<Cursor<Vec<u8>>> {
+        let mut text = lines.join("\n");
+        text.push('\n');
+        SortedFile::new(Cursor::new(text.into_bytes())).unwrap()
+    }
+
+    #[test]
+    fn search_finds_lines_and_their_offsets() {
+        let mut file = sorted(&["Adit", "John", "Maggie", "Rama"]);
+        assert_eq!(
+            file.search("Maggie").unwrap(),
+            Some(Line {
+                offset: 10,
+                text: "Maggie".to_string()
+            })
+        );
+        assert_eq!(file.search("Adit").unwrap().unwrap().offset, 0);
+        assert_eq!(file.search("Rama").unwrap().unwrap().offset, 17);
+        assert_eq!(file.search("Bob").unwrap(), None);
+        assert_eq!(file.search("Zed").unwrap(), None)
+    }
+
+    #[test]
+    fn search_agrees_with_iterative_binary_search() {
+        let numbers: Vec<String> = (0..300).map(|i| format!("{:05}", i * 3)).collect();
+        let lines: Vec<&str> = numbers.iter().map(String::as_str).collect();
+        let mut file = sorted(&lines);
+        for i in 0..=900 {
+            let item = format!("{:05}", i);
+            let found = file.search(&item).unwrap().map(|line| line.text);
+            let expected = iterative_binary_search(&lines, item.as_str()).map(|i| lines[i]);
+            assert_eq!(found.as_deref(), expected);
+        }
+    }
+
+    #[test]
+    fn search_by_key_uses_a_field_of_each_line() {
+        let mut file = sorted(&["1949,1984", "1953,Fahrenheit 451", "1985,Watchmen"]);
+        let year = |line: &str| line[..4].parse::<u32>().unwrap();
+        assert_eq!(
+            file.search_by_key(&1953, year).unwrap().unwrap().text,
+            "1953,Fahrenheit 451"
+        );
+        assert_eq!(file.search_by_key(&1960, year).unwrap(), None)
+    }
+
+    #[test]
+    fn duplicates_and_lower_bounds_find_the_first_line() {
+        let mut file = sorted(&["a", "b", "b", "b", "c"]);
+        assert_eq!(file.search("b").unwrap().unwrap().offset, 2);
+        assert_eq!(file.lower_bound_by(|line| line.cmp("bb")).unwrap(), 8);
+        assert_eq!(
+            file.lower_bound_by(|line| line.cmp("z")).unwrap(),
+            file.len()
+        )
+    }
+
+    #[test]
+    fn line_endings_and_a_missing_final_newline_are_handled() {
+        let text = b"apple\r\nbanana\r\ncherry".to_vec();
+        let mut file = SortedFile::new(Cursor::new(text)).unwrap();
+        assert_eq!(file.search("banana").unwrap().unwrap().offset, 7);
+        assert_eq!(file.search("cherry").unwrap().unwrap().offset, 15);
+
+        let mut empty = SortedFile::new(Cursor::new(Vec::new())).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.search("apple").unwrap(), None)
+    }
+
+    #[test]
+    fn only_one_line_ending_is_stripped() {
+        let text = b"a\r\r\n\n\rb\n".to_vec();
+        let mut file = SortedFile::new(Cursor::new(text)).unwrap();
+        assert_eq!(file.line_at(0).unwrap().unwrap().text, "a\r");
+        assert_eq!(file.line_at(4).unwrap().unwrap().text, "");
+        assert_eq!(file.line_at(5).unwrap().unwrap().text, "\rb")
+    }
+
+    // Removes the file at its path when dropped, even if the test panics first
+    struct TempFile(std::path::PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn searches_files_on_disk() {
+        let temp =
+            TempFile(std::env::temp_dir().join(format!("sorted-file-{}", std::process::id())));
+        let lines: Vec<String> = (0..10_000).map(|i| format!("key{:06},value", i)).collect();
+        std::fs::write(&temp.0, lines.join("\n")).unwrap();
+
+        let mut file = SortedFile::open(&temp.0).unwrap();
+        let found = file.search_by(|line| line[..9].cmp("key004242")).unwrap();
+        assert_eq!(found.unwrap().text, "key004242,value")
+    }
+}
@@ -0,0 +1,328 @@
+//! Sorted Sets
+//!
+//! `sorted_set` provides [SortedSet](struct.SortedSet.html) - a [SortedVec](struct.SortedVec.html)
+//! which holds each value at most once.
+//!
+//! Because both sets are sorted, combining two of them never needs a lookup per element. Instead
+//! we walk both at once, like the merge step of merge sort, always stepping past the smaller of the
+//! two current elements - and keeping or dropping it depending on the operation:
+//!
+//! ```text
+//!  a: [ 1, 3, 5 ]          1 < 2: only in a      3 > 2: only in b      3 = 3: in both
+//!  b: [ 2, 3, 8 ]          5 < 8: only in a      8: only in b
+//!
+//!  union:         [ 1, 2, 3, 5, 8 ]   everything
+//!  intersection:  [ 3 ]               only 'in both'
+//!  difference:    [ 1, 5 ]            only 'only in a'
+//! ```
+//!
+//! Each operation visits every element once - `O(n + m)` - and produces a set that is already
+//! sorted.
+
+use std::cmp::Ordering;
+use std::ops::{Deref, RangeBounds};
+
+use super::sorted_vec::SortedVec;
+
+/// A set of distinct values kept in sorted order
+///
+/// Dereferences to a sorted slice with no duplicates.
+///
+/// Should be expected to have performance characteristics of `O(log n)` for lookups, `O(n)` for
+/// inserts and removals, and `O(n + m)` for set operations.
+///
+/// # Examples
+///
+/// ```rust
+/// let read: SortedSet<&str> = vec!["1984", "Watchmen", "Fun Home"].into_iter().collect();
+/// let owned: SortedSet<&str> = vec!["Watchmen", "Maus"].into_iter().collect();
+///
+/// assert_eq!(read.intersection(&owned).as_slice(), &["Watchmen"]);
+/// assert_eq!(owned.difference(&read).as_slice(), &["Maus"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SortedSet<T> {
+    items: SortedVec<T>,
+}
+
+impl<T: Ord> SortedSet<T> {
+    /// Creates an empty set
+    pub fn new() -> Self {
+        SortedSet {
+            items: SortedVec::new(),
+        }
+    }
+
+    /// Returns the values as a sorted slice
+    pub fn as_slice(&self) -> &[T] {
+        self.items.as_slice()
+    }
+
+    /// Returns the values as a sorted `Vec`
+    pub fn into_vec(self) -> Vec<T> {
+        self.items.into_vec()
+    }
+
+    /// Adds a value - returns `false`, leaving the set unchanged, if it was already present
+    pub fn insert(&mut self, item: T) -> bool {
+        self.items.insert_unique(item)
+    }
+
+    /// Removes a value - returns `true` if it was present
+    pub fn remove(&mut self, item: &T) -> bool {
+        self.items.remove(item).is_some()
+    }
+
+    /// Returns `true` if the value is present
+    pub fn contains(&self, item: &T) -> bool {
+        self.items.contains(item)
+    }
+
+    /// Returns the number of values less than `item`
+    pub fn rank(&self, item: &T) -> usize {
+        self.items.rank(item)
+    }
+
+    /// Returns the value with `rank` smaller values before it
+    pub fn select(&self, rank: usize) -> Option<&T> {
+        self.items.select(rank)
+    }
+
+    /// Returns the values within a range, in order
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> &[T] {
+        self.items.range(range)
+    }
+
+    /// Removes every value
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+impl<T: Ord + Clone> SortedSet<T> {
+    /// Returns the values in either set
+    pub fn union(&self, other: &SortedSet<T>) -> SortedSet<T> {
+        self.combine(other, true, true, true)
+    }
+
+    /// Returns the values in both sets
+    pub fn intersection(&self, other: &SortedSet<T>) -> SortedSet<T> {
+        self.combine(other, false, true, false)
+    }
+
+    /// Returns the values in this set but not in `other`
+    pub fn difference(&self, other: &SortedSet<T>) -> SortedSet<T> {
+        self.combine(other, true, false, false)
+    }
+
+    /// Returns the values in exactly one of the sets
+    pub fn symmetric_difference(&self, other: &SortedSet<T>) -> SortedSet<T> {
+        self.combine(other, true, false, true)
+    }
+
+    /// Returns `true` if every value of this set is also in `other`
+    pub fn is_subset(&self, other: &SortedSet<T>) -> bool {
+        let (a, b) = (self.as_slice(), other.as_slice());
+        let mut j = 0;
+
+        for item in a {
+            // Step past the values of `other` smaller than `item` - `item` must be next
+            while j < b.len() && b[j] < *item {
+                j += 1;
+            }
+            if j == b.len() || b[j] != *item {
+                return false;
+            }
+            j += 1;
+        }
+        true
+    }
+
+    /// Walks both sets in step, keeping values found only in this set, in both, or only in
+    /// `other`, as asked
+    fn combine(&self, other: &SortedSet<T>, only_self: bool, both: bool, only_other: bool) -> Self {
+        let (a, b) = (self.as_slice(), other.as_slice());
+        let (mut i, mut j) = (0, 0);
+        let mut combined = Vec::new();
+
+        while i < a.len() && j < b.len() {
+            match a[i].cmp(&b[j]) {
+                Ordering::Less => {
+                    if only_self {
+                        combined.push(a[i].clone());
+                    }
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    if only_other {
+                        combined.push(b[j].clone());
+                    }
+                    j += 1;
+                }
+                Ordering::Equal => {
+                    if both {
+                        combined.push(a[i].clone());
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        if only_self {
+            combined.extend_from_slice(&a[i..]);
+        }
+        if only_other {
+            combined.extend_from_slice(&b[j..]);
+        }
+
+        // Already sorted and distinct
+        SortedSet {
+            items: SortedVec::from_sorted(combined),
+        }
+    }
+}
+
+impl<T> Deref for SortedSet<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T: Ord> Default for SortedSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for SortedSet<T> {
+    /// Sorts a vector and removes its duplicates
+    fn from(items: Vec<T>) -> Self {
+        let mut items = SortedVec::from(items);
+        items.dedup();
+        SortedSet { items }
+    }
+}
+
+impl<T: Ord> From<SortedSet<T>> for SortedVec<T> {
+    fn from(set: SortedSet<T>) -> Self {
+        set.items
+    }
+}
+
+impl<T: Ord> std::iter::FromIterator<T> for SortedSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().collect::<Vec<T>>().into()
+    }
+}
+
+impl<T: Ord> Extend<T> for SortedSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.items.extend(iter);
+        self.items.dedup();
+    }
+}
+
+impl<T> IntoIterator for SortedSet<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SortedSet<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::instrumentation::SearchStats;
+    use super::*;
+
+    fn set(items: &[u32]) -> SortedSet<u32> {
+        items.iter().copied().collect()
+    }
+
+    #[test]
+    fn values_are_held_once() {
+        let mut s = SortedSet::new();
+        assert!(s.insert(3));
+        assert!(s.insert(1));
+        assert!(!s.insert(3));
+        assert_eq!(s.as_slice(), &[1, 3]);
+
+        assert!(s.remove(&3));
+        assert!(!s.remove(&3));
+        assert_eq!(set(&[4, 2, 4, 2, 9]).as_slice(), &[2, 4, 9])
+    }
+
+    #[test]
+    fn inserts_binary_search_once() {
+        let stats = SearchStats::new();
+        let mut s: SortedSet<_> = stats
+            .track(&(0..1024).map(|i| i * 2).collect::<Vec<u32>>())
+            .into();
+        stats.reset();
+        assert!(s.insert(stats.item(701)));
+        // One lower bound over 1025 values, and one equality check
+        assert!(stats.comparisons() <= 12, "{}", stats.comparisons());
+
+        stats.reset();
+        assert!(!s.insert(stats.item(700)));
+        assert!(stats.comparisons() <= 12, "{}", stats.comparisons())
+    }
+
+    #[test]
+    fn set_operations_merge_in_order() {
+        let (a, b) = (set(&[1, 3, 5, 7]), set(&[2, 3, 7, 8]));
+        assert_eq!(a.union(&b).as_slice(), &[1, 2, 3, 5, 7, 8]);
+        assert_eq!(a.intersection(&b).as_slice(), &[3, 7]);
+        assert_eq!(a.difference(&b).as_slice(), &[1, 5]);
+        assert_eq!(b.difference(&a).as_slice(), &[2, 8]);
+        assert_eq!(a.symmetric_difference(&b).as_slice(), &[1, 2, 5, 8]);
+        assert!(a.intersection(&b).is_subset(&a));
+        assert!(!a.is_subset(&b))
+    }
+
+    #[test]
+    fn set_operations_agree_with_std() {
+        use std::collections::BTreeSet;
+        for mask_a in 0u32..64 {
+            for mask_b in 0u32..64 {
+                let a: Vec<u32> = (0..6).filter(|i| mask_a & (1 << i) != 0).collect();
+                let b: Vec<u32> = (0..6).filter(|i| mask_b & (1 << i) != 0).collect();
+                let (sa, sb) = (set(&a), set(&b));
+                let (ba, bb): (BTreeSet<u32>, BTreeSet<u32>) =
+                    (a.iter().copied().collect(), b.iter().copied().collect());
+
+                let expected: Vec<u32> = ba.union(&bb).copied().collect();
+                assert_eq!(sa.union(&sb).as_slice(), expected.as_slice());
+                let expected: Vec<u32> = ba.intersection(&bb).copied().collect();
+                assert_eq!(sa.intersection(&sb).as_slice(), expected.as_slice());
+                let expected: Vec<u32> = ba.difference(&bb).copied().collect();
+                assert_eq!(sa.difference(&sb).as_slice(), expected.as_slice());
+                assert_eq!(sa.is_subset(&sb), ba.is_subset(&bb));
+            }
+        }
+    }
+
+    #[test]
+    fn rank_select_and_range_follow_the_values() {
+        let s = set(&[10, 20, 30, 40]);
+        assert_eq!(s.rank(&25), 2);
+        assert_eq!(s.select(2), Some(&30));
+        assert_eq!(s.range(15..=30), &[20, 30]);
+
+        let mut extended = s.clone();
+        extended.extend(vec![20, 5]);
+        assert_eq!(extended.as_slice(), &[5, 10, 20, 30, 40])
+    }
+}
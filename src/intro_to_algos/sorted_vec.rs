@@ -0,0 +1,324 @@
+//! Sorted Vectors
+//!
+//! `sorted_vec` provides [SortedVec](struct.SortedVec.html) - a vector which keeps itself sorted,
+//! so that binary search is always safe to use on it.
+//!
+//! Every search in this module assumes a sorted list, and trusts the caller to have sorted it - a
+//! search over an unsorted list doesn't fail, it just quietly gives wrong answers. A `SortedVec`
+//! takes that job over: it has no way to add an element except in order, so the order can't be
+//! broken in the first place.
+//!
+//! Each insert binary searches for the element's place and shifts the elements after it along:
+//!
+//! ```text
+//!  insert 4:   [ 1, 3, 5, 8 ]    upper_bound(4) = 2
+//!                    -->  -->    5 and 8 shift right
+//!              [ 1, 3, 4, 5, 8 ]
+//! ```
+//!
+//! The shift makes inserts `O(n)`, against a tree's `O(log n)` - but the elements sit side by side
+//! in memory, so lookups, iteration and range queries are as fast as they can be, and for
+//! collections built once and read often that trade is worth making.
+
+use std::ops::{Bound, Deref, RangeBounds};
+
+use super::bounds::{equal_range, lower_bound, upper_bound};
+
+/// A vector kept in sorted order
+///
+/// Dereferences to a sorted slice, so that every slice method - and every search in this module -
+/// can be used on it directly.
+///
+/// Should be expected to have performance characteristics of `O(log n)` for lookups, and `O(n)`
+/// for inserts and removals.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut scores = SortedVec::new();
+/// for score in &[72, 95, 64, 88] {
+///     scores.insert(*score);
+/// }
+///
+/// assert_eq!(scores.as_slice(), &[64, 72, 88, 95]);
+/// assert_eq!(scores.rank(&88), 2);
+/// assert_eq!(scores.range(70..90), &[72, 88]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SortedVec<T> {
+    items: Vec<T>,
+}
+
+impl<T: Ord> SortedVec<T> {
+    /// Creates an empty vector
+    pub fn new() -> Self {
+        SortedVec { items: Vec::new() }
+    }
+
+    /// Creates an empty vector with room for `capacity` elements
+    pub fn with_capacity(capacity: usize) -> Self {
+        SortedVec {
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Wraps a vector already in sorted order, without sorting it again
+    pub(crate) fn from_sorted(items: Vec<T>) -> Self {
+        debug_assert!(items.windows(2).all(|pair| pair[0] <= pair[1]));
+        SortedVec { items }
+    }
+
+    /// Returns the elements as a sorted slice
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns the elements as a sorted `Vec`
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+
+    /// Inserts an element after any equal to it - returns the index it was inserted at
+    pub fn insert(&mut self, item: T) -> usize {
+        let index = upper_bound(&self.items, &item);
+        self.items.insert(index, item);
+        index
+    }
+
+    /// Inserts an element unless one equal to it is present - returns `false` if one was
+    ///
+    /// A single binary search finds both whether the element is present and where it belongs.
+    pub(crate) fn insert_unique(&mut self, item: T) -> bool {
+        let index = lower_bound(&self.items, &item);
+        if self.items.get(index) == Some(&item) {
+            return false;
+        }
+        self.items.insert(index, item);
+        true
+    }
+
+    /// Removes the first element equal to `item` - returns the removed element
+    pub fn remove(&mut self, item: &T) -> Option<T> {
+        let range = equal_range(&self.items, item);
+        if range.is_empty() {
+            return None;
+        }
+        Some(self.items.remove(range.start))
+    }
+
+    /// Removes and returns the element at `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_index(&mut self, index: usize) -> T {
+        self.items.remove(index)
+    }
+
+    /// Returns `true` if an element equal to `item` is present
+    pub fn contains(&self, item: &T) -> bool {
+        !self.equal_range(item).is_empty()
+    }
+
+    /// Returns the number of elements equal to `item`
+    pub fn count(&self, item: &T) -> usize {
+        self.equal_range(item).len()
+    }
+
+    /// Returns the elements equal to `item`
+    pub fn equal_range(&self, item: &T) -> &[T] {
+        &self.items[equal_range(&self.items, item)]
+    }
+
+    /// Returns the number of elements less than `item` - its rank among the elements
+    pub fn rank(&self, item: &T) -> usize {
+        lower_bound(&self.items, item)
+    }
+
+    /// Returns the element with `rank` smaller elements before it - the inverse of
+    /// [rank](struct.SortedVec.html#method.rank)
+    pub fn select(&self, rank: usize) -> Option<&T> {
+        self.items.get(rank)
+    }
+
+    /// Returns the elements within a range of values, in order
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let years: SortedVec<u32> = vec![1949, 1953, 1985, 2006].into();
+    /// assert_eq!(years.range(1950..=1985), &[1953, 1985]);
+    /// assert_eq!(years.range(..1950), &[1949]);
+    /// ```
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> &[T] {
+        let start = match range.start_bound() {
+            Bound::Included(start) => lower_bound(&self.items, start),
+            Bound::Excluded(start) => upper_bound(&self.items, start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => upper_bound(&self.items, end),
+            Bound::Excluded(end) => lower_bound(&self.items, end),
+            Bound::Unbounded => self.items.len(),
+        };
+        &self.items[start..end.max(start)]
+    }
+
+    /// Merges another sorted vector into this one, keeping both vectors' elements
+    ///
+    /// Should be expected to have performance characteristics of `O(n + m)`.
+    pub fn merge(&mut self, other: SortedVec<T>) {
+        let left = std::mem::take(&mut self.items);
+        self.items = merge(left, other.items);
+    }
+
+    /// Removes consecutive equal elements, leaving one of each
+    pub fn dedup(&mut self) {
+        self.items.dedup();
+    }
+
+    /// Keeps only the elements for which `keep` returns `true`
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, keep: F) {
+        self.items.retain(keep);
+    }
+
+    /// Removes every element
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+/// Merges two sorted vectors into one, taking from `left` first among equal elements
+fn merge<T: Ord>(left: Vec<T>, right: Vec<T>) -> Vec<T> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if r < l {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+impl<T> Deref for SortedVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T: Ord> Default for SortedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for SortedVec<T> {
+    /// Sorts a vector - stably, so equal elements keep their order
+    fn from(mut items: Vec<T>) -> Self {
+        items.sort();
+        SortedVec { items }
+    }
+}
+
+impl<T: Ord> std::iter::FromIterator<T> for SortedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().collect::<Vec<T>>().into()
+    }
+}
+
+impl<T: Ord> Extend<T> for SortedVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.merge(iter.into_iter().collect());
+    }
+}
+
+impl<T> IntoIterator for SortedVec<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SortedVec<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_sorted<T: Ord>(items: &[T]) -> bool {
+        items.windows(2).all(|pair| pair[0] <= pair[1])
+    }
+
+    #[test]
+    fn inserts_keep_the_order() {
+        let mut v = SortedVec::new();
+        for (i, item) in [5, 1, 4, 1, 9, 2, 6].iter().enumerate() {
+            let index = v.insert(*item);
+            assert_eq!(v[index], *item);
+            assert_eq!(v.len(), i + 1);
+            assert!(is_sorted(&v));
+        }
+        assert_eq!(v.as_slice(), &[1, 1, 2, 4, 5, 6, 9])
+    }
+
+    #[test]
+    fn inserts_go_after_equal_elements_and_removes_take_the_first() {
+        let mut v = SortedVec::from(vec![3, 1, 3, 2]);
+        assert_eq!(v.insert(3), 4);
+        assert_eq!(v.equal_range(&3), &[3, 3, 3]);
+
+        assert_eq!(v.remove(&3), Some(3));
+        assert_eq!(v.remove(&7), None);
+        assert_eq!(v.count(&3), 2);
+        assert!(!v.contains(&7))
+    }
+
+    #[test]
+    fn rank_and_select_are_inverses() {
+        let v: SortedVec<u32> = (0..50).map(|i| i * 2).collect();
+        for rank in 0..v.len() {
+            assert_eq!(v.rank(v.select(rank).unwrap()), rank);
+        }
+        assert_eq!(v.rank(&7), 4);
+        assert_eq!(v.select(50), None)
+    }
+
+    #[test]
+    fn ranges_respect_every_kind_of_bound() {
+        let v: SortedVec<u32> = vec![1, 3, 3, 5, 8].into();
+        assert_eq!(v.range(3..5), &[3, 3]);
+        assert_eq!(v.range(3..=5), &[3, 3, 5]);
+        assert_eq!(v.range((Bound::Excluded(3), Bound::Unbounded)), &[5, 8]);
+        assert_eq!(v.range(..), v.as_slice());
+        assert!(v.range((Bound::Included(6), Bound::Excluded(2))).is_empty())
+    }
+
+    #[test]
+    fn merge_and_dedup_combine_vectors() {
+        let mut v: SortedVec<u32> = vec![1, 4, 4, 9].into();
+        v.merge(vec![0, 4, 10].into());
+        assert_eq!(v.as_slice(), &[0, 1, 4, 4, 4, 9, 10]);
+
+        v.dedup();
+        assert_eq!(v.as_slice(), &[0, 1, 4, 9, 10]);
+        v.extend(vec![5, 2]);
+        assert_eq!(v.as_slice(), &[0, 1, 2, 4, 5, 9, 10])
+    }
+}
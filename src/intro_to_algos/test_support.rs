@@ -0,0 +1,37 @@
+//! Fixtures shared by the tests of the searches in this module
+
+// Every sorted list of up to 7 elements drawn from 0..5, duplicates included - each list once
+pub(crate) fn small_sorted_lists() -> Vec<Vec<u32>> {
+    let mut lists = vec![vec![]];
+    for len in 1..=7u32 {
+        for code in 0..5u32.pow(len) {
+            let mut list: Vec<u32> = (0..len).map(|i| code / 5u32.pow(i) % 5).collect();
+            list.sort_unstable();
+            lists.push(list);
+        }
+    }
+    lists.sort();
+    lists.dedup();
+    lists
+}
+
+// A xorshift generator, seeded so any failure can be replayed
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        XorShift(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // A value in `0..bound`
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}